@@ -94,10 +94,48 @@ fn normal_benchmarks(c: &mut Criterion) {
     group.finish();
 }
 
+fn group_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("group");
+
+    let build_grid = || {
+        let grid = shape::group().build();
+        for x in 0..20 {
+            for z in 0..20 {
+                grid.add_child(
+                    shape::sphere()
+                        .transform(
+                            transform::translation(x * 2, 0, z * 2)
+                                * transform::scaling(0.5, 0.5, 0.5),
+                        )
+                        .build(),
+                );
+            }
+        }
+        grid
+    };
+
+    let brute_force = build_grid();
+    let accelerated = build_grid();
+    accelerated.build_bvh();
+
+    let r = ray(point(-5.0, 0.0, -5.0), vector(1.0, 0.0, 1.0).normalize());
+
+    group.bench_function("grid_400_brute_force", |b| {
+        b.iter(|| brute_force.intersect(hint::black_box(r)));
+    });
+
+    group.bench_function("grid_400_bvh", |b| {
+        b.iter(|| accelerated.intersect(hint::black_box(r)));
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     intersection_benchmarks,
     hit_benchmarks,
-    normal_benchmarks
+    normal_benchmarks,
+    group_benchmarks
 );
 criterion_main!(benches);
@@ -1,7 +1,7 @@
 use std::hint;
 
 use criterion::{Criterion, criterion_group, criterion_main};
-use ray_tracer::{Material, color, material, point, point_light, shape, vector};
+use ray_tracer::{Material, area_light, color, material, point, point_light, shape, vector};
 
 fn lighting_benchmarks(c: &mut Criterion) {
     let mut group = c.benchmark_group("lighting");
@@ -21,7 +21,7 @@ fn lighting_benchmarks(c: &mut Criterion) {
                 hint::black_box(position),
                 hint::black_box(eyev),
                 hint::black_box(normalv),
-                hint::black_box(false),
+                hint::black_box(1.0),
             )
         });
     });
@@ -34,7 +34,7 @@ fn lighting_benchmarks(c: &mut Criterion) {
                 hint::black_box(position),
                 hint::black_box(eyev),
                 hint::black_box(normalv),
-                hint::black_box(true),
+                hint::black_box(0.0),
             )
         });
     });
@@ -48,7 +48,29 @@ fn lighting_benchmarks(c: &mut Criterion) {
                 hint::black_box(position),
                 hint::black_box(eyev),
                 hint::black_box(normalv),
-                hint::black_box(false),
+                hint::black_box(1.0),
+            )
+        });
+    });
+
+    let area = area_light(
+        point(-1.0, 10.0, -10.0),
+        vector(2.0, 0.0, 0.0),
+        4,
+        vector(0.0, 2.0, 0.0),
+        4,
+        color(1, 1, 1),
+    )
+    .build();
+    group.bench_function("phong_area_light", |b| {
+        b.iter(|| {
+            m.lighting(
+                hint::black_box(&object),
+                hint::black_box(&area),
+                hint::black_box(position),
+                hint::black_box(eyev),
+                hint::black_box(normalv),
+                hint::black_box(1.0),
             )
         });
     });
@@ -20,7 +20,9 @@ fn build_test_world() -> World {
 
     World::builder()
         .objects(vec![floor, sphere])
-        .lights(vec![point_light(point(-10.0, 10.0, -10.0), color(1, 1, 1))])
+        .lights(vec![
+            point_light(point(-10.0, 10.0, -10.0), color(1, 1, 1)).into(),
+        ])
         .build()
 }
 
@@ -19,7 +19,10 @@ fn world_benchmarks(c: &mut Criterion) {
 
     group.bench_function("is_shadowed_for_light", |b| {
         b.iter(|| {
-            world.is_shadowed_for_light(hint::black_box(point(0.0, 10.0, 0.0)), &world.lights[0])
+            world.is_shadowed_for_light(
+                hint::black_box(point(0.0, 10.0, 0.0)),
+                world.lights[0].as_ref(),
+            )
         });
     });
 
@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -16,7 +19,7 @@ fn main() -> Result<()> {
                 .pattern(
                     checkers_pattern(color(0.9, 0.9, 0.9), color(0.1, 0.1, 0.1))
                         .transform(transform::scaling(0.5, 0.5, 0.5))
-                        .build(),
+                        .build()?,
                 )
                 .specular(0.0),
         )
@@ -29,7 +32,7 @@ fn main() -> Result<()> {
                 .pattern(
                     stripe_pattern(color(0.8, 0.8, 1), color(0.6, 0.6, 0.9))
                         .transform(transform::scaling(0.25, 0.25, 0.25))
-                        .build(),
+                        .build()?,
                 )
                 .specular(0.0),
         )
@@ -44,7 +47,7 @@ fn main() -> Result<()> {
                         .transform(
                             transform::scaling(0.2, 0.2, 0.2) * transform::rotation_z(FRAC_PI_4),
                         )
-                        .build(),
+                        .build()?,
                 )
                 .diffuse(0.7)
                 .specular(0.3),
@@ -58,7 +61,7 @@ fn main() -> Result<()> {
                 .pattern(
                     gradient_pattern(color(1, 0.5, 0), color(0.5, 0, 0.5))
                         .transform(transform::translation(1, 0, 0) * transform::scaling(2, 2, 2))
-                        .build(),
+                        .build()?,
                 )
                 .diffuse(0.7)
                 .specular(0.3),
@@ -74,7 +77,7 @@ fn main() -> Result<()> {
                         .transform(
                             transform::scaling(0.15, 0.15, 0.15) * transform::rotation_x(FRAC_PI_2),
                         )
-                        .build(),
+                        .build()?,
                 )
                 .diffuse(0.7)
                 .specular(0.3),
@@ -82,7 +85,9 @@ fn main() -> Result<()> {
         .build();
 
     let world = World::builder()
-        .lights(vec![point_light(point(-10, 10, -10), color(1, 1, 1))])
+        .lights(vec![
+            point_light(point(-10, 10, -10), color(1, 1, 1)).into(),
+        ])
         .objects(vec![floor, backdrop, middle, right, left])
         .build();
 
@@ -97,8 +102,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
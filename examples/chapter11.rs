@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_3};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_3},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -13,7 +16,9 @@ fn main() -> Result<()> {
     let floor = plane()
         .material(
             Material::builder()
-                .pattern(checkers_pattern(color(0.35, 0.35, 0.35), color(0.65, 0.65, 0.65)).build())
+                .pattern(
+                    checkers_pattern(color(0.35, 0.35, 0.35), color(0.65, 0.65, 0.65)).build()?,
+                )
                 .reflective(0.4)
                 .specular(0.0),
         )
@@ -23,7 +28,9 @@ fn main() -> Result<()> {
         .transform(transform::translation(0, 0, 10) * transform::rotation_x(FRAC_PI_2))
         .material(
             Material::builder()
-                .pattern(checkers_pattern(color(0.15, 0.15, 0.15), color(0.85, 0.85, 0.85)).build())
+                .pattern(
+                    checkers_pattern(color(0.15, 0.15, 0.15), color(0.85, 0.85, 0.85)).build()?,
+                )
                 .specular(0.0),
         )
         .build();
@@ -79,7 +86,9 @@ fn main() -> Result<()> {
         .build();
 
     let world = World::builder()
-        .lights(vec![point_light(point(-10, 10, -10), color(1, 1, 1))])
+        .lights(vec![
+            point_light(point(-10, 10, -10), color(1, 1, 1)).into(),
+        ])
         .objects(vec![
             floor,
             backdrop,
@@ -101,8 +110,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
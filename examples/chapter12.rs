@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_3, FRAC_PI_4};
+use std::{
+    f32::consts::{FRAC_PI_3, FRAC_PI_4},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -14,7 +17,7 @@ fn table_material() -> Material {
         .build()
 }
 
-fn build_room() -> Vec<Shape> {
+fn build_room() -> Result<Vec<Shape>> {
     let floor = cube()
         .transform(transform::scaling(20, 0.1, 20))
         .material(
@@ -22,7 +25,7 @@ fn build_room() -> Vec<Shape> {
                 .pattern(
                     checkers_pattern(color(0.1, 0.1, 0.1), color(0.9, 0.9, 0.9))
                         .transform(transform::scaling(0.1, 0.1, 0.1))
-                        .build(),
+                        .build()?,
                 )
                 .reflective(0.3)
                 .specular(0.8),
@@ -65,7 +68,7 @@ fn build_room() -> Vec<Shape> {
         )
         .build();
 
-    vec![floor, back_wall, left_wall, right_wall, ceiling]
+    Ok(vec![floor, back_wall, left_wall, right_wall, ceiling])
 }
 
 fn build_table() -> Vec<Shape> {
@@ -192,13 +195,13 @@ fn build_floor_boxes() -> Vec<Shape> {
 }
 
 fn main() -> Result<()> {
-    let mut objects = build_room();
+    let mut objects = build_room()?;
     objects.extend(build_table());
     objects.extend(build_table_cubes());
     objects.extend(build_floor_boxes());
 
     let world = World::builder()
-        .lights(vec![point_light(point(-8, 8, -8), color(1, 1, 1))])
+        .lights(vec![point_light(point(-8, 8, -8), color(1, 1, 1)).into()])
         .objects(objects)
         .build();
 
@@ -213,8 +216,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
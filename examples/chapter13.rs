@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_3};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_3},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -9,19 +12,19 @@ use ray_tracer::{
     transform, vector,
 };
 
-fn build_floor() -> Shape {
-    plane()
+fn build_floor() -> Result<Shape> {
+    Ok(plane()
         .material(
             Material::builder()
                 .pattern(
                     checkers_pattern(color(0.15, 0.15, 0.15), color(0.85, 0.85, 0.85))
                         .transform(transform::scaling(0.5, 0.5, 0.5))
-                        .build(),
+                        .build()?,
                 )
                 .reflective(0.2)
                 .specular(0.0),
         )
-        .build()
+        .build())
 }
 
 fn build_pillars() -> Vec<Shape> {
@@ -177,13 +180,13 @@ fn build_glass_cylinder() -> Shape {
 }
 
 fn main() -> Result<()> {
-    let mut objects = vec![build_floor(), build_glass_cylinder()];
+    let mut objects = vec![build_floor()?, build_glass_cylinder()];
     objects.extend(build_pillars());
     objects.extend(build_cones());
     objects.extend(build_decorative_cylinders());
 
     let world = World::builder()
-        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1))])
+        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1)).into()])
         .objects(objects)
         .build();
 
@@ -198,8 +201,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_3, FRAC_PI_6};
+use std::{
+    f32::consts::{FRAC_PI_3, FRAC_PI_6},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -58,19 +61,19 @@ fn hexagon() -> Shape {
     hex
 }
 
-fn build_floor() -> Shape {
-    plane()
+fn build_floor() -> Result<Shape> {
+    Ok(plane()
         .material(
             Material::builder()
                 .pattern(
                     checkers_pattern(color(0.2, 0.2, 0.2), color(0.8, 0.8, 0.8))
                         .transform(transform::scaling(0.5, 0.5, 0.5))
-                        .build(),
+                        .build()?,
                 )
                 .reflective(0.3)
                 .specular(0.0),
         )
-        .build()
+        .build())
 }
 
 fn main() -> Result<()> {
@@ -88,8 +91,8 @@ fn main() -> Result<()> {
     );
 
     let world = World::builder()
-        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1))])
-        .objects(vec![build_floor(), hex1, hex2, hex3])
+        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1)).into()])
+        .objects(vec![build_floor()?, hex1, hex2, hex3])
         .build();
 
     let camera = camera(1000, 500)
@@ -103,8 +106,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
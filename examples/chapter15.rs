@@ -1,6 +1,6 @@
 use std::{
     f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_6},
-    fs,
+    fs, io,
 };
 
 use anyhow::Result;
@@ -9,19 +9,19 @@ use ray_tracer::{
     shape::plane, transform, vector,
 };
 
-fn build_floor() -> ray_tracer::Shape {
-    plane()
+fn build_floor() -> Result<ray_tracer::Shape> {
+    Ok(plane()
         .material(
             Material::builder()
                 .pattern(
                     checkers_pattern(color(0.2, 0.2, 0.2), color(0.8, 0.8, 0.8))
                         .transform(transform::scaling(0.5, 0.5, 0.5))
-                        .build(),
+                        .build()?,
                 )
                 .reflective(0.2)
                 .specular(0.0),
         )
-        .build()
+        .build())
 }
 
 fn main() -> Result<()> {
@@ -44,8 +44,8 @@ fn main() -> Result<()> {
     );
 
     let world = World::builder()
-        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1))])
-        .objects(vec![build_floor(), teapot])
+        .lights(vec![point_light(point(-5, 5, -5), color(1, 1, 1)).into()])
+        .objects(vec![build_floor()?, teapot])
         .build();
 
     let camera = camera(1000, 500)
@@ -59,8 +59,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::f32::consts::FRAC_PI_3;
+use std::{f32::consts::FRAC_PI_3, io};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -149,19 +149,19 @@ fn die(die_color: Color, pip_color: Color) -> Shape {
     csg(CsgOperation::Difference, &body, &combined_pips)
 }
 
-fn build_floor() -> Shape {
-    ray_tracer::shape::plane()
+fn build_floor() -> Result<Shape> {
+    Ok(ray_tracer::shape::plane()
         .material(
             Material::builder()
                 .pattern(
                     checkers_pattern(color(0.3, 0.3, 0.3), color(0.7, 0.7, 0.7))
                         .transform(transform::scaling(0.5, 0.5, 0.5))
-                        .build(),
+                        .build()?,
                 )
                 .reflective(0.1)
                 .specular(0.0),
         )
-        .build()
+        .build())
 }
 
 fn main() -> Result<()> {
@@ -192,8 +192,8 @@ fn main() -> Result<()> {
     );
 
     let world = World::builder()
-        .lights(vec![point_light(point(-5, 8, -5), color(1, 1, 1))])
-        .objects(vec![build_floor(), blue_die, maroon_die, green_die])
+        .lights(vec![point_light(point(-5, 8, -5), color(1, 1, 1)).into()])
+        .objects(vec![build_floor()?, blue_die, maroon_die, green_die])
         .build();
 
     let camera = camera(1000, 500)
@@ -207,8 +207,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use anyhow::Result;
 use ray_tracer::{Point, Vector, canvas, color, point, vector};
 
@@ -47,8 +49,7 @@ fn main() -> Result<()> {
         projectile = tick(&environment, &projectile);
     }
 
-    let ppm = c.to_ppm()?;
-    print!("{ppm}");
+    c.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
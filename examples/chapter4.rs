@@ -1,4 +1,4 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, io};
 
 use anyhow::Result;
 use ray_tracer::{canvas, color::RED, point, transform::rotation_y};
@@ -32,8 +32,7 @@ fn main() -> Result<()> {
         }
     }
 
-    let ppm = c.to_ppm()?;
-    print!("{ppm}");
+    c.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use anyhow::Result;
 use ray_tracer::{canvas, color::RED, hit, point, ray, shape::sphere};
 
@@ -35,8 +37,7 @@ fn main() -> Result<()> {
         }
     }
 
-    let ppm = c.to_ppm()?;
-    print!("{ppm}");
+    c.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use anyhow::Result;
 use ray_tracer::{Material, canvas, color, hit, point, point_light, ray, shape::sphere};
 
@@ -40,14 +42,13 @@ fn main() -> Result<()> {
                 let eye = -r.direction;
                 let material = intersection.object.material();
                 let pixel_color =
-                    material.lighting(&intersection.object, &light, hit_point, eye, normal, false);
+                    material.lighting(&intersection.object, &light, hit_point, eye, normal, 1.0);
                 c.write_pixel(x, y, pixel_color)?;
             }
         }
     }
 
-    let ppm = c.to_ppm()?;
-    print!("{ppm}");
+    c.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,4 +1,7 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4},
+    io,
+};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -62,7 +65,9 @@ fn main() -> Result<()> {
         .build();
 
     let world = World::builder()
-        .lights(vec![point_light(point(-10, 10, -10), color(1, 1, 1))])
+        .lights(vec![
+            point_light(point(-10, 10, -10), color(1, 1, 1)).into(),
+        ])
         .objects(vec![floor, left_wall, right_wall, middle, right, left])
         .build();
 
@@ -77,8 +82,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::f32::consts::FRAC_PI_2;
+use std::{f32::consts::FRAC_PI_2, io};
 
 use anyhow::Result;
 use ray_tracer::{
@@ -95,8 +95,8 @@ fn glass_sphere() -> Shape {
 
 fn main() -> Result<()> {
     let lights = vec![
-        point_light(point(50, 100, -50), color(1, 1, 1)), // Main light
-        point_light(point(-400, 50, -10), color(0.2, 0.2, 0.2)), // Secondary fill light
+        point_light(point(50, 100, -50), color(1, 1, 1)).into(), // Main light
+        point_light(point(-400, 50, -10), color(0.2, 0.2, 0.2)).into(), // Secondary fill light
     ];
 
     let objects = vec![
@@ -187,8 +187,7 @@ fn main() -> Result<()> {
         .build();
 
     let canvas = camera.render(&world);
-    let ppm = canvas.to_ppm()?;
-    print!("{ppm}");
+    canvas.write_png(io::stdout().lock()).write()?;
 
     Ok(())
 }
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::{
+    Canvas, Color, ExrLayer, Shape, Vector, canvas_with_pixels, color, intersection::Computations,
+};
+
+/// Selects the arbitrary output variables (AOVs) that
+/// `Camera::render_with_aovs` produces alongside the beauty image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Aovs {
+    /// Distance along the primary ray to the first hit.
+    pub depth: bool,
+    /// World-space surface normal at the first hit, facing the camera.
+    pub normal: bool,
+    /// Unlit material colour at the first hit.
+    pub albedo: bool,
+    /// ID of the hit primitive, as numbered by `World::object_ids`.
+    pub object_id: bool,
+}
+
+impl Aovs {
+    pub const ALL: Aovs = Aovs {
+        depth: true,
+        normal: true,
+        albedo: true,
+        object_id: true,
+    };
+
+    #[must_use]
+    pub fn any(self) -> bool {
+        self.depth || self.normal || self.albedo || self.object_id
+    }
+}
+
+/// Per-pixel AOV buffers in row order. Buffers that were not requested are
+/// `None`. Pixels whose primary ray misses everything have an infinite
+/// depth, a zero normal and albedo, and an object ID of `0`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AovBuffers {
+    pub depth: Option<Vec<f32>>,
+    /// Normals stored with x, y and z in the red, green and blue channels.
+    pub normal: Option<Canvas>,
+    pub albedo: Option<Canvas>,
+    pub object_id: Option<Vec<u32>>,
+}
+
+impl AovBuffers {
+    /// Converts the buffers into OpenEXR layers named `depth.Z`,
+    /// `normal.{X,Y,Z}`, `albedo.{R,G,B}` and `object.id`.
+    #[must_use]
+    pub fn exr_layers(&self) -> Vec<ExrLayer> {
+        let mut layers = vec![];
+        if let Some(depth) = &self.depth {
+            layers.push(ExrLayer::new("depth").channel("Z", depth));
+        }
+        if let Some(normal) = &self.normal {
+            layers.push(ExrLayer::xyz("normal", normal));
+        }
+        if let Some(albedo) = &self.albedo {
+            layers.push(ExrLayer::rgb("albedo", albedo));
+        }
+        if let Some(object_id) = &self.object_id {
+            #[allow(clippy::cast_precision_loss)]
+            let ids: Vec<f32> = object_id.iter().map(|&id| id as f32).collect();
+            layers.push(ExrLayer::new("object").channel("id", &ids));
+        }
+        layers
+    }
+
+    /// Gathers the requested buffers from one sample per pixel, looking up
+    /// each hit object in `ids`.
+    pub(crate) fn from_samples(
+        aovs: Aovs,
+        width: usize,
+        height: usize,
+        samples: &[Option<AovSample>],
+        ids: &HashMap<Shape, u32>,
+    ) -> AovBuffers {
+        let field = |f: &dyn Fn(&AovSample) -> Color| {
+            let pixels = samples
+                .iter()
+                .map(|sample| sample.as_ref().map_or(color(0, 0, 0), f))
+                .collect();
+            canvas_with_pixels(width, height, pixels)
+        };
+
+        AovBuffers {
+            depth: aovs.depth.then(|| {
+                samples
+                    .iter()
+                    .map(|sample| sample.as_ref().map_or(f32::INFINITY, |s| s.depth))
+                    .collect()
+            }),
+            normal: aovs
+                .normal
+                .then(|| field(&|s| color(s.normal.x(), s.normal.y(), s.normal.z()))),
+            albedo: aovs.albedo.then(|| field(&|s| s.albedo)),
+            object_id: aovs.object_id.then(|| {
+                samples
+                    .iter()
+                    .map(|sample| {
+                        sample
+                            .as_ref()
+                            .and_then(|s| ids.get(&s.object))
+                            .copied()
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// Surface data at the first hit along a primary ray.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AovSample {
+    pub depth: f32,
+    pub normal: Vector,
+    pub albedo: Color,
+    pub object: Shape,
+}
+
+impl AovSample {
+    pub(crate) fn from_computations(comps: &Computations) -> AovSample {
+        let material = comps.object.material();
+        let albedo = material.pattern.as_ref().map_or(material.color, |p| {
+            p.pattern_at_shape(&comps.object, comps.point)
+        });
+
+        AovSample {
+            depth: comps.time,
+            normal: comps.normalv,
+            albedo,
+            object: comps.object.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, canvas, default_world, point, ray, shape::sphere, vector};
+
+    #[test]
+    fn sampling_the_first_hit() {
+        let w = default_world();
+        let r = ray(point(0, 0, -5), vector(0, 0, 1));
+        let sample = w.color_and_aov_at(r, 0, true).1.unwrap();
+        assert_relative_eq!(sample.depth, 4.0, epsilon = EPSILON);
+        assert_eq!(sample.normal, vector(0, 0, -1));
+        assert_eq!(sample.albedo, color(0.8, 1.0, 0.6));
+        assert_eq!(sample.object, w.objects[0]);
+    }
+
+    #[test]
+    fn sampling_a_miss() {
+        let w = default_world();
+        let r = ray(point(0, 0, -5), vector(0, 1, 0));
+        assert_eq!(w.color_and_aov_at(r, 0, true), (color(0, 0, 0), None));
+    }
+
+    #[test]
+    fn buffers_hold_only_requested_passes() {
+        let object = sphere().build();
+        let sample = AovSample {
+            depth: 2.0,
+            normal: vector(0, 1, 0),
+            albedo: color(0.5, 0.5, 0.5),
+            object: object.clone(),
+        };
+        let ids = HashMap::from([(object, 3)]);
+        let samples = [Some(sample), None];
+        let aovs = Aovs {
+            depth: true,
+            object_id: true,
+            ..Aovs::default()
+        };
+        let buffers = AovBuffers::from_samples(aovs, 2, 1, &samples, &ids);
+        assert_eq!(buffers.depth, Some(vec![2.0, f32::INFINITY]));
+        assert_eq!(buffers.object_id, Some(vec![3, 0]));
+        assert_eq!(buffers.normal, None);
+        assert_eq!(buffers.albedo, None);
+
+        let buffers = AovBuffers::from_samples(Aovs::ALL, 2, 1, &samples, &ids);
+        let normal = buffers.normal.unwrap();
+        assert_eq!(normal.pixel_at(0, 0).unwrap(), color(0, 1, 0));
+        assert_eq!(normal.pixel_at(1, 0).unwrap(), color(0, 0, 0));
+        assert_eq!(
+            buffers.albedo.unwrap().pixel_at(0, 0).unwrap(),
+            color(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn buffers_as_exr_layers() {
+        let buffers = AovBuffers {
+            depth: Some(vec![1.0]),
+            normal: Some(canvas(1, 1)),
+            albedo: None,
+            object_id: Some(vec![7]),
+        };
+        let layers = buffers.exr_layers();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[0], ExrLayer::new("depth").channel("Z", &[1.0]));
+        assert_eq!(layers[2], ExrLayer::new("object").channel("id", &[7.0]));
+    }
+}
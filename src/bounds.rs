@@ -0,0 +1,390 @@
+use crate::{Matrix4, Point, Ray, point};
+
+#[must_use]
+pub fn bounding_box(min: Point, max: Point) -> BoundingBox {
+    BoundingBox { min, max }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    /// Returns a box that contains nothing. Adding a point or box to it
+    /// yields exactly that point or box.
+    #[must_use]
+    pub fn empty() -> Self {
+        bounding_box(
+            point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        )
+    }
+
+    /// Returns a box that extends to infinity along every axis.
+    #[must_use]
+    pub fn infinite() -> Self {
+        bounding_box(
+            point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        )
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.min.x() > self.max.x() || self.min.y() > self.max.y() || self.min.z() > self.max.z()
+    }
+
+    /// Returns true if the box is non-empty and every extent is finite.
+    #[must_use]
+    pub fn is_finite(&self) -> bool {
+        !self.is_empty()
+            && self.min.data.as_array()[..3]
+                .iter()
+                .chain(&self.max.data.as_array()[..3])
+                .all(|n| n.is_finite())
+    }
+
+    pub fn add_point(&mut self, p: Point) {
+        self.min = point(
+            self.min.x().min(p.x()),
+            self.min.y().min(p.y()),
+            self.min.z().min(p.z()),
+        );
+        self.max = point(
+            self.max.x().max(p.x()),
+            self.max.y().max(p.y()),
+            self.max.z().max(p.z()),
+        );
+    }
+
+    pub fn add_box(&mut self, other: &BoundingBox) {
+        if other.is_empty() {
+            return;
+        }
+
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    #[must_use]
+    pub fn centroid(&self) -> Point {
+        point(
+            (self.min.x() + self.max.x()) * 0.5,
+            (self.min.y() + self.max.y()) * 0.5,
+            (self.min.z() + self.max.z()) * 0.5,
+        )
+    }
+
+    #[must_use]
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x() * extent.y() + extent.y() * extent.z() + extent.z() * extent.x())
+    }
+
+    /// Returns the axis-aligned box enclosing this box after it has been
+    /// transformed by `matrix`.
+    ///
+    /// Each output extent is accumulated from the matrix terms directly
+    /// rather than by transforming the eight corners, so infinite extents
+    /// never get multiplied by zero and turned into NaN.
+    #[must_use]
+    pub fn transform(&self, matrix: Matrix4) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let old_min = self.min.data.as_array();
+        let old_max = self.max.data.as_array();
+        let mut min = [matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]];
+        let mut max = min;
+
+        for i in 0..3 {
+            // Rotations leave rounding residues where a coefficient should be
+            // zero; those must not smear an infinite extent onto an axis the
+            // shape is actually flat along. Only values at rounding level
+            // relative to the rest of the row are snapped, so genuine small
+            // rotations still spread the infinite extent.
+            let scale = (0..3).map(|k| matrix[(i, k)].abs()).fold(0.0, f32::max);
+            for j in 0..3 {
+                let m = matrix[(i, j)];
+                let unbounded = old_min[j].is_infinite() || old_max[j].is_infinite();
+                if m == 0.0 || (unbounded && m.abs() < f32::EPSILON * scale) {
+                    continue;
+                }
+
+                let a = m * old_min[j];
+                let b = m * old_max[j];
+                min[i] += a.min(b);
+                max[i] += a.max(b);
+            }
+        }
+
+        bounding_box(point(min[0], min[1], min[2]), point(max[0], max[1], max[2]))
+    }
+
+    /// Tests whether the infinite line through `ray` passes through the box.
+    /// Intersections behind the ray origin count, matching `Shape::intersect`.
+    #[must_use]
+    pub fn intersects(&self, ray: Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let (xtmin, xtmax) = check_axis(
+            ray.origin.x(),
+            ray.direction.x(),
+            self.min.x(),
+            self.max.x(),
+        );
+        let (ytmin, ytmax) = check_axis(
+            ray.origin.y(),
+            ray.direction.y(),
+            self.min.y(),
+            self.max.y(),
+        );
+        let (ztmin, ztmax) = check_axis(
+            ray.origin.z(),
+            ray.direction.z(),
+            self.min.z(),
+            self.max.z(),
+        );
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+
+        tmin <= tmax
+    }
+}
+
+fn check_axis(origin: f32, direction: f32, min: f32, max: f32) -> (f32, f32) {
+    let inverse = direction.recip();
+    let tmin = (min - origin) * inverse;
+    let tmax = (max - origin) * inverse;
+
+    // A ray lying exactly on a slab boundary produces 0 * inf = NaN; treat
+    // that axis as unconstrained.
+    let tmin = if tmin.is_nan() {
+        f32::NEG_INFINITY
+    } else {
+        tmin
+    };
+    let tmax = if tmax.is_nan() { f32::INFINITY } else { tmax };
+
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, identity_matrix, ray, transform, vector};
+
+    #[test]
+    fn creating_empty_bounding_box() {
+        let b = BoundingBox::empty();
+        assert!(b.is_empty());
+        assert_eq!(b.min, point(f32::INFINITY, f32::INFINITY, f32::INFINITY));
+        assert_eq!(
+            b.max,
+            point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn creating_bounding_box_with_volume() {
+        let b = bounding_box(point(-1, -2, -3), point(3, 2, 1));
+        assert!(!b.is_empty());
+        assert!(b.is_finite());
+        assert_eq!(b.min, point(-1, -2, -3));
+        assert_eq!(b.max, point(3, 2, 1));
+    }
+
+    #[test]
+    fn adding_points_to_empty_bounding_box() {
+        let mut b = BoundingBox::empty();
+        b.add_point(point(-5, 2, 0));
+        b.add_point(point(7, 0, -3));
+        assert_eq!(b.min, point(-5, 0, -3));
+        assert_eq!(b.max, point(7, 2, 0));
+    }
+
+    #[test]
+    fn adding_one_bounding_box_to_another() {
+        let mut b1 = bounding_box(point(-5, -2, 0), point(7, 4, 4));
+        let b2 = bounding_box(point(8, -7, -2), point(14, 2, 8));
+        b1.add_box(&b2);
+        assert_eq!(b1.min, point(-5, -7, -2));
+        assert_eq!(b1.max, point(14, 4, 8));
+    }
+
+    #[test]
+    fn adding_empty_box_leaves_bounds_unchanged() {
+        let mut b = bounding_box(point(-1, -1, -1), point(1, 1, 1));
+        b.add_box(&BoundingBox::empty());
+        assert_eq!(b, bounding_box(point(-1, -1, -1), point(1, 1, 1)));
+    }
+
+    #[test]
+    fn infinite_box_is_not_finite() {
+        let b = BoundingBox::infinite();
+        assert!(!b.is_empty());
+        assert!(!b.is_finite());
+    }
+
+    #[test]
+    fn centroid_and_surface_area_of_box() {
+        let b = bounding_box(point(-1, 0, 2), point(1, 4, 5));
+        assert_eq!(b.centroid(), point(0, 2, 3.5));
+        assert_relative_eq!(b.surface_area(), 52.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn transforming_bounding_box() {
+        let b = bounding_box(point(-1, -1, -1), point(1, 1, 1));
+        let matrix = transform::rotation_x(FRAC_PI_4) * transform::rotation_y(FRAC_PI_4);
+        let b2 = b.transform(matrix);
+        assert_relative_eq!(b2.min.x(), -SQRT_2, epsilon = EPSILON);
+        assert_relative_eq!(b2.min.y(), -1.7071, epsilon = EPSILON);
+        assert_relative_eq!(b2.min.z(), -1.7071, epsilon = EPSILON);
+        assert_relative_eq!(b2.max.x(), SQRT_2, epsilon = EPSILON);
+        assert_relative_eq!(b2.max.y(), 1.7071, epsilon = EPSILON);
+        assert_relative_eq!(b2.max.z(), 1.7071, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn transforming_box_with_infinite_extents() {
+        let b = bounding_box(
+            point(f32::NEG_INFINITY, 0, f32::NEG_INFINITY),
+            point(f32::INFINITY, 0, f32::INFINITY),
+        );
+        let b2 = b.transform(transform::translation(0, 2, 0) * transform::scaling(3, 3, 3));
+        assert_eq!(
+            b2,
+            bounding_box(
+                point(f32::NEG_INFINITY, 2, f32::NEG_INFINITY),
+                point(f32::INFINITY, 2, f32::INFINITY),
+            )
+        );
+    }
+
+    #[test]
+    fn rotating_box_with_infinite_extents() {
+        let b = bounding_box(
+            point(f32::NEG_INFINITY, 0, f32::NEG_INFINITY),
+            point(f32::INFINITY, 0, f32::INFINITY),
+        );
+        let b2 = b.transform(transform::rotation_x(FRAC_PI_2));
+        assert_eq!(b2.min.x(), f32::NEG_INFINITY);
+        assert_eq!(b2.max.x(), f32::INFINITY);
+        assert_eq!(b2.min.y(), f32::NEG_INFINITY);
+        assert_eq!(b2.max.y(), f32::INFINITY);
+        assert_relative_eq!(b2.min.z(), 0.0, epsilon = EPSILON);
+        assert_relative_eq!(b2.max.z(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn slightly_rotating_box_with_infinite_extents() {
+        let b = bounding_box(
+            point(f32::NEG_INFINITY, 0, f32::NEG_INFINITY),
+            point(f32::INFINITY, 0, f32::INFINITY),
+        );
+        let b2 = b.transform(transform::rotation_z(0.001));
+        assert_eq!(b2.min.y(), f32::NEG_INFINITY);
+        assert_eq!(b2.max.y(), f32::INFINITY);
+    }
+
+    #[test]
+    fn transforming_empty_box_stays_empty() {
+        let b = BoundingBox::empty().transform(identity_matrix());
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn intersecting_ray_with_bounding_box_at_origin() {
+        let b = bounding_box(point(-1, -1, -1), point(1, 1, 1));
+        let test_cases = [
+            (point(5, 0.5, 0), vector(-1, 0, 0), true),
+            (point(-5, 0.5, 0), vector(1, 0, 0), true),
+            (point(0.5, 5, 0), vector(0, -1, 0), true),
+            (point(0.5, -5, 0), vector(0, 1, 0), true),
+            (point(0.5, 0, 5), vector(0, 0, -1), true),
+            (point(0.5, 0, -5), vector(0, 0, 1), true),
+            (point(0, 0.5, 0), vector(0, 0, 1), true),
+            (point(-2, 0, 0), vector(2, 4, 6), false),
+            (point(0, -2, 0), vector(6, 2, 4), false),
+            (point(0, 0, -2), vector(4, 6, 2), false),
+            (point(2, 0, 2), vector(0, 0, -1), false),
+            (point(0, 2, 2), vector(0, -1, 0), false),
+            (point(2, 2, 0), vector(-1, 0, 0), false),
+        ];
+
+        for (origin, direction, expected) in test_cases {
+            let r = ray(origin, direction.normalize());
+            assert_eq!(b.intersects(r), expected);
+        }
+    }
+
+    #[test]
+    fn intersecting_ray_with_non_cubic_bounding_box() {
+        let b = bounding_box(point(5, -2, 0), point(11, 4, 7));
+        let test_cases = [
+            (point(15, 1, 2), vector(-1, 0, 0), true),
+            (point(-5, -1, 4), vector(1, 0, 0), true),
+            (point(7, 6, 5), vector(0, -1, 0), true),
+            (point(9, -5, 6), vector(0, 1, 0), true),
+            (point(8, 2, 12), vector(0, 0, -1), true),
+            (point(6, 0, -5), vector(0, 0, 1), true),
+            (point(8, 1, 3.5), vector(0, 0, 1), true),
+            (point(9, -1, -8), vector(2, 4, 6), false),
+            (point(8, 3, -4), vector(6, 2, 4), false),
+            (point(9, -1, -2), vector(4, 6, 2), false),
+            (point(4, 0, 9), vector(0, 0, -1), false),
+            (point(8, 6, -1), vector(0, -1, 0), false),
+            (point(12, 5, 4), vector(-1, 0, 0), false),
+        ];
+
+        for (origin, direction, expected) in test_cases {
+            let r = ray(origin, direction.normalize());
+            assert_eq!(b.intersects(r), expected);
+        }
+    }
+
+    #[test]
+    fn ray_along_face_of_flat_box_intersects() {
+        let b = bounding_box(point(-1, -1, 0), point(1, 1, 0));
+        let r = ray(point(-5, 0, 0), vector(1, 0, 0));
+        assert!(b.intersects(r));
+    }
+
+    #[test]
+    fn ray_intersects_infinite_box() {
+        let b = BoundingBox::infinite();
+        let r = ray(point(0, 100, 0), vector(0, 0, 1));
+        assert!(b.intersects(r));
+    }
+
+    #[test]
+    fn ray_never_intersects_empty_box() {
+        let r = ray(point(0, 0, -5), vector(0, 0, 1));
+        assert!(!BoundingBox::empty().intersects(r));
+    }
+}
@@ -0,0 +1,344 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    Intersection, Matrix4, Point, Shape, Vector, identity_matrix,
+    pattern::{Pattern, UvPattern},
+    vector,
+};
+
+/// Distance between the samples used to estimate a height field's gradient.
+const DELTA: f32 = 0.001;
+
+/// A scalar height field that tilts surface normals to fake small bumps and
+/// dents. Heights are measured in the bump map's own space, which is the
+/// object's space transformed by its transform, and its scale sets how
+/// strongly their slope tilts the normal. The transform must be invertible,
+/// so its inverse is computed once rather than for every sample.
+#[derive(Clone)]
+pub struct BumpMap {
+    transform: Matrix4,
+    inverse_transform: Matrix4,
+    scale: f32,
+    height: Arc<dyn Fn(Point) -> f32 + Send + Sync>,
+}
+
+impl BumpMap {
+    /// Creates a bump map from a procedural height function.
+    pub fn new(height: impl Fn(Point) -> f32 + Send + Sync + 'static) -> Self {
+        BumpMap {
+            transform: identity_matrix(),
+            inverse_transform: identity_matrix(),
+            scale: 1.0,
+            height: Arc::new(height),
+        }
+    }
+
+    /// Creates a bump map whose height is the luminance of `pattern`, so
+    /// that greyscale images and procedural patterns can both drive it. The
+    /// pattern's transform becomes the bump map's.
+    #[must_use]
+    pub fn from_pattern(pattern: Pattern) -> Self {
+        let transform = pattern.transform();
+        let inverse_transform = pattern.inverse_transform();
+        BumpMap {
+            transform,
+            inverse_transform,
+            ..BumpMap::new(move |p| {
+                let c = pattern.pattern_at(p);
+                0.2126 * c.red() + 0.7152 * c.green() + 0.0722 * c.blue()
+            })
+        }
+    }
+
+    #[must_use]
+    pub fn with_scale(self, scale: f32) -> Self {
+        BumpMap { scale, ..self }
+    }
+
+    /// # Errors
+    /// Returns an error if `transform` is not invertible.
+    pub fn with_transform(self, transform: Matrix4) -> Result<Self> {
+        let inverse_transform = transform
+            .inverse()
+            .context("bump map transform is not invertible")?;
+        Ok(BumpMap {
+            transform,
+            inverse_transform,
+            ..self
+        })
+    }
+
+    #[must_use]
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Returns this bump map's transformation matrix.
+    #[must_use]
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    /// Returns the height at a point in the bump map's space.
+    #[must_use]
+    pub fn height_at(&self, point: Point) -> f32 {
+        (self.height)(point)
+    }
+
+    /// Tilts the world-space `normal` at `world_point` on `shape` away from
+    /// the slope of the height field.
+    ///
+    /// # Panics
+    /// Panics if the shape's internal lock is poisoned.
+    #[must_use]
+    pub fn perturb(&self, shape: &Shape, world_point: Point, normal: Vector) -> Vector {
+        let height = |offset: Vector| {
+            self.height_at(self.inverse_transform * shape.world_to_object(world_point + offset))
+        };
+        let slope = |axis: Vector| (height(axis * DELTA) - height(axis * -DELTA)) / (2.0 * DELTA);
+        let gradient = vector(
+            slope(vector(1, 0, 0)),
+            slope(vector(0, 1, 0)),
+            slope(vector(0, 0, 1)),
+        );
+
+        let tangential = gradient - normal * gradient.dot(&normal);
+        (normal - tangential * self.scale).normalize()
+    }
+}
+
+/// A tangent-space normal map, which replaces the shading normal of shapes
+/// with texture coordinates by one read from a texture. Each texel's red,
+/// green and blue encode a normal's components along the directions in
+/// which `u` and `v` increase and along the surface normal, mapped from
+/// `-1.0..=1.0` to `0.0..=1.0`. `strength` scales the tilt away from the
+/// surface normal.
+#[derive(Clone)]
+pub struct NormalMap {
+    pub strength: f32,
+    texture: UvPattern,
+}
+
+impl NormalMap {
+    /// Creates a normal map that reads its normals from `texture`, which
+    /// should hold linear rather than sRGB encoded values.
+    #[must_use]
+    pub fn new(texture: UvPattern) -> Self {
+        NormalMap {
+            strength: 1.0,
+            texture,
+        }
+    }
+
+    #[must_use]
+    pub fn with_strength(self, strength: f32) -> Self {
+        NormalMap { strength, ..self }
+    }
+
+    /// Returns the tangent-space normal stored at texture coordinates `u`
+    /// and `v`.
+    #[must_use]
+    pub fn normal_at(&self, u: f32, v: f32) -> Vector {
+        let texel = self.texture.uv_pattern_at(u, v);
+        vector(
+            (texel.red() * 2.0 - 1.0) * self.strength,
+            (texel.green() * 2.0 - 1.0) * self.strength,
+            texel.blue() * 2.0 - 1.0,
+        )
+    }
+
+    /// Returns the world-space shading normal for `hit` at `world_point`.
+    /// Shapes without texture coordinates keep their own `normal`.
+    ///
+    /// # Panics
+    /// Panics if the shape's internal lock is poisoned.
+    #[must_use]
+    pub fn perturb(
+        &self,
+        shape: &Shape,
+        world_point: Point,
+        hit: &Intersection,
+        normal: Vector,
+    ) -> Vector {
+        let local_point = shape.world_to_object(world_point);
+        let inner = shape.inner();
+        let Some(frame) = inner.geometry.local_tangent_frame(hit) else {
+            return normal;
+        };
+        let n = inner
+            .geometry
+            .local_normal_at(local_point, Some(hit))
+            .normalize();
+        drop(inner);
+
+        // Tangents transform like the surface itself, while the normal needs
+        // the inverse transpose, so the frame is built in world space.
+        let n = shape.normal_to_world(n);
+        let tangent = shape.vector_to_world(frame.tangent);
+        let bitangent = shape.vector_to_world(frame.bitangent);
+        let t = (tangent - n * tangent.dot(&n)).normalize();
+        let b = (bitangent - n * bitangent.dot(&n) - t * bitangent.dot(&t)).normalize();
+        let mapped = self.normal_at(frame.u, frame.v);
+        (t * mapped.x() + b * mapped.y() + n * mapped.z()).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        Color, EPSILON, color,
+        pattern::gradient_pattern,
+        point, ray,
+        shape::{plane, triangle},
+        sphere, transform,
+    };
+
+    fn assert_vector(actual: Vector, expected: Vector) {
+        assert_relative_eq!(actual.x(), expected.x(), epsilon = 0.001);
+        assert_relative_eq!(actual.y(), expected.y(), epsilon = 0.001);
+        assert_relative_eq!(actual.z(), expected.z(), epsilon = 0.001);
+    }
+
+    #[test]
+    fn flat_height_field_leaves_normal_alone() {
+        let bump = BumpMap::new(|_| 0.5);
+        let s = sphere().build();
+        let n = bump.perturb(&s, point(0, 1, 0), vector(0, 1, 0));
+        assert_vector(n, vector(0, 1, 0));
+    }
+
+    #[test]
+    fn slope_tilts_normal_downhill() {
+        let bump = BumpMap::new(|p| p.x());
+        let p = plane().build();
+        let n = bump.perturb(&p, point(0, 0, 0), vector(0, 1, 0));
+        let expected = vector(-1, 1, 0).normalize();
+        assert_vector(n, expected);
+
+        let gentle = bump
+            .with_scale(0.5)
+            .perturb(&p, point(0, 0, 0), vector(0, 1, 0));
+        assert_vector(gentle, vector(-0.5, 1, 0).normalize());
+    }
+
+    #[test]
+    fn slope_along_the_normal_is_ignored() {
+        let bump = BumpMap::new(|p| p.y() * 10.0);
+        let p = plane().build();
+        let n = bump.perturb(&p, point(3, 0, 1), vector(0, 1, 0));
+        assert_vector(n, vector(0, 1, 0));
+    }
+
+    #[test]
+    fn bump_map_follows_object_and_own_transforms() {
+        let bump = BumpMap::new(|p| p.x())
+            .with_transform(transform::scaling(2, 2, 2))
+            .unwrap();
+        let p = plane()
+            .transform(transform::rotation_y(std::f32::consts::FRAC_PI_2))
+            .build();
+        let n = bump.perturb(&p, point(0, 0, 0), vector(0, 1, 0));
+        assert_vector(n, vector(0, 1, 0.5).normalize());
+    }
+
+    #[test]
+    fn singular_bump_map_transform_is_an_error() {
+        let e = BumpMap::new(|p| p.x())
+            .with_transform(transform::scaling(0, 1, 1))
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "bump map transform is not invertible");
+    }
+
+    #[test]
+    fn height_from_pattern_luminance() {
+        let pattern = gradient_pattern(color(0, 0, 0), color(1, 1, 1))
+            .transform(transform::scaling(4, 1, 1))
+            .build()
+            .unwrap();
+        let bump = BumpMap::from_pattern(pattern);
+        assert_eq!(bump.transform(), transform::scaling(4, 1, 1));
+        assert_relative_eq!(bump.height_at(point(0.5, 0, 0)), 0.5, epsilon = EPSILON);
+        assert_relative_eq!(bump.height_at(point(0.25, 7, 3)), 0.25, epsilon = EPSILON);
+    }
+
+    fn textured_triangle() -> Shape {
+        triangle(point(0, 1, 0), point(-1, 0, 0), point(1, 0, 0))
+            .uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .build()
+    }
+
+    fn normal_map(c: Color) -> NormalMap {
+        NormalMap::new(UvPattern::new(move |_, _| c))
+    }
+
+    fn hit_on(shape: &Shape) -> Intersection {
+        shape.intersect(ray(point(0.0, 0.5, -2.0), vector(0, 0, 1)))[0].clone()
+    }
+
+    #[test]
+    fn decoding_normal_map_texels() {
+        let map = normal_map(color(1, 0.5, 0.75));
+        assert_vector(map.normal_at(0.3, 0.3), vector(1, 0, 0.5));
+        assert_vector(
+            map.with_strength(0.5).normal_at(0.3, 0.3),
+            vector(0.5, 0, 0.5),
+        );
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_surface_normal() {
+        let t = textured_triangle();
+        let i = hit_on(&t);
+        let n = normal_map(color(0.5, 0.5, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(0, 0, -1));
+    }
+
+    #[test]
+    fn normal_map_tilts_along_the_tangent_frame() {
+        let t = textured_triangle();
+        let i = hit_on(&t);
+        let n = normal_map(color(1, 0.5, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(1, 0, -1).normalize());
+
+        let n = normal_map(color(0.5, 0, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(0, -1, -1).normalize());
+    }
+
+    #[test]
+    fn normal_map_follows_the_object_transform() {
+        let t = textured_triangle();
+        t.set_transform(transform::rotation_y(std::f32::consts::FRAC_PI_2));
+        let r = ray(point(-2.0, 0.5, 0.0), vector(1, 0, 0));
+        let i = t.intersect(r)[0].clone();
+        let n = normal_map(color(1, 0.5, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(-1, 0, 0));
+        assert_vector(n, vector(-1, 0, -1).normalize());
+    }
+
+    #[test]
+    fn normal_map_keeps_its_tilt_under_non_uniform_scaling() {
+        let t = textured_triangle();
+        t.set_transform(transform::scaling(3, 1, 1));
+        let i = hit_on(&t);
+        let n = normal_map(color(1, 0.5, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(1, 0, -1).normalize());
+
+        t.set_transform(transform::scaling(1, 4, 1));
+        let i = hit_on(&t);
+        let n = normal_map(color(0.5, 0, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(0, -1, -1).normalize());
+    }
+
+    #[test]
+    fn normal_map_ignores_shapes_without_texture_coordinates() {
+        let t = triangle(point(0, 1, 0), point(-1, 0, 0), point(1, 0, 0)).build();
+        let i = hit_on(&t);
+        let n = normal_map(color(1, 0.5, 1)).perturb(&t, point(0, 0.5, 0), &i, vector(0, 0, -1));
+        assert_vector(n, vector(0, 0, -1));
+    }
+}
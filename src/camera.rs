@@ -1,12 +1,57 @@
-use std::f32::consts::FRAC_PI_2;
+use std::{
+    f32::consts::FRAC_PI_2,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use bon::builder;
 
 use crate::{
-    Canvas, Color, Matrix4, ORIGIN, REFLECTION_DEPTH, Ray, World, canvas_with_pixels,
+    AovBuffers, Aovs, Canvas, Color, Matrix4, ORIGIN, Ray, TraceDepth, World,
+    aov::AovSample,
+    canvas_with_pixels,
+    color::BLACK,
     identity_matrix, point, ray,
+    sampling::{random_unit, seed},
 };
 
+/// Narrowest Gaussian pixel filter, in pixels. Below this the weights of
+/// samples near the pixel's edge underflow and stop summing to a usable
+/// total.
+const MIN_GAUSSIAN_SIGMA: f32 = 0.1;
+
+/// How the samples taken within a pixel are combined into its final color.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PixelFilter {
+    /// Every sample contributes equally.
+    #[default]
+    Box,
+    /// Samples are weighted by a Gaussian centred on the pixel, with the
+    /// standard deviation given in pixels. Values below 0.1, including zero,
+    /// negative and NaN values, are clamped to 0.1.
+    Gaussian { sigma: f32 },
+}
+
+impl PixelFilter {
+    fn weight(self, dx: f32, dy: f32) -> f32 {
+        match self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Gaussian { sigma } => {
+                let sigma = sigma.max(MIN_GAUSSIAN_SIGMA);
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            }
+        }
+    }
+}
+
+/// Sample counts gathered while rendering a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Samples taken in the initial pass over every pixel.
+    pub primary_samples: usize,
+    /// Additional samples taken while adaptively refining high-contrast pixels.
+    pub extra_samples: usize,
+}
+
 #[must_use]
 #[builder(finish_fn = build)]
 pub fn camera(
@@ -15,6 +60,22 @@ pub fn camera(
     #[builder(default = FRAC_PI_2)] field_of_view: f32,
     #[builder(default = identity_matrix())] transform: Matrix4,
     #[builder(default = false)] parallel: bool,
+    /// Samples traced per pixel. Ignored in adaptive mode.
+    #[builder(default = 1)]
+    samples_per_pixel: u16,
+    /// How the samples in a pixel are combined. Ignored in adaptive mode.
+    #[builder(default)]
+    filter: PixelFilter,
+    /// Switches to adaptive mode, which traces one sample through each
+    /// pixel's centre and subdivides only pixels whose color differs from a
+    /// neighbour's by more than this amount. Subsamples are averaged evenly,
+    /// so `samples_per_pixel` and `filter` are ignored.
+    adaptive_threshold: Option<f32>,
+    /// Maximum number of times an adaptively refined pixel is subdivided.
+    #[builder(default = 2)]
+    adaptive_depth: u8,
+    #[builder(default)] aovs: Aovs,
+    #[builder(default, into)] depth: TraceDepth,
 ) -> Camera {
     let half_view = (field_of_view / 2.0).tan();
     let aspect = f32::from(horizontal_size) / f32::from(vertical_size);
@@ -40,6 +101,12 @@ pub fn camera(
         half_width,
         half_height,
         parallel,
+        samples_per_pixel: samples_per_pixel.max(1),
+        filter,
+        adaptive_threshold,
+        adaptive_depth,
+        aovs,
+        depth,
     }
 }
 
@@ -53,15 +120,39 @@ pub struct Camera {
     pub half_width: f32,
     pub half_height: f32,
     pub parallel: bool,
+    pub samples_per_pixel: u16,
+    pub filter: PixelFilter,
+    /// When set, `render` traces one sample per pixel and then subdivides
+    /// only pixels whose color differs from a neighbour's by more than this
+    /// amount in any channel. `samples_per_pixel` and `filter` are ignored.
+    pub adaptive_threshold: Option<f32>,
+    /// Maximum number of times an adaptively refined pixel is subdivided.
+    pub adaptive_depth: u8,
+    /// Extra buffers produced by `render_with_aovs`.
+    pub aovs: Aovs,
+    /// How far each ray is followed through reflections and refractions.
+    pub depth: TraceDepth,
 }
 
 impl Camera {
+    /// Returns the ray through the centre of the given pixel.
+    ///
     /// # Panics
     /// Panics if the camera's transform matrix is not invertible.
     #[must_use]
     pub fn ray_for_pixel(&self, px: u16, py: u16) -> Ray {
-        let x_offset = (f32::from(px) + 0.5) * self.pixel_size;
-        let y_offset = (f32::from(py) + 0.5) * self.pixel_size;
+        self.ray_for_subpixel(px, py, 0.5, 0.5)
+    }
+
+    /// Returns the ray through a point inside the given pixel, where
+    /// `(0, 0)` is the pixel's top-left corner and `(1, 1)` its bottom-right.
+    ///
+    /// # Panics
+    /// Panics if the camera's transform matrix is not invertible.
+    #[must_use]
+    pub fn ray_for_subpixel(&self, px: u16, py: u16, dx: f32, dy: f32) -> Ray {
+        let x_offset = (f32::from(px) + dx) * self.pixel_size;
+        let y_offset = (f32::from(py) + dy) * self.pixel_size;
 
         let world_x = self.half_width - x_offset;
         let world_y = self.half_height - y_offset;
@@ -77,33 +168,314 @@ impl Camera {
         ray(origin, direction)
     }
 
+    /// Returns the subpixel offsets sampled for a pixel. A single sample
+    /// goes through the pixel centre; more samples are stratified over a
+    /// grid of cells covering the pixel, one sample jittered within each
+    /// cell. The grid is the most nearly square one with exactly as many
+    /// cells as samples, so a prime count gives a row of vertical strips.
+    #[must_use]
+    pub fn subpixel_offsets(&self, px: u16, py: u16) -> Vec<(f32, f32)> {
+        let count = self.samples_per_pixel;
+        if count <= 1 {
+            return vec![(0.5, 0.5)];
+        }
+
+        let rows_int = (1..=count)
+            .take_while(|&rows| u32::from(rows).pow(2) <= u32::from(count))
+            .filter(|&rows| count.is_multiple_of(rows))
+            .last()
+            .unwrap_or(1);
+        let columns_int = count / rows_int;
+        let (columns, rows) = (f32::from(columns_int), f32::from(rows_int));
+
+        (0..count)
+            .map(|sample| {
+                let column = f32::from(sample % columns_int);
+                let row = f32::from(sample / columns_int);
+                let jitter_x = random_unit(seed(&[px.into(), py.into(), sample.into(), 0]));
+                let jitter_y = random_unit(seed(&[px.into(), py.into(), sample.into(), 1]));
+                ((column + jitter_x) / columns, (row + jitter_y) / rows)
+            })
+            .collect()
+    }
+
+    /// Traces every sample for a pixel and combines them with the camera's
+    /// reconstruction filter.
+    #[must_use]
+    pub fn color_for_pixel(&self, world: &World, px: u16, py: u16) -> Color {
+        self.sample_pixel(world, px, py, false).0
+    }
+
+    /// Like `color_for_pixel`, but when `aovs` is set also returns the AOV
+    /// sample from the sample nearest the pixel's centre.
+    fn sample_pixel(
+        &self,
+        world: &World,
+        px: u16,
+        py: u16,
+        aovs: bool,
+    ) -> (Color, Option<AovSample>) {
+        let offsets = self.subpixel_offsets(px, py);
+        if let [(dx, dy)] = offsets[..] {
+            let ray = self.ray_for_subpixel(px, py, dx, dy);
+            return world.color_and_aov_at(ray, self.depth, aovs);
+        }
+
+        let distance = |(dx, dy): (f32, f32)| (dx - 0.5).powi(2) + (dy - 0.5).powi(2);
+        let nearest = offsets
+            .iter()
+            .enumerate()
+            .min_by(|&(_, &a), &(_, &b)| distance(a).total_cmp(&distance(b)))
+            .map(|(index, _)| index)
+            .filter(|_| aovs);
+
+        let mut aov = None;
+        let (sum, total_weight) = offsets.into_iter().enumerate().fold(
+            (BLACK, 0.0),
+            |(sum, total_weight), (index, (dx, dy))| {
+                let weight = self.filter.weight(dx - 0.5, dy - 0.5);
+                let ray = self.ray_for_subpixel(px, py, dx, dy);
+                let (color, sample) =
+                    world.color_and_aov_at(ray, self.depth, nearest == Some(index));
+                if sample.is_some() {
+                    aov = sample;
+                }
+                (sum + color * weight, total_weight + weight)
+            },
+        );
+
+        (sum * (1.0 / total_weight), aov)
+    }
+
     #[must_use]
     pub fn render(&self, world: &World) -> Canvas {
-        let pixels: Vec<Color> = if self.parallel {
+        self.render_with_stats(world).0
+    }
+
+    /// Renders the world and reports how many samples were traced.
+    #[must_use]
+    pub fn render_with_stats(&self, world: &World) -> (Canvas, RenderStats) {
+        self.render_with_progress(world, |_, _| {})
+    }
+
+    /// Renders like `render_with_stats`, calling `progress` with the number
+    /// of pixels finished and the total after each pixel. Adaptive rendering
+    /// visits every pixel twice, so its total is twice the pixel count.
+    #[must_use]
+    pub fn render_with_progress<P>(&self, world: &World, progress: P) -> (Canvas, RenderStats)
+    where
+        P: Fn(usize, usize) + Sync,
+    {
+        let (image, _, stats) = self.render_pass(world, false, progress);
+        (image, stats)
+    }
+
+    /// Renders the world along with the AOV buffers selected by `aovs`. The
+    /// buffers are filled during the beauty pass from the primary ray
+    /// nearest the centre of each pixel, so they stay unfiltered at object
+    /// edges. Object IDs are numbered from `world`'s objects as they are
+    /// when the render runs.
+    #[must_use]
+    pub fn render_with_aovs(&self, world: &World) -> (Canvas, AovBuffers) {
+        let (image, samples, _) = self.render_pass(world, self.aovs.any(), |_, _| {});
+        if !self.aovs.any() {
+            return (image, AovBuffers::default());
+        }
+
+        let buffers = AovBuffers::from_samples(
+            self.aovs,
+            usize::from(self.width),
+            usize::from(self.height),
+            &samples,
+            &world.object_ids(),
+        );
+        (image, buffers)
+    }
+
+    /// Renders the beauty image, also collecting one AOV sample per pixel
+    /// from its primary rays when `aovs` is set.
+    fn render_pass<P>(
+        &self,
+        world: &World,
+        aovs: bool,
+        progress: P,
+    ) -> (Canvas, Vec<Option<AovSample>>, RenderStats)
+    where
+        P: Fn(usize, usize) + Sync,
+    {
+        let pixel_count = usize::from(self.width) * usize::from(self.height);
+        let total = if self.adaptive_threshold.is_some() {
+            pixel_count * 2
+        } else {
+            pixel_count
+        };
+        let finished = AtomicUsize::new(0);
+        let tick = || progress(finished.fetch_add(1, Ordering::Relaxed) + 1, total);
+
+        let Some(threshold) = self.adaptive_threshold else {
+            let (pixels, samples) = self
+                .map_pixels(|x, y| {
+                    let pixel = self.sample_pixel(world, x, y, aovs);
+                    tick();
+                    pixel
+                })
+                .into_iter()
+                .unzip();
+            let stats = RenderStats {
+                primary_samples: pixel_count * usize::from(self.samples_per_pixel),
+                extra_samples: 0,
+            };
+            return (self.canvas_from(pixels), samples, stats);
+        };
+
+        let (initial, samples): (Vec<Color>, _) = self
+            .map_pixels(|x, y| {
+                let pixel = world.color_and_aov_at(self.ray_for_pixel(x, y), self.depth, aovs);
+                tick();
+                pixel
+            })
+            .into_iter()
+            .unzip();
+        let refined = self.map_pixels(|x, y| {
+            let centre = initial[self.pixel_index(x, y)];
+            if self.max_neighbour_difference(&initial, x, y) <= threshold {
+                tick();
+                return (centre, 0);
+            }
+
+            let mut refinement = Refinement {
+                world,
+                pixel: (x, y),
+                threshold,
+                extra_samples: 0,
+            };
+            let color = self.refine_region(
+                &mut refinement,
+                (0.0, 0.0, 1.0),
+                centre,
+                self.adaptive_depth,
+            );
+            tick();
+            (color, refinement.extra_samples)
+        });
+
+        let extra_samples = refined.iter().map(|&(_, samples)| samples).sum();
+        let pixels = refined.into_iter().map(|(color, _)| color).collect();
+        let stats = RenderStats {
+            primary_samples: pixel_count,
+            extra_samples,
+        };
+        (self.canvas_from(pixels), samples, stats)
+    }
+
+    fn map_pixels<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(u16, u16) -> T + Send + Sync,
+    {
+        let f = &f;
+        if self.parallel {
             use rayon::prelude::*;
 
             (0..self.height)
                 .into_par_iter()
-                .flat_map_iter(|y| {
-                    (0..self.width).map(move |x| {
-                        let ray = self.ray_for_pixel(x, y);
-                        world.color_at(ray, REFLECTION_DEPTH)
-                    })
-                })
+                .flat_map_iter(|y| (0..self.width).map(move |x| f(x, y)))
                 .collect()
         } else {
             (0..self.height)
-                .flat_map(|y| {
-                    (0..self.width).map(move |x| {
-                        let ray = self.ray_for_pixel(x, y);
-                        world.color_at(ray, REFLECTION_DEPTH)
-                    })
-                })
+                .flat_map(|y| (0..self.width).map(move |x| f(x, y)))
                 .collect()
-        };
+        }
+    }
 
+    fn canvas_from(&self, pixels: Vec<Color>) -> Canvas {
         canvas_with_pixels(self.width as usize, self.height as usize, pixels)
     }
+
+    fn pixel_index(&self, x: u16, y: u16) -> usize {
+        usize::from(y) * usize::from(self.width) + usize::from(x)
+    }
+
+    fn max_neighbour_difference(&self, pixels: &[Color], x: u16, y: u16) -> f32 {
+        let centre = pixels[self.pixel_index(x, y)];
+        let neighbours = [
+            x.checked_sub(1).map(|nx| (nx, y)),
+            (x + 1 < self.width).then(|| (x + 1, y)),
+            y.checked_sub(1).map(|ny| (x, ny)),
+            (y + 1 < self.height).then(|| (x, y + 1)),
+        ];
+
+        neighbours
+            .into_iter()
+            .flatten()
+            .map(|(nx, ny)| color_difference(centre, pixels[self.pixel_index(nx, ny)]))
+            .fold(0.0, f32::max)
+    }
+
+    /// Estimates the average color of a square region of a pixel by sampling
+    /// the centres of its four quadrants, recursing into any quadrant that
+    /// still differs from the region's centre sample by more than the
+    /// threshold. The region is given as `(left, top, size)` in subpixel units.
+    fn refine_region(
+        &self,
+        refinement: &mut Refinement,
+        (left, top, size): (f32, f32, f32),
+        centre: Color,
+        depth: u8,
+    ) -> Color {
+        if depth == 0 {
+            return centre;
+        }
+
+        let half = size / 2.0;
+        let quadrants = [
+            (left, top),
+            (left + half, top),
+            (left, top + half),
+            (left + half, top + half),
+        ];
+
+        let sum = quadrants
+            .into_iter()
+            .fold(BLACK, |sum, (quadrant_left, quadrant_top)| {
+                let dx = quadrant_left + half / 2.0;
+                let dy = quadrant_top + half / 2.0;
+                let (px, py) = refinement.pixel;
+                let ray = self.ray_for_subpixel(px, py, dx, dy);
+                let sample = refinement.world.color_at(ray, self.depth);
+                refinement.extra_samples += 1;
+
+                let color = if color_difference(sample, centre) > refinement.threshold {
+                    self.refine_region(
+                        refinement,
+                        (quadrant_left, quadrant_top, half),
+                        sample,
+                        depth - 1,
+                    )
+                } else {
+                    sample
+                };
+                sum + color
+            });
+
+        sum * 0.25
+    }
+}
+
+/// State shared by the recursive calls that adaptively refine one pixel.
+struct Refinement<'a> {
+    world: &'a World,
+    pixel: (u16, u16),
+    threshold: f32,
+    extra_samples: usize,
+}
+
+fn color_difference(a: Color, b: Color) -> f32 {
+    let diff = a - b;
+    diff.red()
+        .abs()
+        .max(diff.green().abs())
+        .max(diff.blue().abs())
 }
 
 #[cfg(test)]
@@ -113,7 +485,10 @@ mod tests {
     use approx::assert_relative_eq;
 
     use super::*;
-    use crate::{EPSILON, default_world, point, transform, vector};
+    use crate::{
+        EPSILON, Material, REFLECTION_DEPTH, World, color, default_world, plane, point, transform,
+        vector,
+    };
 
     #[test]
     fn constructing_a_camera() {
@@ -214,4 +589,344 @@ mod tests {
 
         assert_eq!(seq_image, par_image);
     }
+
+    #[test]
+    fn camera_defaults_to_one_sample_per_pixel() {
+        let c = camera(160, 120).build();
+        assert_eq!(c.samples_per_pixel, 1);
+        assert_eq!(c.filter, PixelFilter::Box);
+        assert_eq!(c.subpixel_offsets(3, 4), vec![(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn subpixel_offsets_are_stratified_within_pixel() {
+        let c = camera(160, 120).samples_per_pixel(4).build();
+        let offsets = c.subpixel_offsets(10, 20);
+        assert_eq!(offsets.len(), 4);
+
+        let cells = [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)];
+        for ((dx, dy), (cell_x, cell_y)) in offsets.into_iter().zip(cells) {
+            assert!((cell_x..cell_x + 0.5).contains(&dx));
+            assert!((cell_y..cell_y + 0.5).contains(&dy));
+        }
+    }
+
+    #[test]
+    fn subpixel_offsets_are_jittered_per_pixel() {
+        let c = camera(160, 120).samples_per_pixel(4).build();
+        assert_ne!(c.subpixel_offsets(0, 0), c.subpixel_offsets(1, 0));
+        assert_eq!(c.subpixel_offsets(5, 5), c.subpixel_offsets(5, 5));
+    }
+
+    #[test]
+    fn non_square_sample_counts_cover_pixel() {
+        let c = camera(160, 120).samples_per_pixel(5).build();
+        let offsets = c.subpixel_offsets(0, 0);
+        assert_eq!(offsets.len(), 5);
+        for (dx, dy) in offsets {
+            assert!((0.0..1.0).contains(&dx));
+            assert!((0.0..1.0).contains(&dy));
+        }
+    }
+
+    #[test]
+    fn every_stratum_gets_one_sample_for_non_square_counts() {
+        for (count, columns, rows) in [(5, 5, 1), (6, 3, 2), (8, 4, 2), (12, 4, 3)] {
+            let c = camera(160, 120).samples_per_pixel(count).build();
+            let mut hits = vec![0; usize::from(columns * rows)];
+            for (dx, dy) in c.subpixel_offsets(7, 3) {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let cell = |d: f32, cells: u16| (d * f32::from(cells)) as usize;
+                hits[cell(dy, rows) * usize::from(columns) + cell(dx, columns)] += 1;
+            }
+            assert!(hits.iter().all(|&h| h == 1), "{count} samples: {hits:?}");
+        }
+    }
+
+    #[test]
+    fn ray_for_subpixel_at_centre_matches_ray_for_pixel() {
+        let c = camera(201, 101).field_of_view(FRAC_PI_2).build();
+        let r1 = c.ray_for_pixel(0, 0);
+        let r2 = c.ray_for_subpixel(0, 0, 0.5, 0.5);
+        assert_eq!(r1.origin, r2.origin);
+        assert_eq!(r1.direction, r2.direction);
+    }
+
+    #[test]
+    fn gaussian_filter_favours_samples_near_centre() {
+        let filter = PixelFilter::Gaussian { sigma: 0.5 };
+        assert_relative_eq!(filter.weight(0.0, 0.0), 1.0, epsilon = EPSILON);
+        assert!(filter.weight(0.4, 0.4) < filter.weight(0.1, 0.1));
+        assert_relative_eq!(PixelFilter::Box.weight(0.4, 0.4), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn degenerate_gaussian_filters_are_clamped() {
+        let narrowest = PixelFilter::Gaussian {
+            sigma: MIN_GAUSSIAN_SIGMA,
+        }
+        .weight(0.5, 0.5);
+        assert!(narrowest > 0.0);
+        for sigma in [0.0, -1.0, f32::NAN] {
+            let weight = PixelFilter::Gaussian { sigma }.weight(0.5, 0.5);
+            assert_relative_eq!(weight, narrowest);
+        }
+    }
+
+    #[test]
+    fn supersampling_stays_close_to_centre_sample() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let c = camera(11, 11)
+            .field_of_view(FRAC_PI_2)
+            .transform(t)
+            .samples_per_pixel(9)
+            .filter(PixelFilter::Gaussian { sigma: 0.5 })
+            .build();
+        let image = c.render(&w);
+        let corner = image.pixel_at(0, 0).unwrap();
+        assert_eq!(corner, color(0, 0, 0));
+        let centre = image.pixel_at(5, 5).unwrap();
+        assert_relative_eq!(centre.red(), 0.38066, epsilon = 0.05);
+        assert_relative_eq!(centre.green(), 0.47583, epsilon = 0.05);
+        assert_relative_eq!(centre.blue(), 0.2855, epsilon = 0.05);
+    }
+
+    #[test]
+    fn supersampling_blends_colors_across_silhouette() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let single = camera(21, 21).field_of_view(FRAC_PI_2).transform(t).build();
+        let multi = camera(21, 21)
+            .field_of_view(FRAC_PI_2)
+            .transform(t)
+            .samples_per_pixel(16)
+            .build();
+
+        let single_image = single.render(&w);
+        let multi_image = multi.render(&w);
+        assert_ne!(single_image, multi_image);
+    }
+
+    #[test]
+    fn parallel_supersampling_matches_sequential() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+
+        let seq = camera(11, 11)
+            .field_of_view(FRAC_PI_2)
+            .transform(t)
+            .samples_per_pixel(4)
+            .build();
+        let par = camera(11, 11)
+            .field_of_view(FRAC_PI_2)
+            .transform(t)
+            .samples_per_pixel(4)
+            .parallel(true)
+            .build();
+
+        assert_eq!(seq.render(&w), par.render(&w));
+    }
+
+    #[test]
+    fn adaptive_rendering_is_disabled_by_default() {
+        let c = camera(160, 120).build();
+        assert!(c.adaptive_threshold.is_none());
+        assert_eq!(c.adaptive_depth, 2);
+    }
+
+    #[test]
+    fn render_with_stats_counts_primary_samples() {
+        let w = default_world();
+        let c = camera(11, 11).samples_per_pixel(4).build();
+        let (_, stats) = c.render_with_stats(&w);
+        assert_eq!(stats.primary_samples, 11 * 11 * 4);
+        assert_eq!(stats.extra_samples, 0);
+    }
+
+    #[test]
+    fn render_with_progress_reports_every_pixel() {
+        let w = default_world();
+        for c in [
+            camera(7, 5).parallel(true).build(),
+            camera(7, 5).adaptive_threshold(0.01).build(),
+        ] {
+            let calls = AtomicUsize::new(0);
+            let last = AtomicUsize::new(0);
+            let (image, _) = c.render_with_progress(&w, |finished, total| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                last.fetch_max(finished, Ordering::Relaxed);
+                assert!(finished <= total);
+            });
+            assert_eq!(image, c.render(&w));
+            assert_eq!(calls.into_inner(), last.into_inner());
+        }
+    }
+
+    #[test]
+    fn depth_limits_reflections() {
+        let mut w = default_world();
+        let mirror = plane()
+            .material(Material::builder().reflective(0.5))
+            .transform(transform::translation(0, -1, 0))
+            .build();
+        w.objects.push(mirror);
+        let view = transform::view_transform(point(0, 0, -5), point(0, -1, -2.5), vector(0, 1, 0));
+        let shallow = camera(1, 1).transform(view).depth(0).build();
+        let deep = camera(1, 1).transform(view).build();
+        assert_eq!(deep.depth, TraceDepth::from(REFLECTION_DEPTH));
+
+        let r = deep.ray_for_pixel(0, 0);
+        assert_ne!(w.color_at(r, 0), w.color_at(r, REFLECTION_DEPTH));
+        assert_eq!(shallow.render(&w).pixel_at(0, 0).unwrap(), w.color_at(r, 0));
+        assert_eq!(
+            deep.render(&w).pixel_at(0, 0).unwrap(),
+            w.color_at(r, REFLECTION_DEPTH)
+        );
+    }
+
+    #[test]
+    fn rendering_aovs_with_camera() {
+        let w = default_world();
+        let c = camera(11, 11)
+            .transform(transform::view_transform(
+                point(0, 0, -5),
+                point(0, 0, 0),
+                vector(0, 1, 0),
+            ))
+            .aovs(Aovs::ALL)
+            .build();
+        let (image, buffers) = c.render_with_aovs(&w);
+        assert_eq!(image, c.render(&w));
+
+        let centre = 5 * 11 + 5;
+        let depth = buffers.depth.unwrap();
+        assert_relative_eq!(depth[centre], 4.0, epsilon = EPSILON);
+        assert_eq!(depth[0], f32::INFINITY);
+        let normal = buffers.normal.unwrap().pixel_at(5, 5).unwrap();
+        assert_relative_eq!(normal.blue(), -1.0, epsilon = EPSILON);
+        assert_eq!(
+            buffers.albedo.unwrap().pixel_at(5, 5).unwrap(),
+            color(0.8, 1.0, 0.6)
+        );
+        let object_id = buffers.object_id.unwrap();
+        assert_eq!((object_id[centre], object_id[0]), (1, 0));
+    }
+
+    #[test]
+    fn object_ids_follow_objects_added_between_renders() {
+        let mut w = default_world();
+        let c = camera(11, 11)
+            .transform(transform::view_transform(
+                point(0, 0, -5),
+                point(0, 0, 0),
+                vector(0, 1, 0),
+            ))
+            .aovs(Aovs::ALL)
+            .build();
+        let (_, buffers) = c.render_with_aovs(&w);
+        assert_eq!(buffers.object_id.unwrap()[0], 0);
+
+        let backdrop = plane()
+            .transform(transform::translation(0, 0, 10) * transform::rotation_x(FRAC_PI_2))
+            .build();
+        w.objects.push(backdrop);
+        let (_, buffers) = c.render_with_aovs(&w);
+        let object_id = buffers.object_id.unwrap();
+        assert_eq!((object_id[5 * 11 + 5], object_id[0]), (1, 3));
+    }
+
+    #[test]
+    fn aovs_come_from_the_beauty_pass_when_supersampling() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let supersampled = camera(11, 11)
+            .transform(t)
+            .samples_per_pixel(4)
+            .aovs(Aovs::ALL)
+            .build();
+        let adaptive = camera(11, 11)
+            .transform(t)
+            .adaptive_threshold(0.1)
+            .aovs(Aovs::ALL)
+            .build();
+
+        for c in [supersampled, adaptive] {
+            let (image, buffers) = c.render_with_aovs(&w);
+            assert_eq!(image, c.render(&w));
+            let depth = buffers.depth.unwrap();
+            assert_relative_eq!(depth[5 * 11 + 5], 4.0, epsilon = 0.05);
+            assert_eq!(depth[0], f32::INFINITY);
+            assert_eq!(buffers.object_id.unwrap()[5 * 11 + 5], 1);
+        }
+    }
+
+    #[test]
+    fn rendering_without_aovs_skips_buffers() {
+        let w = default_world();
+        let (_, buffers) = camera(5, 5).build().render_with_aovs(&w);
+        assert_eq!(buffers, AovBuffers::default());
+    }
+
+    #[test]
+    fn adaptive_rendering_skips_flat_images() {
+        let w = World::default();
+        let c = camera(11, 11).adaptive_threshold(0.1).build();
+        let (image, stats) = c.render_with_stats(&w);
+        assert_eq!(stats.primary_samples, 121);
+        assert_eq!(stats.extra_samples, 0);
+        assert_eq!(image.pixel_at(5, 5).unwrap(), color(0, 0, 0));
+    }
+
+    #[test]
+    fn adaptive_rendering_refines_only_high_contrast_pixels() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let c = camera(21, 21)
+            .field_of_view(FRAC_PI_2)
+            .transform(t)
+            .adaptive_threshold(0.1)
+            .adaptive_depth(3)
+            .build();
+        let (image, stats) = c.render_with_stats(&w);
+        assert_eq!(stats.primary_samples, 21 * 21);
+        assert!(stats.extra_samples > 0);
+        assert!(stats.extra_samples < 21 * 21 * 4);
+
+        // The background corner is far from any edge and keeps its single
+        // centre sample.
+        assert_eq!(image.pixel_at(0, 0).unwrap(), color(0, 0, 0));
+    }
+
+    #[test]
+    fn deeper_adaptive_refinement_takes_more_samples() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let shallow = camera(21, 21)
+            .transform(t)
+            .adaptive_threshold(0.05)
+            .adaptive_depth(1)
+            .build();
+        let deep = camera(21, 21)
+            .transform(t)
+            .adaptive_threshold(0.05)
+            .adaptive_depth(3)
+            .build();
+        let (_, shallow_stats) = shallow.render_with_stats(&w);
+        let (_, deep_stats) = deep.render_with_stats(&w);
+        assert!(deep_stats.extra_samples > shallow_stats.extra_samples);
+    }
+
+    #[test]
+    fn parallel_adaptive_rendering_matches_sequential() {
+        let w = default_world();
+        let t = transform::view_transform(point(0, 0, -5), point(0, 0, 0), vector(0, 1, 0));
+        let seq = camera(11, 11).transform(t).adaptive_threshold(0.1).build();
+        let par = camera(11, 11)
+            .transform(t)
+            .adaptive_threshold(0.1)
+            .parallel(true)
+            .build();
+        assert_eq!(seq.render_with_stats(&w), par.render_with_stats(&w));
+    }
 }
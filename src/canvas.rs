@@ -1,8 +1,18 @@
-use std::fmt::Write;
+use std::{fs::File, io::BufReader, path::Path};
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 
-use crate::{Color, clamp, color::BLACK};
+use crate::{Color, ToneMapping, clamp, color::BLACK};
+
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+mod ppm;
+
+pub use exr::{ExrCompression, ExrImage, ExrLayer, ExrPixelType};
+pub use png::PngBitDepth;
+pub use ppm::PpmFormat;
 
 #[must_use]
 pub fn canvas(width: usize, height: usize) -> Canvas {
@@ -48,38 +58,69 @@ impl Canvas {
         self.pixels.get(index).copied()
     }
 
-    /// # Errors
-    /// Returns an error if writing to the output string fails.
-    pub fn to_ppm(&self) -> Result<String> {
-        let mut output = String::new();
-        writeln!(output, "P3")?;
-        writeln!(output, "{} {}", self.width, self.height)?;
-        writeln!(output, "255")?;
-
-        for y in 0..self.height {
-            let offset = y * self.width;
-            let codes = self.pixels[offset..]
-                .iter()
-                .take(self.width)
-                .flat_map(|p| [p.red(), p.green(), p.blue()])
-                .map(|n| {
-                    let scaled = clamp(n * 255.0, 0.0, 255.0).round();
-                    scaled.to_string()
-                })
-                .collect::<Vec<String>>();
-            for line in codes.chunks(17) {
-                writeln!(output, "{}", line.join(" "))?;
-            }
+    /// Applies `mapping` to every pixel in place, leaving display-ready values
+    /// for any of the output formats.
+    pub fn tone_map(&mut self, mapping: &ToneMapping) {
+        for pixel in &mut self.pixels {
+            *pixel = mapping.apply(*pixel);
         }
+    }
+
+    /// Returns a copy of the canvas with `mapping` applied to every pixel.
+    #[must_use]
+    pub fn tone_mapped(&self, mapping: &ToneMapping) -> Canvas {
+        let mut mapped = self.clone();
+        mapped.tone_map(mapping);
+        mapped
+    }
 
-        writeln!(output)?;
+    /// Reads an image file, choosing the format from its extension: `ppm`,
+    /// `png`, `hdr`, `pfm` or `exr`.
+    ///
+    /// # Errors
+    /// Returns an error if the extension is not recognised, or the file
+    /// cannot be read or is not a valid image.
+    pub fn open(path: impl AsRef<Path>) -> Result<Canvas> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let open = || -> Result<BufReader<File>> { Ok(BufReader::new(File::open(path)?)) };
 
-        Ok(output)
+        match extension.as_deref() {
+            Some("ppm") => Canvas::from_ppm(open()?),
+            Some("png") => Canvas::from_png(open()?),
+            Some("hdr") => Canvas::from_hdr(open()?),
+            Some("pfm") => Canvas::from_pfm(open()?),
+            Some("exr") => Canvas::from_exr(open()?),
+            _ => bail!("unsupported image format"),
+        }
+        .with_context(|| format!("failed to read image {}", path.display()))
+    }
+
+    /// # Errors
+    /// Returns an error if writing to the output string fails.
+    pub fn to_ppm(&self) -> Result<String> {
+        let mut output = vec![];
+        self.write_ppm(&mut output)
+            .format(PpmFormat::Plain)
+            .write()?;
+        Ok(String::from_utf8(output)?)
     }
 }
 
+/// Quantises a colour channel to a byte, clamping it to `0.0..=1.0`.
+fn to_byte(n: f32) -> u8 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let byte = clamp(n * 255.0, 0.0, 255.0).round() as u8;
+    byte
+}
+
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::*;
     use crate::color;
 
@@ -165,4 +206,74 @@ mod tests {
         let ppm = c.to_ppm().unwrap();
         assert!(ppm.ends_with('\n'));
     }
+
+    #[test]
+    fn tone_mapping_a_canvas() {
+        let mut c = canvas(2, 1);
+        c.write_pixel(0, 0, color(3, 1, 0)).unwrap();
+        let mapping = crate::tone_mapping()
+            .operator(crate::ToneOperator::Reinhard)
+            .srgb(false)
+            .build();
+        let mapped = c.tone_mapped(&mapping);
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(3, 1, 0));
+        assert_eq!(mapped.pixel_at(0, 0).unwrap(), color(0.75, 0.5, 0));
+
+        c.tone_map(&mapping);
+        assert_eq!(c, mapped);
+    }
+
+    #[test]
+    fn tone_mapping_while_writing() {
+        let mut c = canvas(2, 1);
+        c.write_pixel(0, 0, color(3, 0.5, 0)).unwrap();
+        c.write_pixel(1, 0, color(2, 0.1, 0)).unwrap();
+        let mapping = crate::tone_mapping()
+            .operator(crate::ToneOperator::Reinhard)
+            .build();
+
+        let mut ppm = vec![];
+        c.write_ppm(&mut ppm)
+            .format(PpmFormat::Plain)
+            .tone(&mapping)
+            .write()
+            .unwrap();
+        assert_eq!(ppm, b"P3\n2 1\n255\n225 156 0 213 85 0\n\n");
+
+        let mut png = vec![];
+        c.write_png(&mut png).tone(&mapping).write().unwrap();
+        let read = Canvas::from_png(png.as_slice()).unwrap();
+        assert_eq!(
+            read.pixel_at(0, 0).unwrap(),
+            color(225.0 / 255.0, 156.0 / 255.0, 0)
+        );
+        assert_eq!(
+            read.pixel_at(1, 0).unwrap(),
+            color(213.0 / 255.0, 85.0 / 255.0, 0)
+        );
+    }
+
+    #[test]
+    fn opening_image_files_by_extension() {
+        let dir = std::env::temp_dir().join(format!("canvas-open-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut c = canvas(2, 1);
+        c.write_pixel(1, 0, color(1, 0, 0)).unwrap();
+
+        let ppm = dir.join("image.PPM");
+        c.write_ppm(File::create(&ppm).unwrap())
+            .format(PpmFormat::Binary)
+            .write()
+            .unwrap();
+        let pfm = dir.join("image.pfm");
+        c.write_pfm(File::create(&pfm).unwrap()).unwrap();
+        let opened = [Canvas::open(&ppm), Canvas::open(&pfm)];
+        let unknown = Canvas::open(dir.join("image.gif"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        for image in opened {
+            assert_eq!(image.unwrap(), c);
+        }
+        assert!(unknown.is_err());
+    }
 }
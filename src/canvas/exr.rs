@@ -0,0 +1,923 @@
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result, bail, ensure};
+use bon::bon;
+
+use super::Canvas;
+use crate::{
+    Color, color,
+    deflate::{zlib_compress, zlib_decompress},
+};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: u32 = 2;
+const TILED_FLAG: u32 = 0x200;
+const LONG_NAMES_FLAG: u32 = 0x400;
+const DEEP_FLAG: u32 = 0x800;
+const MULTIPART_FLAG: u32 = 0x1000;
+/// The most that DEFLATE can shrink its input by.
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+/// How scanline blocks are compressed in OpenEXR output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrCompression {
+    #[default]
+    None,
+    /// Deflate applied to each scanline on its own.
+    Zips,
+    /// Deflate applied to blocks of sixteen scanlines.
+    Zip,
+}
+
+impl ExrCompression {
+    fn code(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zips => 2,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<ExrCompression> {
+        match code {
+            0 => Ok(ExrCompression::None),
+            2 => Ok(ExrCompression::Zips),
+            3 => Ok(ExrCompression::Zip),
+            _ => bail!("unsupported OpenEXR compression {code}"),
+        }
+    }
+
+    fn lines_per_block(self) -> usize {
+        match self {
+            ExrCompression::None | ExrCompression::Zips => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// Sample format of the channels in OpenEXR output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrPixelType {
+    /// 16-bit floating point.
+    #[default]
+    Half,
+    /// 32-bit floating point.
+    Float,
+}
+
+impl ExrPixelType {
+    fn code(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+}
+
+/// A named group of extra channels written alongside the colour channels of
+/// an OpenEXR image, such as depth or normals. Each channel is stored as
+/// `layer.channel` and holds one value per pixel in row order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrLayer {
+    name: String,
+    channels: Vec<(String, Vec<f32>)>,
+}
+
+impl ExrLayer {
+    #[must_use]
+    pub fn new(name: &str) -> ExrLayer {
+        ExrLayer {
+            name: name.to_string(),
+            channels: vec![],
+        }
+    }
+
+    /// Creates a layer with `R`, `G` and `B` channels taken from `canvas`.
+    #[must_use]
+    pub fn rgb(name: &str, canvas: &Canvas) -> ExrLayer {
+        ExrLayer::from_canvas(name, canvas, ["R", "G", "B"])
+    }
+
+    /// Creates a layer with `X`, `Y` and `Z` channels taken from the red,
+    /// green and blue components of `canvas`, for vector data such as
+    /// normals.
+    #[must_use]
+    pub fn xyz(name: &str, canvas: &Canvas) -> ExrLayer {
+        ExrLayer::from_canvas(name, canvas, ["X", "Y", "Z"])
+    }
+
+    /// Adds a channel to the layer.
+    #[must_use]
+    pub fn channel(mut self, name: &str, samples: &[f32]) -> ExrLayer {
+        self.channels.push((name.to_string(), samples.to_vec()));
+        self
+    }
+
+    fn from_canvas(name: &str, canvas: &Canvas, channels: [&str; 3]) -> ExrLayer {
+        let [red, green, blue] = [Color::red, Color::green, Color::blue]
+            .map(|component| canvas.pixels.iter().map(component).collect::<Vec<_>>());
+        ExrLayer::new(name)
+            .channel(channels[0], &red)
+            .channel(channels[1], &green)
+            .channel(channels[2], &blue)
+    }
+}
+
+/// The channels of an OpenEXR image, as read by [`ExrImage::from_exr`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrImage {
+    pub width: usize,
+    pub height: usize,
+    channels: Vec<(String, Vec<f32>)>,
+}
+
+impl ExrImage {
+    /// Reads a single-part scanline OpenEXR image that is uncompressed or
+    /// uses ZIP or ZIPS compression. Every channel is converted to `f32`.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails or the data is not a supported
+    /// OpenEXR image.
+    pub fn from_exr<R: Read>(mut reader: R) -> Result<ExrImage> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let mut bytes = Bytes {
+            data: &data,
+            pos: 0,
+        };
+        ensure!(bytes.take(4)? == MAGIC, "missing OpenEXR magic number");
+        let version = bytes.u32()?;
+        ensure!(version & 0xff == VERSION, "unsupported OpenEXR version");
+        ensure!(
+            version & (TILED_FLAG | DEEP_FLAG | MULTIPART_FLAG) == 0,
+            "only single-part scanline OpenEXR images are supported"
+        );
+
+        let mut channels = None;
+        let mut compression = None;
+        let mut data_window = None;
+        loop {
+            let name = bytes.string()?;
+            if name.is_empty() {
+                break;
+            }
+            let _kind = bytes.string()?;
+            let size = usize::try_from(bytes.i32()?).context("negative attribute size")?;
+            let mut value = Bytes {
+                data: bytes.take(size)?,
+                pos: 0,
+            };
+            match name.as_str() {
+                "channels" => channels = Some(read_channels(&mut value)?),
+                "compression" => compression = Some(ExrCompression::from_code(value.u8()?)?),
+                "dataWindow" => {
+                    data_window = Some([value.i32()?, value.i32()?, value.i32()?, value.i32()?]);
+                }
+                _ => {}
+            }
+        }
+
+        let channels = channels.context("OpenEXR header has no channels")?;
+        let compression = compression.context("OpenEXR header has no compression")?;
+        let [x_min, y_min, x_max, y_max] =
+            data_window.context("OpenEXR header has no data window")?;
+        let width = usize::try_from(i64::from(x_max) - i64::from(x_min) + 1)
+            .context("invalid OpenEXR data window")?;
+        let height = usize::try_from(i64::from(y_max) - i64::from(y_min) + 1)
+            .context("invalid OpenEXR data window")?;
+
+        let too_large = "OpenEXR data window is too large";
+        let line_size = channels
+            .iter()
+            .try_fold(0usize, |size, (_, kind)| {
+                size.checked_add(kind.size().checked_mul(width)?)
+            })
+            .context(too_large)?;
+        let raster_size = line_size.checked_mul(height).context(too_large)?;
+        let pixel_count = width.checked_mul(height).context(too_large)?;
+        let max_ratio = if compression == ExrCompression::None {
+            1
+        } else {
+            MAX_DEFLATE_RATIO
+        };
+        ensure!(
+            raster_size <= data.len().saturating_mul(max_ratio),
+            "OpenEXR data is truncated"
+        );
+
+        let lines_per_block = compression.lines_per_block();
+        let blocks = height.div_ceil(lines_per_block);
+        let offsets = (0..blocks)
+            .map(|_| bytes.u64())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut samples: Vec<Vec<f32>> = channels.iter().map(|_| vec![0.0; pixel_count]).collect();
+
+        for offset in offsets {
+            let mut block = Bytes {
+                data: &data,
+                pos: usize::try_from(offset)?,
+            };
+            let y = i64::from(block.i32()?) - i64::from(y_min);
+            let first_line = usize::try_from(y)
+                .ok()
+                .filter(|&y| y < height)
+                .context("OpenEXR block is outside the data window")?;
+            let size = usize::try_from(block.i32()?).context("negative block size")?;
+            let packed = block.take(size)?;
+
+            let lines = lines_per_block.min(height - first_line);
+            let expected = lines * line_size;
+            let raw = if size == expected {
+                packed.to_vec()
+            } else {
+                ensure!(
+                    compression != ExrCompression::None,
+                    "OpenEXR block has {size} bytes but {expected} were expected"
+                );
+                let raw = zip_decode(&zlib_decompress(packed)?);
+                ensure!(
+                    raw.len() == expected,
+                    "OpenEXR block decompressed to wrong size"
+                );
+                raw
+            };
+
+            let mut raw = Bytes { data: &raw, pos: 0 };
+            for line in first_line..first_line + lines {
+                for ((_, kind), buffer) in channels.iter().zip(&mut samples) {
+                    for sample in &mut buffer[line * width..(line + 1) * width] {
+                        *sample = kind.read(&mut raw)?;
+                    }
+                }
+            }
+        }
+
+        Ok(ExrImage {
+            width,
+            height,
+            channels: channels
+                .into_iter()
+                .map(|(name, _)| name)
+                .zip(samples)
+                .collect(),
+        })
+    }
+
+    /// Returns the names of the channels in the order they are stored.
+    pub fn channel_names(&self) -> impl Iterator<Item = &str> {
+        self.channels.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the samples of the named channel in row order.
+    #[must_use]
+    pub fn channel(&self, name: &str) -> Option<&[f32]> {
+        self.channels
+            .iter()
+            .find(|(channel, _)| channel == name)
+            .map(|(_, samples)| samples.as_slice())
+    }
+
+    /// Builds a canvas from the `R`, `G` and `B` channels.
+    ///
+    /// # Errors
+    /// Returns an error if any of the channels is missing.
+    pub fn canvas(&self) -> Result<Canvas> {
+        let [red, green, blue] = ["R", "G", "B"].map(|name| self.channel(name));
+        let (Some(red), Some(green), Some(blue)) = (red, green, blue) else {
+            bail!("OpenEXR image has no RGB channels");
+        };
+
+        Ok(Canvas {
+            width: self.width,
+            height: self.height,
+            pixels: red
+                .iter()
+                .zip(green)
+                .zip(blue)
+                .map(|((&r, &g), &b)| color(r, g, b))
+                .collect(),
+        })
+    }
+}
+
+#[bon]
+impl Canvas {
+    /// Writes the canvas as a scanline OpenEXR image with `R`, `G` and `B`
+    /// channels holding the unclamped linear colour. If `alpha` is given it
+    /// must hold one coverage value per pixel in row order and is written as
+    /// an `A` channel. Each of `layers` adds its channels to the same file.
+    ///
+    /// # Errors
+    /// Returns an error if `alpha` or a layer has the wrong number of
+    /// values, two channels share a name, or writing to `writer` fails.
+    #[builder(finish_fn = write)]
+    pub fn write_exr<W: Write>(
+        &self,
+        #[builder(start_fn)] mut writer: W,
+        #[builder(default)] compression: ExrCompression,
+        #[builder(default)] pixel_type: ExrPixelType,
+        alpha: Option<&[f32]>,
+        #[builder(default)] layers: &[ExrLayer],
+    ) -> Result<()> {
+        let pixel_count = self.pixels.len();
+        let [red, green, blue] = [Color::red, Color::green, Color::blue]
+            .map(|component| self.pixels.iter().map(component).collect::<Vec<_>>());
+
+        let mut channels: Vec<(String, &[f32])> = vec![
+            ("R".to_string(), &red),
+            ("G".to_string(), &green),
+            ("B".to_string(), &blue),
+        ];
+        if let Some(alpha) = alpha {
+            channels.push(("A".to_string(), alpha));
+        }
+        for layer in layers {
+            for (name, samples) in &layer.channels {
+                channels.push((format!("{}.{name}", layer.name), samples));
+            }
+        }
+
+        for (name, samples) in &channels {
+            ensure!(
+                samples.len() == pixel_count,
+                "channel {name} has {} values but the canvas has {pixel_count} pixels",
+                samples.len()
+            );
+            ensure!(
+                !name.is_empty() && name.len() < 256,
+                "invalid OpenEXR channel name {name:?}"
+            );
+        }
+        channels.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if let Some(pair) = channels.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            bail!("duplicate OpenEXR channel {}", pair[0].0);
+        }
+
+        let x_max = i32::try_from(self.width).context("canvas is too wide for OpenEXR")? - 1;
+        let y_max = i32::try_from(self.height).context("canvas is too tall for OpenEXR")? - 1;
+
+        let mut header = vec![];
+        header.extend(MAGIC);
+        let long_names = channels.iter().any(|(name, _)| name.len() > 31);
+        let version = VERSION | if long_names { LONG_NAMES_FLAG } else { 0 };
+        header.extend(version.to_le_bytes());
+
+        let mut list = vec![];
+        for (name, _) in &channels {
+            list.extend(name.as_bytes());
+            list.push(0);
+            list.extend(pixel_type.code().to_le_bytes());
+            list.extend([0, 0, 0, 0]);
+            list.extend(1i32.to_le_bytes());
+            list.extend(1i32.to_le_bytes());
+        }
+        list.push(0);
+        let window: Vec<u8> = [0, 0, x_max, y_max]
+            .iter()
+            .flat_map(|n| n.to_le_bytes())
+            .collect();
+
+        write_attribute(&mut header, "channels", "chlist", &list)?;
+        write_attribute(
+            &mut header,
+            "compression",
+            "compression",
+            &[compression.code()],
+        )?;
+        write_attribute(&mut header, "dataWindow", "box2i", &window)?;
+        write_attribute(&mut header, "displayWindow", "box2i", &window)?;
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        )?;
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        )?;
+        header.push(0);
+
+        let lines_per_block = compression.lines_per_block();
+        let mut blocks = vec![];
+        for first_line in (0..self.height).step_by(lines_per_block) {
+            let lines = first_line..self.height.min(first_line + lines_per_block);
+            let mut raw = vec![];
+            for line in lines {
+                let row = line * self.width..(line + 1) * self.width;
+                for (_, samples) in &channels {
+                    for &sample in &samples[row.clone()] {
+                        match pixel_type {
+                            ExrPixelType::Half => raw.extend(to_half(sample).to_le_bytes()),
+                            ExrPixelType::Float => raw.extend(sample.to_le_bytes()),
+                        }
+                    }
+                }
+            }
+
+            let packed = match compression {
+                ExrCompression::None => raw,
+                ExrCompression::Zips | ExrCompression::Zip => {
+                    let packed = zlib_compress(&zip_encode(&raw));
+                    // Readers treat a block as uncompressed when it is full size
+                    if packed.len() < raw.len() {
+                        packed
+                    } else {
+                        raw
+                    }
+                }
+            };
+
+            let mut block = vec![];
+            block.extend(i32::try_from(first_line)?.to_le_bytes());
+            block.extend(u32::try_from(packed.len())?.to_le_bytes());
+            block.extend(packed);
+            blocks.push(block);
+        }
+
+        let mut offset = u64::try_from(header.len() + blocks.len() * 8)?;
+        writer.write_all(&header)?;
+        for block in &blocks {
+            writer.write_all(&offset.to_le_bytes())?;
+            offset += u64::try_from(block.len())?;
+        }
+        for block in &blocks {
+            writer.write_all(block)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads the `R`, `G` and `B` channels of an OpenEXR image. See
+    /// [`ExrImage::from_exr`] for the supported files.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails, the data is not a supported OpenEXR
+    /// image, or it has no RGB channels.
+    pub fn from_exr<R: Read>(reader: R) -> Result<Canvas> {
+        ExrImage::from_exr(reader)?.canvas()
+    }
+}
+
+/// Sample formats that can appear in a channel list.
+#[derive(Clone, Copy, Debug)]
+enum SampleKind {
+    Uint,
+    Half,
+    Float,
+}
+
+impl SampleKind {
+    fn size(self) -> usize {
+        match self {
+            SampleKind::Half => 2,
+            SampleKind::Uint | SampleKind::Float => 4,
+        }
+    }
+
+    fn read(self, bytes: &mut Bytes) -> Result<f32> {
+        Ok(match self {
+            SampleKind::Half => from_half(u16::from_le_bytes(bytes.array()?)),
+            SampleKind::Float => f32::from_le_bytes(bytes.array()?),
+            #[allow(clippy::cast_precision_loss)]
+            SampleKind::Uint => bytes.u32()? as f32,
+        })
+    }
+}
+
+fn read_channels(bytes: &mut Bytes) -> Result<Vec<(String, SampleKind)>> {
+    let mut channels = vec![];
+    loop {
+        let name = bytes.string()?;
+        if name.is_empty() {
+            return Ok(channels);
+        }
+        let kind = match bytes.i32()? {
+            0 => SampleKind::Uint,
+            1 => SampleKind::Half,
+            2 => SampleKind::Float,
+            code => bail!("unsupported OpenEXR pixel type {code}"),
+        };
+        bytes.take(4)?;
+        let sampling = (bytes.i32()?, bytes.i32()?);
+        ensure!(
+            sampling == (1, 1),
+            "subsampled OpenEXR channel {name} is not supported"
+        );
+        channels.push((name, kind));
+    }
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) -> Result<()> {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend(i32::try_from(value.len())?.to_le_bytes());
+    header.extend(value);
+    Ok(())
+}
+
+/// Little-endian reader over an in-memory OpenEXR file.
+struct Bytes<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.pos..end))
+            .context("OpenEXR data is truncated")?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let rest = self.data.get(self.pos..).unwrap_or_default();
+        let length = rest
+            .iter()
+            .position(|&b| b == 0)
+            .context("OpenEXR string is not terminated")?;
+        self.pos += length + 1;
+        Ok(String::from_utf8_lossy(&rest[..length]).into_owned())
+    }
+}
+
+/// Splits the even and odd bytes into two halves and replaces each byte by
+/// its difference from the previous one, which helps deflate on float data.
+fn zip_encode(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut encoded = vec![0; raw.len()];
+    for (i, &byte) in raw.iter().enumerate() {
+        let index = if i % 2 == 0 { i / 2 } else { half + i / 2 };
+        encoded[index] = byte;
+    }
+
+    let mut previous = encoded.first().copied().unwrap_or_default();
+    for byte in encoded.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    encoded
+}
+
+/// Reverses [`zip_encode`].
+fn zip_decode(encoded: &[u8]) -> Vec<u8> {
+    let mut decoded = encoded.to_vec();
+    for i in 1..decoded.len() {
+        decoded[i] = decoded[i - 1].wrapping_add(decoded[i]).wrapping_sub(128);
+    }
+
+    let half = decoded.len().div_ceil(2);
+    let (even, odd) = decoded.split_at(half);
+    let mut raw = Vec::with_capacity(decoded.len());
+    for (i, &byte) in even.iter().enumerate() {
+        raw.push(byte);
+        if let Some(&byte) = odd.get(i) {
+            raw.push(byte);
+        }
+    }
+    raw
+}
+
+/// Converts to a 16-bit float, rounding to nearest even. Values too large
+/// for a half become infinite.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = u16::try_from((bits >> 16) & 0x8000).unwrap_or_default();
+    let exponent = i32::try_from((bits >> 23) & 0xff).unwrap_or_default();
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa == 0 { 0 } else { 0x200 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Normal halves drop 13 mantissa bits; subnormals also shift out the
+    // implicit leading one
+    let (base, full, shift) = if exponent > 0 {
+        (
+            u32::try_from(exponent).unwrap_or_default() << 10,
+            mantissa,
+            13,
+        )
+    } else if exponent >= -10 {
+        let shift = u32::try_from(14 - exponent).unwrap_or_default();
+        (0, mantissa | 0x80_0000, shift)
+    } else {
+        return sign;
+    };
+    let value = base | (full >> shift);
+
+    let remainder = full & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if remainder > halfway || (remainder == halfway && value & 1 == 1) {
+        value + 1
+    } else {
+        value
+    };
+
+    // Rounding may carry into the exponent, which is the correct result
+    sign | u16::try_from(rounded).unwrap_or(0x7c00)
+}
+
+fn from_half(half: u16) -> f32 {
+    let negative = half & 0x8000 != 0;
+    let exponent = u32::from(half >> 10) & 0x1f;
+    let mantissa = u32::from(half & 0x3ff);
+
+    let magnitude = match exponent {
+        0 => f32::from(half & 0x3ff) * 2f32.powi(-24),
+        0x1f => f32::from_bits(0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    };
+    if negative { -magnitude } else { magnitude }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, canvas};
+
+    fn sample_canvas() -> Canvas {
+        let mut c = canvas(5, 19);
+        for y in 0..c.height {
+            for x in 0..c.width {
+                #[allow(clippy::cast_precision_loss)]
+                let (u, v) = (x as f32, y as f32);
+                let pixel = color(u * 0.25, v * 3.5, -0.125 + u * v / 64.0);
+                c.write_pixel(x, y, pixel).unwrap();
+            }
+        }
+        c
+    }
+
+    fn ramp(count: usize) -> Vec<f32> {
+        #[allow(clippy::cast_precision_loss)]
+        (0..count).map(|n| n as f32 * 0.5).collect()
+    }
+
+    #[test]
+    fn converting_to_half() {
+        assert_eq!(to_half(0.0), 0x0000);
+        assert_eq!(to_half(-0.0), 0x8000);
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(0.5), 0x3800);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(65504.0), 0x7bff);
+        assert_eq!(to_half(1e6), 0x7c00);
+        assert_eq!(to_half(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(to_half(2f32.powi(-14)), 0x0400);
+        assert_eq!(to_half(1e-9), 0x0000);
+        assert!(from_half(to_half(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn half_conversion_rounds_to_nearest_even() {
+        // Halfway between 1.0 and the next half rounds down to the even value
+        assert_eq!(to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(to_half(65519.0), 0x7bff);
+        assert_eq!(to_half(65520.0), 0x7c00);
+    }
+
+    #[test]
+    fn converting_from_half() {
+        assert_eq!(from_half(0x3c00), 1.0);
+        assert_eq!(from_half(0xc000), -2.0);
+        assert_eq!(from_half(0x7bff), 65504.0);
+        assert_eq!(from_half(0x0001), 2f32.powi(-24));
+        assert_eq!(from_half(0x7c00), f32::INFINITY);
+        assert_relative_eq!(from_half(to_half(0.1)), 0.1, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn zip_prediction_round_trip() {
+        let raw: Vec<u8> = (0..=255).chain([7, 7, 0]).collect();
+        let encoded = zip_encode(&raw);
+        assert_eq!(encoded[..3], [0, 130, 130]);
+        assert_eq!(zip_decode(&encoded), raw);
+    }
+
+    #[test]
+    fn exr_header() {
+        let mut output = vec![];
+        canvas(2, 1).write_exr(&mut output).write().unwrap();
+        assert_eq!(output[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let list = b"channels\0chlist\0";
+        assert_eq!(output[8..8 + list.len()], *list);
+        let size = i32::from_le_bytes(output[24..28].try_into().unwrap());
+        assert_eq!(size, 3 * 18 + 1);
+        assert_eq!(output[28..30], *b"B\0");
+        assert_eq!(output[30..34], 1i32.to_le_bytes());
+    }
+
+    #[test]
+    fn uncompressed_exr_has_one_block_per_line() {
+        let mut output = vec![];
+        canvas(4, 3)
+            .write_exr(&mut output)
+            .pixel_type(ExrPixelType::Float)
+            .write()
+            .unwrap();
+        let block_size = 8 + 4 * 3 * 4;
+        let header_size = output.len() - 3 * (8 + block_size);
+        let offset = |n: usize| {
+            let start = header_size + n * 8;
+            u64::from_le_bytes(output[start..start + 8].try_into().unwrap())
+        };
+        assert_eq!(offset(0), (header_size + 24) as u64);
+        assert_eq!(offset(2), (header_size + 24 + 2 * block_size) as u64);
+    }
+
+    #[test]
+    fn zip_compression_shrinks_flat_images() {
+        let c = canvas(64, 64);
+        let mut plain = vec![];
+        c.write_exr(&mut plain).write().unwrap();
+        let mut zipped = vec![];
+        c.write_exr(&mut zipped)
+            .compression(ExrCompression::Zip)
+            .write()
+            .unwrap();
+        assert!(zipped.len() * 10 < plain.len());
+    }
+
+    #[test]
+    fn float_exr_round_trip_is_exact() {
+        let c = sample_canvas();
+        for compression in [
+            ExrCompression::None,
+            ExrCompression::Zips,
+            ExrCompression::Zip,
+        ] {
+            let mut output = vec![];
+            c.write_exr(&mut output)
+                .compression(compression)
+                .pixel_type(ExrPixelType::Float)
+                .write()
+                .unwrap();
+            assert_eq!(Canvas::from_exr(output.as_slice()).unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn half_exr_round_trip() {
+        let c = sample_canvas();
+        let mut output = vec![];
+        c.write_exr(&mut output)
+            .compression(ExrCompression::Zip)
+            .write()
+            .unwrap();
+        let read = Canvas::from_exr(output.as_slice()).unwrap();
+        for (a, b) in c.pixels.iter().zip(&read.pixels) {
+            for (ca, cb) in [
+                (a.red(), b.red()),
+                (a.green(), b.green()),
+                (a.blue(), b.blue()),
+            ] {
+                assert_relative_eq!(ca, cb, epsilon = ca.abs().max(EPSILON) / 1024.0);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_with_alpha_and_layers() {
+        let c = sample_canvas();
+        let pixels = c.width * c.height;
+        let alpha = vec![0.75; pixels];
+        let depth = ramp(pixels);
+        let layers = [
+            ExrLayer::new("depth").channel("Z", &depth),
+            ExrLayer::xyz("normal", &c),
+        ];
+
+        let mut output = vec![];
+        c.write_exr(&mut output)
+            .compression(ExrCompression::Zips)
+            .pixel_type(ExrPixelType::Float)
+            .alpha(&alpha)
+            .layers(&layers)
+            .write()
+            .unwrap();
+
+        let image = ExrImage::from_exr(output.as_slice()).unwrap();
+        assert_eq!((image.width, image.height), (c.width, c.height));
+        assert_eq!(
+            image.channel_names().collect::<Vec<_>>(),
+            [
+                "A", "B", "G", "R", "depth.Z", "normal.X", "normal.Y", "normal.Z"
+            ]
+        );
+        assert_eq!(image.channel("A").unwrap(), alpha);
+        assert_eq!(image.channel("depth.Z").unwrap(), depth);
+        let normal_x = c.pixels.iter().map(Color::red).collect::<Vec<_>>();
+        assert_eq!(image.channel("normal.X").unwrap(), normal_x);
+        assert_eq!(image.canvas().unwrap(), c);
+    }
+
+    #[test]
+    fn exr_layer_with_wrong_length_is_an_error() {
+        let layers = [ExrLayer::new("depth").channel("Z", &[1.0])];
+        let result = canvas(2, 2).write_exr(vec![]).layers(&layers).write();
+        assert!(result.is_err());
+        let result = canvas(2, 2).write_exr(vec![]).alpha(&[1.0; 3]).write();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn duplicate_exr_channels_are_an_error() {
+        let c = canvas(1, 1);
+        let layers = [ExrLayer::rgb("albedo", &c), ExrLayer::rgb("albedo", &c)];
+        assert!(c.write_exr(vec![]).layers(&layers).write().is_err());
+    }
+
+    #[test]
+    fn unsupported_exr_is_an_error() {
+        assert!(Canvas::from_exr(&b"P6\n1 1\n255\n"[..]).is_err());
+
+        let mut tiled = vec![];
+        canvas(1, 1).write_exr(&mut tiled).write().unwrap();
+        tiled[5] |= 0x02;
+        assert!(Canvas::from_exr(tiled.as_slice()).is_err());
+
+        let mut truncated = vec![];
+        canvas(4, 4).write_exr(&mut truncated).write().unwrap();
+        truncated.truncate(truncated.len() - 1);
+        assert!(Canvas::from_exr(truncated.as_slice()).is_err());
+
+        let image = ExrImage {
+            width: 1,
+            height: 1,
+            channels: vec![("Y".to_string(), vec![0.5])],
+        };
+        assert!(image.canvas().is_err());
+    }
+
+    #[test]
+    fn oversized_exr_data_window_is_an_error() {
+        let with_window = |x_max: i32, y_max: i32| {
+            let mut output = vec![];
+            canvas(2, 1).write_exr(&mut output).write().unwrap();
+            let name = b"dataWindow\0box2i\0";
+            let start = output
+                .windows(name.len())
+                .position(|window| window == name)
+                .unwrap()
+                + name.len()
+                + 4;
+            output[start + 8..start + 12].copy_from_slice(&x_max.to_le_bytes());
+            output[start + 12..start + 16].copy_from_slice(&y_max.to_le_bytes());
+            ExrImage::from_exr(output.as_slice())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            with_window(i32::MAX, i32::MAX),
+            "OpenEXR data window is too large"
+        );
+        assert_eq!(with_window(99_999, 99_999), "OpenEXR data is truncated");
+    }
+}
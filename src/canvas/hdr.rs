@@ -0,0 +1,400 @@
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use super::Canvas;
+use crate::{Color, color};
+
+/// Runs shorter than this are cheaper to store as literals.
+const MIN_RUN: usize = 4;
+/// Scanlines outside this range cannot use run-length encoding.
+const RLE_WIDTHS: std::ops::Range<usize> = 8..0x8000;
+
+impl Canvas {
+    /// Writes the canvas as a Radiance RGBE (`.hdr`) image with run-length
+    /// encoded scanlines. Channels keep their full range above `1.0`, with
+    /// roughly eight bits of relative precision; negative values are written
+    /// as zero.
+    ///
+    /// # Errors
+    /// Returns an error if writing to `writer` fails.
+    pub fn write_hdr<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(
+            writer,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+
+        let mut line = Vec::with_capacity(self.width * 4);
+        let mut channel = Vec::with_capacity(self.width);
+        for pixels in self.pixels.chunks(self.width.max(1)) {
+            line.clear();
+            let rgbe: Vec<[u8; 4]> = pixels.iter().map(|&p| to_rgbe(p)).collect();
+
+            if RLE_WIDTHS.contains(&self.width) {
+                let [high, low] = u16::try_from(self.width)?.to_be_bytes();
+                line.extend([2, 2, high, low]);
+                for i in 0..4 {
+                    channel.clear();
+                    channel.extend(rgbe.iter().map(|bytes| bytes[i]));
+                    encode_run_lengths(&channel, &mut line);
+                }
+            } else {
+                line.extend(rgbe.iter().flatten());
+            }
+
+            writer.write_all(&line)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a Radiance RGBE (`.hdr`) image in the standard `-Y height +X
+    /// width` orientation, with flat or run-length encoded scanlines.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails or the data is not a supported
+    /// Radiance image.
+    pub fn from_hdr<R: Read>(mut reader: R) -> Result<Canvas> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let mut pos = 0;
+        let magic = header_line(&data, &mut pos)?;
+        ensure!(magic.starts_with("#?"), "missing Radiance magic number");
+        loop {
+            let line = header_line(&data, &mut pos)?;
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                ensure!(
+                    format.trim() == "32-bit_rle_rgbe",
+                    "unsupported Radiance format {format:?}"
+                );
+            }
+        }
+
+        let resolution = header_line(&data, &mut pos)?;
+        let (height, width): (usize, usize) =
+            match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+                ["-Y", height, "+X", width] => (height.parse()?, width.parse()?),
+                _ => bail!("unsupported Radiance resolution {resolution:?}"),
+            };
+
+        let pixel_count = width
+            .checked_mul(height)
+            .context("Radiance dimensions are too large")?;
+        let raster_size = min_scanline_size(width)
+            .and_then(|n| n.checked_mul(height))
+            .context("Radiance dimensions are too large")?;
+        ensure!(
+            data.len() - pos >= raster_size,
+            "Radiance raster is truncated"
+        );
+
+        let mut pixels = Vec::with_capacity(pixel_count);
+        for _ in 0..height {
+            let rgbe = decode_scanline(&data, &mut pos, width)?;
+            pixels.extend(rgbe.iter().map(|&bytes| from_rgbe(bytes)));
+        }
+
+        Ok(Canvas {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+fn to_rgbe(pixel: Color) -> [u8; 4] {
+    let [r, g, b] = [pixel.red(), pixel.green(), pixel.blue()].map(|c| c.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 || !v.is_finite() {
+        return [0; 4];
+    }
+
+    // v = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    #[allow(clippy::cast_possible_truncation)]
+    let mut exponent = v.log2().floor() as i32 + 1;
+    if v / 2f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2f32.powi(exponent);
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let quantise = |c: f32| (c * scale).min(255.0) as u8;
+    let exponent = u8::try_from((exponent + 128).clamp(0, 255)).unwrap_or(0);
+    [quantise(r), quantise(g), quantise(b), exponent]
+}
+
+fn from_rgbe([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return color(0, 0, 0);
+    }
+
+    let scale = 2f32.powi(i32::from(e) - 136);
+    color(
+        f32::from(r) * scale,
+        f32::from(g) * scale,
+        f32::from(b) * scale,
+    )
+}
+
+/// Appends `values` as a sequence of runs (a count above 128 followed by the
+/// repeated byte) and literal spans (a count of at most 128 followed by the
+/// bytes).
+fn encode_run_lengths(values: &[u8], output: &mut Vec<u8>) {
+    let mut pos = 0;
+    while pos < values.len() {
+        let mut run_start = pos;
+        let mut run_length = 0;
+        while run_start < values.len() {
+            run_length = values[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&v| v == values[run_start])
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+            run_length = 0;
+        }
+
+        while pos < run_start {
+            let count = (run_start - pos).min(128);
+            output.push(u8::try_from(count).unwrap_or(128));
+            output.extend_from_slice(&values[pos..pos + count]);
+            pos += count;
+        }
+
+        if run_length >= MIN_RUN {
+            output.push(128 + u8::try_from(run_length).unwrap_or(127));
+            output.push(values[run_start]);
+            pos += run_length;
+        }
+    }
+}
+
+/// Returns the header line starting at `pos` and moves past its newline.
+fn header_line(data: &[u8], pos: &mut usize) -> Result<String> {
+    let rest = data.get(*pos..).unwrap_or_default();
+    let length = rest
+        .iter()
+        .position(|&b| b == b'\n')
+        .context("Radiance header is truncated")?;
+    *pos += length + 1;
+    Ok(String::from_utf8_lossy(&rest[..length]).into_owned())
+}
+
+/// Returns the fewest bytes a scanline of `width` pixels can take: four
+/// channels of maximal runs if it may be run-length encoded, or four bytes
+/// per pixel if not.
+fn min_scanline_size(width: usize) -> Option<usize> {
+    if RLE_WIDTHS.contains(&width) {
+        Some(4 + 4 * 2 * width.div_ceil(127))
+    } else {
+        width.checked_mul(4)
+    }
+}
+
+fn decode_scanline(data: &[u8], pos: &mut usize, width: usize) -> Result<Vec<[u8; 4]>> {
+    let truncated = "Radiance scanline is truncated";
+    let header = data.get(*pos..*pos + 4).context(truncated)?;
+    let encoded_width = usize::from(u16::from_be_bytes([header[2], header[3]]));
+
+    if !(RLE_WIDTHS.contains(&width) && header[..2] == [2, 2] && encoded_width == width) {
+        let bytes = data
+            .get(*pos..)
+            .and_then(|rest| rest.get(..width.checked_mul(4)?))
+            .context(truncated)?;
+        *pos += width * 4;
+        return Ok(bytes
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]])
+            .collect());
+    }
+
+    *pos += 4;
+    let mut scanline = vec![[0u8; 4]; width];
+    for i in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(*pos).context(truncated)?;
+            *pos += 1;
+            if count > 128 {
+                let count = usize::from(count - 128);
+                let value = *data.get(*pos).context(truncated)?;
+                *pos += 1;
+                ensure!(x + count <= width, "Radiance run overruns scanline");
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[i] = value;
+                }
+                x += count;
+            } else {
+                let count = usize::from(count);
+                ensure!(count > 0, "Radiance scanline has an empty span");
+                ensure!(x + count <= width, "Radiance span overruns scanline");
+                let values = data.get(*pos..*pos + count).context(truncated)?;
+                *pos += count;
+                for (pixel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[i] = value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(scanline)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::canvas;
+
+    fn radiance_canvas(width: usize, height: usize) -> Canvas {
+        let mut c = canvas(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                #[allow(clippy::cast_precision_loss)]
+                let pixel = if x < width / 2 {
+                    color(2.5, 0.75, 0.0)
+                } else {
+                    color(x as f32 * 0.37, y as f32 * 12.5, 0.001 * (x + y) as f32)
+                };
+                c.write_pixel(x, y, pixel).unwrap();
+            }
+        }
+        c
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        let tolerance = a.abs().max(b.abs()) / 128.0;
+        assert!(
+            (a - b).abs() <= tolerance,
+            "{a} and {b} differ by more than {tolerance}"
+        );
+    }
+
+    #[test]
+    fn rgbe_encoding_of_known_colors() {
+        assert_eq!(to_rgbe(color(0, 0, 0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(color(1, 1, 1)), [128, 128, 128, 129]);
+        assert_eq!(to_rgbe(color(0.5, 0.25, 0)), [128, 64, 0, 128]);
+        assert_eq!(to_rgbe(color(-1, 4, 0)), [0, 128, 0, 131]);
+    }
+
+    #[test]
+    fn rgbe_decoding_of_known_colors() {
+        assert_eq!(from_rgbe([0, 0, 0, 0]), color(0, 0, 0));
+        assert_eq!(from_rgbe([128, 128, 128, 129]), color(1, 1, 1));
+        assert_eq!(from_rgbe([128, 64, 0, 128]), color(0.5, 0.25, 0));
+    }
+
+    #[test]
+    fn rgbe_keeps_values_above_one() {
+        let decoded = from_rgbe(to_rgbe(color(1000, 1, 0.5)));
+        assert_close(decoded.red(), 1000.0);
+        assert!(decoded.green() < 5.0);
+    }
+
+    #[test]
+    fn hdr_header() {
+        let mut output = vec![];
+        canvas(3, 2).write_hdr(&mut output).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert!(output.starts_with(header));
+        assert_eq!(output.len(), header.len() + 3 * 2 * 4);
+    }
+
+    #[test]
+    fn wide_scanlines_are_run_length_encoded() {
+        let mut c = canvas(64, 1);
+        for x in 0..64 {
+            c.write_pixel(x, 0, color(1, 1, 1)).unwrap();
+        }
+        let mut output = vec![];
+        c.write_hdr(&mut output).unwrap();
+        let header_length = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 64\n".len();
+        assert_eq!(
+            output[header_length..],
+            [2, 2, 0, 64, 192, 128, 192, 128, 192, 128, 192, 129]
+        );
+    }
+
+    #[test]
+    fn run_length_encoding_mixes_runs_and_literals() {
+        let values = [1, 2, 3, 7, 7, 7, 7, 7, 4, 4];
+        let mut output = vec![];
+        encode_run_lengths(&values, &mut output);
+        assert_eq!(output, [3, 1, 2, 3, 133, 7, 2, 4, 4]);
+
+        let mut scanline = vec![2, 2, 0, 10];
+        for _ in 0..4 {
+            scanline.extend(&output);
+        }
+        let mut pos = 0;
+        let decoded = decode_scanline(&scanline, &mut pos, 10).unwrap();
+        let reds: Vec<u8> = decoded.iter().map(|p| p[0]).collect();
+        assert_eq!(reds, values);
+        assert_eq!(pos, scanline.len());
+    }
+
+    #[test]
+    fn hdr_round_trip() {
+        for (width, height) in [(3, 2), (40, 5), (300, 2)] {
+            let c = radiance_canvas(width, height);
+            let mut output = vec![];
+            c.write_hdr(&mut output).unwrap();
+            let read = Canvas::from_hdr(output.as_slice()).unwrap();
+            assert_eq!((read.width, read.height), (width, height));
+            for y in 0..height {
+                for x in 0..width {
+                    let (a, b) = (c.pixel_at(x, y).unwrap(), read.pixel_at(x, y).unwrap());
+                    let brightest = a.red().max(a.green()).max(a.blue());
+                    for (ca, cb) in [
+                        (a.red(), b.red()),
+                        (a.green(), b.green()),
+                        (a.blue(), b.blue()),
+                    ] {
+                        assert_relative_eq!(ca, cb, epsilon = brightest / 128.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reading_flat_hdr_with_extra_header_lines() {
+        let mut data = b"#?RGBE\n# made by hand\nEXPOSURE=1.0\n\n-Y 1 +X 2\n".to_vec();
+        data.extend([128, 128, 128, 129, 0, 0, 128, 130]);
+        let c = Canvas::from_hdr(data.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(1, 1, 1));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(0, 0, 2));
+    }
+
+    #[test]
+    fn unsupported_hdr_is_an_error() {
+        assert!(Canvas::from_hdr(&b"P6\n1 1\n255\n"[..]).is_err());
+        assert!(
+            Canvas::from_hdr(&b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0"[..])
+                .is_err()
+        );
+        assert!(Canvas::from_hdr(&b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0"[..]).is_err());
+        assert!(Canvas::from_hdr(&b"#?RADIANCE\n\n-Y 2 +X 1\n\0\0\0\0"[..]).is_err());
+    }
+
+    #[test]
+    fn oversized_hdr_is_an_error() {
+        let huge = format!("#?RADIANCE\n\n-Y {0} +X {0}\n\0\0\0\0", usize::MAX / 2);
+        assert!(Canvas::from_hdr(huge.as_bytes()).is_err());
+        let wide = b"#?RADIANCE\n\n-Y 100000 +X 30000\n\x02\x02\x75\x30";
+        let error = Canvas::from_hdr(&wide[..]).unwrap_err();
+        assert_eq!(error.to_string(), "Radiance raster is truncated");
+    }
+}
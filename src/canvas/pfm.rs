@@ -0,0 +1,157 @@
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use super::{Canvas, ppm::Tokens};
+use crate::color;
+
+impl Canvas {
+    /// Writes the canvas as a colour Portable FloatMap (`.pfm`) with
+    /// little-endian 32-bit samples. Pixel values are stored exactly, without
+    /// clamping.
+    ///
+    /// # Errors
+    /// Returns an error if writing to `writer` fails.
+    pub fn write_pfm<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+
+        // PFM rows run from the bottom of the image to the top
+        let mut row = Vec::with_capacity(self.width * 12);
+        for pixels in self.pixels.chunks(self.width.max(1)).rev() {
+            row.clear();
+            for pixel in pixels {
+                for channel in [pixel.red(), pixel.green(), pixel.blue()] {
+                    row.extend(channel.to_le_bytes());
+                }
+            }
+            writer.write_all(&row)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a colour (`PF`) or greyscale (`Pf`) Portable FloatMap. The sign
+    /// of the scale factor selects the byte order; its magnitude is ignored.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails or the data is not a valid PFM image.
+    pub fn from_pfm<R: Read>(mut reader: R) -> Result<Canvas> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let mut tokens = Tokens {
+            data: &data,
+            pos: 0,
+        };
+        let magic = tokens.next().context("missing PFM magic number")?;
+        let channels = match magic {
+            b"PF" => 3,
+            b"Pf" => 1,
+            _ => bail!(
+                "unsupported PFM magic number {:?}",
+                String::from_utf8_lossy(magic)
+            ),
+        };
+
+        let width: usize = tokens.number("PFM width")?;
+        let height: usize = tokens.number("PFM height")?;
+        let scale: f32 = tokens.number("PFM scale")?;
+        ensure!(
+            scale != 0.0 && scale.is_finite(),
+            "invalid PFM scale {scale}"
+        );
+        let little_endian = scale < 0.0;
+
+        // Exactly one whitespace byte separates the header from the samples
+        let start = tokens.pos + 1;
+        let length = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(channels * 4))
+            .context("PFM dimensions are too large")?;
+        let raster = data
+            .get(start..)
+            .and_then(|raster| raster.get(..length))
+            .context("PFM raster data is truncated")?;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in raster.chunks_exact((width * channels * 4).max(1)).rev() {
+            for pixel in row.chunks_exact(channels * 4) {
+                let mut samples = pixel.chunks_exact(4).map(|bytes| {
+                    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                    if little_endian {
+                        f32::from_le_bytes(bytes)
+                    } else {
+                        f32::from_be_bytes(bytes)
+                    }
+                });
+                let red = samples.next().unwrap_or_default();
+                let green = samples.next().unwrap_or(red);
+                let blue = samples.next().unwrap_or(red);
+                pixels.push(color(red, green, blue));
+            }
+        }
+
+        Ok(Canvas {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas;
+
+    #[test]
+    fn pfm_header_and_bottom_up_rows() {
+        let mut c = canvas(2, 2);
+        c.write_pixel(0, 0, color(1.5, -2, 0.25)).unwrap();
+        c.write_pixel(1, 1, color(100, 0, 0)).unwrap();
+        let mut output = vec![];
+        c.write_pfm(&mut output).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert!(output.starts_with(header));
+        assert_eq!(output.len(), header.len() + 2 * 2 * 12);
+
+        let sample = |i: usize| {
+            let offset = header.len() + i * 4;
+            f32::from_le_bytes(output[offset..offset + 4].try_into().unwrap())
+        };
+        // The first row written is the bottom of the image
+        assert_eq!(sample(3), 100.0);
+        assert_eq!(sample(6), 1.5);
+        assert_eq!(sample(7), -2.0);
+        assert_eq!(sample(8), 0.25);
+    }
+
+    #[test]
+    fn pfm_round_trip_is_exact() {
+        let mut c = canvas(3, 2);
+        c.write_pixel(0, 0, color(1.5, 0, 1000)).unwrap();
+        c.write_pixel(2, 1, color(-0.5, 0.1, 1e-6)).unwrap();
+        let mut output = vec![];
+        c.write_pfm(&mut output).unwrap();
+        assert_eq!(Canvas::from_pfm(output.as_slice()).unwrap(), c);
+    }
+
+    #[test]
+    fn reading_big_endian_greyscale_pfm() {
+        let mut data = b"Pf\n2 1\n1.0\n".to_vec();
+        data.extend(0.5f32.to_be_bytes());
+        data.extend(4f32.to_be_bytes());
+        let c = Canvas::from_pfm(data.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(0.5, 0.5, 0.5));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(4, 4, 4));
+    }
+
+    #[test]
+    fn invalid_pfm_is_an_error() {
+        assert!(Canvas::from_pfm(&b"P6\n1 1\n255\n\0\0\0"[..]).is_err());
+        assert!(Canvas::from_pfm(&b"PF\n1 1\n0\n"[..]).is_err());
+        assert!(Canvas::from_pfm(&b"PF\n1 1\n-1.0\n\0\0\0\0"[..]).is_err());
+    }
+}
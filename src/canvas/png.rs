@@ -0,0 +1,574 @@
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result, bail, ensure};
+use bon::bon;
+
+use super::{Canvas, to_byte};
+use crate::{
+    ToneMapping, clamp, color,
+    deflate::{zlib_compress, zlib_decompress},
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Bits per channel in PNG output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngBitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+#[bon]
+impl Canvas {
+    /// Writes the canvas as a PNG image, applying `tone` to each pixel if it
+    /// is given and then clamping each channel to `0.0..=1.0`. If `alpha` is
+    /// given it must hold one coverage value per pixel in row order, and the
+    /// image is written with an alpha channel.
+    ///
+    /// # Errors
+    /// Returns an error if `alpha` has the wrong length or writing to
+    /// `writer` fails.
+    #[builder(finish_fn = write)]
+    pub fn write_png<W: Write>(
+        &self,
+        #[builder(start_fn)] mut writer: W,
+        #[builder(default)] bit_depth: PngBitDepth,
+        alpha: Option<&[f32]>,
+        tone: Option<&ToneMapping>,
+    ) -> Result<()> {
+        if let Some(alpha) = alpha {
+            ensure!(
+                alpha.len() == self.pixels.len(),
+                "alpha has {} values but the canvas has {} pixels",
+                alpha.len(),
+                self.pixels.len()
+            );
+        }
+
+        let width = u32::try_from(self.width).context("canvas is too wide for PNG")?;
+        let height = u32::try_from(self.height).context("canvas is too tall for PNG")?;
+        let channels = if alpha.is_some() { 4 } else { 3 };
+        let (depth, sample_size) = match bit_depth {
+            PngBitDepth::Eight => (8, 1),
+            PngBitDepth::Sixteen => (16, 2),
+        };
+        let bytes_per_pixel = channels * sample_size;
+        let stride = self.width * bytes_per_pixel;
+
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        let mut row = Vec::with_capacity(stride);
+        let mut previous = vec![0; stride];
+        let mut filtered = vec![0; stride];
+        let mut best = vec![0; stride];
+
+        for (y, pixels) in self.pixels.chunks(self.width.max(1)).enumerate() {
+            row.clear();
+            for (x, &pixel) in pixels.iter().enumerate() {
+                let pixel = tone.map_or(pixel, |tone| tone.apply(pixel));
+                let coverage = alpha.map(|alpha| alpha[y * self.width + x]);
+                let samples = [pixel.red(), pixel.green(), pixel.blue()]
+                    .into_iter()
+                    .chain(coverage);
+                for sample in samples {
+                    match bit_depth {
+                        PngBitDepth::Eight => row.push(to_byte(sample)),
+                        PngBitDepth::Sixteen => row.extend(to_u16(sample).to_be_bytes()),
+                    }
+                }
+            }
+
+            let mut best_filter = 0;
+            let mut best_score = u64::MAX;
+            for filter in 0..5 {
+                filter_row(filter, &row, &previous, bytes_per_pixel, &mut filtered);
+                let score = filtered
+                    .iter()
+                    .map(|&b| u64::from(b.cast_signed().unsigned_abs()))
+                    .sum();
+                if score < best_score {
+                    best_score = score;
+                    best_filter = filter;
+                    best.copy_from_slice(&filtered);
+                }
+            }
+
+            raw.push(best_filter);
+            raw.extend_from_slice(&best);
+            previous.copy_from_slice(&row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        header.extend([depth, if channels == 4 { 6 } else { 2 }, 0, 0, 0]);
+
+        writer.write_all(&SIGNATURE)?;
+        write_chunk(&mut writer, b"IHDR", &header)?;
+        write_chunk(&mut writer, b"IDAT", &zlib_compress(&raw))?;
+        write_chunk(&mut writer, b"IEND", &[])?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a non-interlaced PNG image with 8 or 16 bits per channel, in
+    /// greyscale, RGB or palette colour. Any alpha channel is ignored.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails, the data is not a valid PNG image,
+    /// or it uses a feature that is not supported.
+    pub fn from_png<R: Read>(mut reader: R) -> Result<Canvas> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        ensure!(data.starts_with(&SIGNATURE), "missing PNG signature");
+
+        let mut header = None;
+        let mut palette = vec![];
+        let mut compressed = vec![];
+        let mut pos = SIGNATURE.len();
+        loop {
+            let length = data.get(pos..pos + 4).context("PNG chunk is truncated")?;
+            let length = usize::try_from(u32::from_be_bytes(length.try_into()?))?;
+            let chunk = data
+                .get(pos + 4..pos + 8 + length + 4)
+                .context("PNG chunk is truncated")?;
+            let (body, crc) = chunk.split_at(4 + length);
+            ensure!(
+                crc32(body).to_be_bytes() == crc,
+                "PNG chunk checksum mismatch"
+            );
+            pos += 12 + length;
+
+            let (kind, contents) = body.split_at(4);
+            match kind {
+                b"IHDR" => header = Some(Header::parse(contents)?),
+                b"PLTE" => palette = contents.to_vec(),
+                b"IDAT" => compressed.extend_from_slice(contents),
+                b"IEND" => break,
+                _ => ensure!(
+                    kind[0].is_ascii_lowercase(),
+                    "unsupported critical PNG chunk {:?}",
+                    String::from_utf8_lossy(kind)
+                ),
+            }
+        }
+
+        let header = header.context("missing PNG header")?;
+        let raw = zlib_decompress(&compressed)?;
+        let samples = unfilter(&raw, &header)?;
+        let max = if header.bit_depth == 16 {
+            65535.0
+        } else {
+            255.0
+        };
+
+        let sample = |index: usize| -> f32 {
+            let value = if header.bit_depth == 16 {
+                u16::from_be_bytes([samples[index * 2], samples[index * 2 + 1]])
+            } else {
+                u16::from(samples[index])
+            };
+            f32::from(value) / max
+        };
+
+        let channels = header.channels();
+        let pixel_count = header
+            .width
+            .checked_mul(header.height)
+            .context("PNG dimensions are too large")?;
+        let pixels = (0..pixel_count)
+            .map(|i| {
+                let base = i * channels;
+                match header.color_type {
+                    0 | 4 => {
+                        let v = sample(base);
+                        Ok(color(v, v, v))
+                    }
+                    2 | 6 => Ok(color(sample(base), sample(base + 1), sample(base + 2))),
+                    _ => {
+                        let index = usize::from(samples[base]) * 3;
+                        let rgb = palette
+                            .get(index..index + 3)
+                            .context("PNG palette index is out of range")?;
+                        Ok(color(
+                            f32::from(rgb[0]) / 255.0,
+                            f32::from(rgb[1]) / 255.0,
+                            f32::from(rgb[2]) / 255.0,
+                        ))
+                    }
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Canvas {
+            width: header.width,
+            height: header.height,
+            pixels,
+        })
+    }
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(contents: &[u8]) -> Result<Self> {
+        ensure!(contents.len() == 13, "PNG header has the wrong length");
+        let width = usize::try_from(u32::from_be_bytes(contents[0..4].try_into()?))?;
+        let height = usize::try_from(u32::from_be_bytes(contents[4..8].try_into()?))?;
+        let (bit_depth, color_type) = (contents[8], contents[9]);
+
+        match (color_type, bit_depth) {
+            (0 | 2 | 4 | 6, 8 | 16) | (3, 8) => {}
+            _ => bail!("unsupported PNG colour type {color_type} with bit depth {bit_depth}"),
+        }
+        ensure!(contents[12] == 0, "interlaced PNG images are not supported");
+
+        Ok(Header {
+            width,
+            height,
+            bit_depth,
+            color_type,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// Reverses the per-row filters, returning the raw samples of every row.
+fn unfilter(raw: &[u8], header: &Header) -> Result<Vec<u8>> {
+    let bytes_per_pixel = header.channels() * usize::from(header.bit_depth / 8);
+    let too_large = "PNG dimensions are too large";
+    let stride = header
+        .width
+        .checked_mul(bytes_per_pixel)
+        .context(too_large)?;
+    let filtered_size = (stride + 1).checked_mul(header.height).context(too_large)?;
+    ensure!(raw.len() >= filtered_size, "PNG image data is truncated");
+
+    let mut output = vec![0u8; stride * header.height];
+    for y in 0..header.height {
+        let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        let (filter, line) = (line[0], &line[1..]);
+        let (done, rest) = output.split_at_mut(y * stride);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * stride..])
+        };
+        let row = &mut rest[..stride];
+
+        for i in 0..stride {
+            let a = if i >= bytes_per_pixel {
+                row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let b = previous.map_or(0, |p| p[i]);
+            let c = match previous {
+                Some(p) if i >= bytes_per_pixel => p[i - bytes_per_pixel],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => average(a, b),
+                4 => paeth(a, b, c),
+                _ => bail!("invalid PNG filter type {filter}"),
+            };
+            row[i] = line[i].wrapping_add(predictor);
+        }
+    }
+
+    Ok(output)
+}
+
+fn filter_row(filter: u8, row: &[u8], previous: &[u8], bytes_per_pixel: usize, out: &mut [u8]) {
+    for i in 0..row.len() {
+        let a = if i >= bytes_per_pixel {
+            row[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let b = previous[i];
+        let c = if i >= bytes_per_pixel {
+            previous[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predictor = match filter {
+            1 => a,
+            2 => b,
+            3 => average(a, b),
+            4 => paeth(a, b, c),
+            _ => 0,
+        };
+        out[i] = row[i].wrapping_sub(predictor);
+    }
+}
+
+fn average(a: u8, b: u8) -> u8 {
+    u8::try_from((u16::from(a) + u16::from(b)) / 2).unwrap_or(u8::MAX)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let (ia, ib, ic) = (i16::from(a), i16::from(b), i16::from(c));
+    let p = ia + ib - ic;
+    let (pa, pb, pc) = ((p - ia).abs(), (p - ib).abs(), (p - ic).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn to_u16(n: f32) -> u16 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let value = clamp(n * 65535.0, 0.0, 65535.0).round() as u16;
+    value
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], contents: &[u8]) -> Result<()> {
+    let length = u32::try_from(contents.len()).context("PNG chunk is too large")?;
+    let mut body = Vec::with_capacity(contents.len() + 4);
+    body.extend_from_slice(kind);
+    body.extend_from_slice(contents);
+
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(&body)?;
+    writer.write_all(&crc32(&body).to_be_bytes())?;
+    Ok(())
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, &byte| {
+        CRC_TABLE[usize::from(crc.to_le_bytes()[0] ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, canvas, deflate::zlib_compress};
+
+    fn gradient_canvas(width: usize, height: usize) -> Canvas {
+        let mut c = canvas(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                #[allow(clippy::cast_precision_loss)]
+                let pixel = color(
+                    x as f32 / width as f32,
+                    y as f32 / height as f32,
+                    ((x + y) % 7) as f32 / 6.0,
+                );
+                c.write_pixel(x, y, pixel).unwrap();
+            }
+        }
+        c
+    }
+
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = vec![];
+        let mut pos = 8;
+        while pos < png.len() {
+            let length = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8(png[pos + 4..pos + 8].to_vec()).unwrap();
+            chunks.push((kind, png[pos + 8..pos + 8 + length].to_vec()));
+            pos += 12 + length;
+        }
+        chunks
+    }
+
+    fn assert_canvases_match(a: &Canvas, b: &Canvas, epsilon: f32) {
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        for y in 0..a.height {
+            for x in 0..a.width {
+                let (pa, pb) = (a.pixel_at(x, y).unwrap(), b.pixel_at(x, y).unwrap());
+                assert_relative_eq!(pa.red(), pb.red(), epsilon = epsilon);
+                assert_relative_eq!(pa.green(), pb.green(), epsilon = epsilon);
+                assert_relative_eq!(pa.blue(), pb.blue(), epsilon = epsilon);
+            }
+        }
+    }
+
+    #[test]
+    fn crc32_of_known_string() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn png_starts_with_signature_and_header() {
+        let c = canvas(5, 3);
+        let mut png = vec![];
+        c.write_png(&mut png).write().unwrap();
+        assert_eq!(png[..8], SIGNATURE);
+
+        let chunks = chunks(&png);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 5, 0, 0, 0, 3, 8, 2, 0, 0, 0]);
+        assert!(png.ends_with(&[0xae, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn png_pixel_data_is_clamped_and_filtered() {
+        let mut c = canvas(2, 1);
+        c.write_pixel(0, 0, color(1.5, 0.5, -0.5)).unwrap();
+        c.write_pixel(1, 0, color(0, 1, 0)).unwrap();
+        let mut png = vec![];
+        c.write_png(&mut png).write().unwrap();
+
+        let chunks = chunks(&png);
+        let raw = zlib_decompress(&chunks[1].1).unwrap();
+        assert_eq!(raw.len(), 7);
+        let header = Header::parse(&chunks[0].1).unwrap();
+        assert_eq!(unfilter(&raw, &header).unwrap(), [255, 128, 0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn sixteen_bit_png_with_alpha() {
+        let mut c = canvas(1, 1);
+        c.write_pixel(0, 0, color(1, 0.5, 0)).unwrap();
+        let mut png = vec![];
+        c.write_png(&mut png)
+            .bit_depth(PngBitDepth::Sixteen)
+            .alpha(&[0.25])
+            .write()
+            .unwrap();
+
+        let chunks = chunks(&png);
+        assert_eq!(chunks[0].1[8..10], [16, 6]);
+        let header = Header::parse(&chunks[0].1).unwrap();
+        let raw = zlib_decompress(&chunks[1].1).unwrap();
+        assert_eq!(
+            unfilter(&raw, &header).unwrap(),
+            [0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0x40, 0x00]
+        );
+    }
+
+    #[test]
+    fn alpha_must_cover_every_pixel() {
+        let c = canvas(2, 2);
+        let mut png = vec![];
+        assert!(c.write_png(&mut png).alpha(&[1.0; 3]).write().is_err());
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let c = gradient_canvas(37, 23);
+        for (depth, epsilon) in [
+            (PngBitDepth::Eight, 0.5 / 255.0),
+            (PngBitDepth::Sixteen, 0.5 / 65535.0),
+        ] {
+            let mut png = vec![];
+            c.write_png(&mut png).bit_depth(depth).write().unwrap();
+            let read = Canvas::from_png(png.as_slice()).unwrap();
+            assert_canvases_match(&c, &read, epsilon + EPSILON);
+        }
+    }
+
+    #[test]
+    fn png_round_trip_ignores_alpha() {
+        let c = gradient_canvas(4, 4);
+        let alpha = vec![0.5; 16];
+        let mut png = vec![];
+        c.write_png(&mut png).alpha(&alpha).write().unwrap();
+        let read = Canvas::from_png(png.as_slice()).unwrap();
+        assert_canvases_match(&c, &read, 0.5 / 255.0 + EPSILON);
+    }
+
+    #[test]
+    fn reading_greyscale_and_palette_png() {
+        let mut header = vec![0, 0, 0, 2, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        let grey = zlib_compress(&[0, 0, 255]);
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header).unwrap();
+        write_chunk(&mut png, b"IDAT", &grey).unwrap();
+        write_chunk(&mut png, b"IEND", &[]).unwrap();
+        let c = Canvas::from_png(png.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(0, 0, 0));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(1, 1, 1));
+
+        header[9] = 3;
+        let indices = zlib_compress(&[0, 1, 0]);
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header).unwrap();
+        write_chunk(&mut png, b"PLTE", &[255, 0, 0, 0, 0, 255]).unwrap();
+        write_chunk(&mut png, b"IDAT", &indices).unwrap();
+        write_chunk(&mut png, b"IEND", &[]).unwrap();
+        let c = Canvas::from_png(png.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(0, 0, 1));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(1, 0, 0));
+    }
+
+    #[test]
+    fn corrupt_png_is_an_error() {
+        let mut png = vec![];
+        canvas(2, 2).write_png(&mut png).write().unwrap();
+        png[20] ^= 0xff;
+        assert!(Canvas::from_png(png.as_slice()).is_err());
+        assert!(Canvas::from_png(&b"not a png"[..]).is_err());
+    }
+
+    #[test]
+    fn oversized_png_is_an_error() {
+        let header = |width, height| Header {
+            width,
+            height,
+            bit_depth: 16,
+            color_type: 6,
+        };
+        let error = unfilter(&[0; 16], &header(usize::MAX / 4, 1)).unwrap_err();
+        assert_eq!(error.to_string(), "PNG dimensions are too large");
+        let error = unfilter(&[0; 16], &header(1, usize::MAX / 4)).unwrap_err();
+        assert_eq!(error.to_string(), "PNG dimensions are too large");
+        let error = unfilter(&[0; 16], &header(1, 2)).unwrap_err();
+        assert_eq!(error.to_string(), "PNG image data is truncated");
+    }
+
+    #[test]
+    fn paeth_predictor() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(0, 0, 0), 0);
+    }
+}
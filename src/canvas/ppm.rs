@@ -0,0 +1,407 @@
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use bon::bon;
+
+use super::{Canvas, to_byte};
+use crate::{ToneMapping, color};
+
+/// Values per line in plain PPM output, which keeps lines under the
+/// 70 characters that some readers expect.
+const VALUES_PER_LINE: usize = 17;
+
+/// The flavours of PPM that [`Canvas::write_ppm`] can produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PpmFormat {
+    /// ASCII `P3`, readable by anything but large.
+    #[default]
+    Plain,
+    /// Binary `P6` with one byte per channel.
+    Binary,
+}
+
+#[bon]
+impl Canvas {
+    /// Streams the canvas to `writer` as a PPM image, one row at a time. If
+    /// `tone` is given it is applied to each pixel before the channels are
+    /// clamped to `0.0..=1.0`.
+    ///
+    /// # Errors
+    /// Returns an error if writing to `writer` fails.
+    #[builder(finish_fn = write)]
+    pub fn write_ppm<W: Write>(
+        &self,
+        #[builder(start_fn)] mut writer: W,
+        #[builder(default)] format: PpmFormat,
+        tone: Option<&ToneMapping>,
+    ) -> Result<()> {
+        let magic = match format {
+            PpmFormat::Plain => "P3",
+            PpmFormat::Binary => "P6",
+        };
+        write!(writer, "{magic}\n{} {}\n255\n", self.width, self.height)?;
+
+        let mut row = Vec::with_capacity(self.width * 12);
+        for pixels in self.pixels.chunks(self.width.max(1)) {
+            row.clear();
+            let bytes = pixels
+                .iter()
+                .map(|&p| tone.map_or(p, |tone| tone.apply(p)))
+                .flat_map(|p| [p.red(), p.green(), p.blue()])
+                .map(to_byte);
+
+            match format {
+                PpmFormat::Plain => {
+                    for (i, byte) in bytes.enumerate() {
+                        if i > 0 {
+                            let separator = if i % VALUES_PER_LINE == 0 {
+                                b'\n'
+                            } else {
+                                b' '
+                            };
+                            row.push(separator);
+                        }
+                        write!(row, "{byte}")?;
+                    }
+                    row.push(b'\n');
+                }
+                PpmFormat::Binary => row.extend(bytes),
+            }
+
+            writer.write_all(&row)?;
+        }
+
+        if format == PpmFormat::Plain {
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a `P3` or `P6` PPM image. Comments and any amount of whitespace
+    /// are accepted between header values, and samples are scaled by the
+    /// image's maximum value so that it maps to `1.0`.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails or the data is not a valid PPM image.
+    pub fn from_ppm<R: Read>(mut reader: R) -> Result<Canvas> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let mut tokens = Tokens {
+            data: &data,
+            pos: 0,
+        };
+        let magic = tokens.next().context("missing PPM magic number")?;
+        let binary = match magic {
+            b"P3" => false,
+            b"P6" => true,
+            _ => bail!(
+                "unsupported PPM magic number {:?}",
+                String::from_utf8_lossy(magic)
+            ),
+        };
+
+        let width: usize = tokens.number("PPM width")?;
+        let height: usize = tokens.number("PPM height")?;
+        let max_value: usize = tokens.number("PPM maximum value")?;
+        ensure!(
+            (1..=65535).contains(&max_value),
+            "PPM maximum value {max_value} is out of range"
+        );
+
+        #[allow(clippy::cast_precision_loss)]
+        let max = max_value as f32;
+        let samples = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(3))
+            .context("PPM dimensions are too large")?;
+
+        let values = if binary {
+            // Exactly one whitespace character separates the header from the
+            // raster, which may itself start with bytes that look like
+            // whitespace.
+            let start = tokens.pos + 1;
+            let sample_size = if max_value < 256 { 1 } else { 2 };
+            let raster = data
+                .get(start..)
+                .filter(|raster| raster.len() >= samples * sample_size)
+                .context("PPM raster is truncated")?;
+
+            raster
+                .chunks_exact(sample_size)
+                .take(samples)
+                .map(|bytes| match *bytes {
+                    [value] => usize::from(value),
+                    [high, low] => usize::from(u16::from_be_bytes([high, low])),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        } else {
+            (0..samples)
+                .map(|_| tokens.number("PPM sample"))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        ensure!(
+            values.iter().all(|&value| value <= max_value),
+            "PPM sample exceeds maximum value {max_value}"
+        );
+
+        #[allow(clippy::cast_precision_loss)]
+        let pixels = values
+            .chunks_exact(3)
+            .map(|rgb| {
+                color(
+                    rgb[0] as f32 / max,
+                    rgb[1] as f32 / max,
+                    rgb[2] as f32 / max,
+                )
+            })
+            .collect();
+
+        Ok(Canvas {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// Splits Netpbm-style headers and plain raster data into whitespace
+/// separated tokens, skipping `#` comments that run to the end of the line.
+pub(super) struct Tokens<'a> {
+    pub(super) data: &'a [u8],
+    pub(super) pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    pub(super) fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.pos)? {
+                b'#' => {
+                    while self
+                        .data
+                        .get(self.pos)
+                        .is_some_and(|&b| b != b'\n' && b != b'\r')
+                    {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'#')
+        {
+            self.pos += 1;
+        }
+
+        Some(&self.data[start..self.pos])
+    }
+
+    pub(super) fn number<T: FromStr>(&mut self, name: &str) -> Result<T> {
+        let token = self.next().with_context(|| format!("missing {name}"))?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .with_context(|| format!("invalid {name} {:?}", String::from_utf8_lossy(token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, canvas};
+
+    fn sample_canvas() -> Canvas {
+        let mut c = canvas(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                #[allow(clippy::cast_precision_loss)]
+                let pixel = color(x as f32 / 3.0, y as f32 / 2.0, 0.5);
+                c.write_pixel(x, y, pixel).unwrap();
+            }
+        }
+        c
+    }
+
+    fn assert_canvases_match(a: &Canvas, b: &Canvas, epsilon: f32) {
+        assert_eq!(a.width, b.width);
+        assert_eq!(a.height, b.height);
+        for y in 0..a.height {
+            for x in 0..a.width {
+                let (pa, pb) = (a.pixel_at(x, y).unwrap(), b.pixel_at(x, y).unwrap());
+                assert_relative_eq!(pa.red(), pb.red(), epsilon = epsilon);
+                assert_relative_eq!(pa.green(), pb.green(), epsilon = epsilon);
+                assert_relative_eq!(pa.blue(), pb.blue(), epsilon = epsilon);
+            }
+        }
+    }
+
+    #[test]
+    fn plain_output_has_one_line_per_row() {
+        let c = sample_canvas();
+        let mut output = vec![];
+        c.write_ppm(&mut output).write().unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+P3
+4 3
+255
+0 0 128 85 0 128 170 0 128 255 0 128
+0 128 128 85 128 128 170 128 128 255 128 128
+0 255 128 85 255 128 170 255 128 255 255 128
+
+"
+        );
+    }
+
+    #[test]
+    fn binary_output_has_header_and_raw_bytes() {
+        let mut c = canvas(2, 1);
+        c.write_pixel(0, 0, color(1, 0, 0.5)).unwrap();
+        c.write_pixel(1, 0, color(0, 1.5, -1)).unwrap();
+        let mut output = vec![];
+        c.write_ppm(&mut output)
+            .format(PpmFormat::Binary)
+            .write()
+            .unwrap();
+        assert_eq!(output, b"P6\n2 1\n255\n\xff\x00\x80\x00\xff\x00");
+    }
+
+    #[test]
+    fn reading_file_with_wrong_magic_number() {
+        let ppm = "P32\n1 1\n255\n0 0 0\n";
+        assert!(Canvas::from_ppm(ppm.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reading_ppm_returns_canvas_of_right_size() {
+        let ppm = "P3\n10 2\n255\n\
+            0 0 0  0 0 0  0 0 0  0 0 0  0 0 0\n\
+            0 0 0  0 0 0  0 0 0  0 0 0  0 0 0\n\
+            0 0 0  0 0 0  0 0 0  0 0 0  0 0 0\n\
+            0 0 0  0 0 0  0 0 0  0 0 0  0 0 0\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(c.width, 10);
+        assert_eq!(c.height, 2);
+    }
+
+    #[test]
+    fn reading_pixel_data_from_ppm() {
+        let ppm = "P3\n4 3\n255\n\
+            255 127 0  0 127 255  127 255 0  255 255 255\n\
+            0 0 0  255 0 0  0 255 0  0 0 255\n\
+            255 255 0  0 255 255  255 0 255  127 127 127\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+
+        let test_cases = [
+            (0, 0, color(1, 0.49804, 0)),
+            (1, 0, color(0, 0.49804, 1)),
+            (2, 0, color(0.49804, 1, 0)),
+            (3, 0, color(1, 1, 1)),
+            (0, 1, color(0, 0, 0)),
+            (1, 1, color(1, 0, 0)),
+            (2, 1, color(0, 1, 0)),
+            (3, 1, color(0, 0, 1)),
+            (0, 2, color(1, 1, 0)),
+            (1, 2, color(0, 1, 1)),
+            (2, 2, color(1, 0, 1)),
+            (3, 2, color(0.49804, 0.49804, 0.49804)),
+        ];
+        for (x, y, expected) in test_cases {
+            let pixel = c.pixel_at(x, y).unwrap();
+            assert_relative_eq!(pixel.red(), expected.red(), epsilon = EPSILON);
+            assert_relative_eq!(pixel.green(), expected.green(), epsilon = EPSILON);
+            assert_relative_eq!(pixel.blue(), expected.blue(), epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn ppm_parsing_ignores_comment_lines() {
+        let ppm = "P3\n# this is a comment\n2 1\n# this, too\n255\n\
+            # another comment\n255 255 255\n# oh, no, comments in the pixel data!\n255 0 255\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(1, 1, 1));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(1, 0, 1));
+    }
+
+    #[test]
+    fn ppm_parsing_allows_rgb_triple_to_span_lines() {
+        let ppm = "P3\n1 1\n255\n51\n153\n\n204\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), color(0.2, 0.6, 0.8));
+    }
+
+    #[test]
+    fn ppm_parsing_respects_scale_setting() {
+        let ppm = "P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(c.pixel_at(0, 1).unwrap(), color(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn ppm_parsing_accepts_comments_and_tabs_in_header() {
+        let ppm = "P3#magic\n\t2\r\n1 #size\n\t255\n0 0 0 255 255 255";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(c.width, 2);
+        assert_eq!(c.height, 1);
+        assert_eq!(c.pixel_at(1, 0).unwrap(), color(1, 1, 1));
+    }
+
+    #[test]
+    fn reading_binary_ppm() {
+        // The first sample is a newline byte, which must not be mistaken for
+        // header whitespace.
+        let ppm = b"P6 # binary\n2 1\n255\n\x0a\x00\xff\x80\x80\x80";
+        let c = Canvas::from_ppm(&ppm[..]).unwrap();
+        assert_relative_eq!(c.pixel_at(0, 0).unwrap().red(), 10.0 / 255.0);
+        assert_eq!(c.pixel_at(0, 0).unwrap().blue(), 1.0);
+        assert_relative_eq!(c.pixel_at(1, 0).unwrap().green(), 128.0 / 255.0);
+    }
+
+    #[test]
+    fn reading_sixteen_bit_binary_ppm() {
+        let ppm = b"P6\n1 1\n65535\n\xff\xff\x80\x00\x00\x00";
+        let c = Canvas::from_ppm(&ppm[..]).unwrap();
+        let pixel = c.pixel_at(0, 0).unwrap();
+        assert_relative_eq!(pixel.red(), 1.0);
+        assert_relative_eq!(pixel.green(), 32768.0 / 65535.0);
+        assert_relative_eq!(pixel.blue(), 0.0);
+    }
+
+    #[test]
+    fn truncated_ppm_is_an_error() {
+        assert!(Canvas::from_ppm(&b"P6\n2 2\n255\n\x00\x00\x00"[..]).is_err());
+        assert!(Canvas::from_ppm("P3\n2 1\n255\n0 0 0 0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn sample_above_maximum_value_is_an_error() {
+        assert!(Canvas::from_ppm("P3\n1 1\n100\n101 0 0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn plain_and_binary_round_trip() {
+        let c = sample_canvas();
+        for format in [PpmFormat::Plain, PpmFormat::Binary] {
+            let mut output = vec![];
+            c.write_ppm(&mut output).format(format).write().unwrap();
+            let read = Canvas::from_ppm(output.as_slice()).unwrap();
+            assert_canvases_match(&c, &read, 0.5 / 255.0 + EPSILON);
+        }
+    }
+}
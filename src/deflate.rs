@@ -0,0 +1,580 @@
+use anyhow::{Context, Result, bail, ensure};
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 128;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Wraps [`deflate`] output in a zlib stream (RFC 1950).
+#[must_use]
+pub(crate) fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    output.extend(deflate(data));
+    output.extend(adler32(data).to_be_bytes());
+    output
+}
+
+/// Decompresses a zlib stream (RFC 1950) and checks its checksum.
+///
+/// # Errors
+/// Returns an error if the stream is malformed, uses a preset dictionary,
+/// or fails its checksum.
+pub(crate) fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    ensure!(data.len() >= 6, "zlib stream is truncated");
+    let (cmf, flg) = (data[0], data[1]);
+    ensure!(cmf & 0x0f == 8, "zlib stream does not use deflate");
+    ensure!(
+        (u16::from(cmf) * 256 + u16::from(flg)) % 31 == 0,
+        "zlib header is corrupt"
+    );
+    ensure!(
+        flg & 0x20 == 0,
+        "zlib preset dictionaries are not supported"
+    );
+
+    let (output, consumed) = inflate(&data[2..])?;
+    let checksum = data
+        .get(2 + consumed..2 + consumed + 4)
+        .context("zlib checksum is missing")?;
+    ensure!(
+        checksum == adler32(&output).to_be_bytes(),
+        "zlib checksum mismatch"
+    );
+
+    Ok(output)
+}
+
+/// Compresses `data` into a raw DEFLATE stream (RFC 1951) made of a single
+/// block with the fixed Huffman codes, using greedy LZ77 matching over hash
+/// chains.
+#[must_use]
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut matcher = Matcher::new(data);
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = matcher.longest_match(pos);
+        if length >= MIN_MATCH {
+            writer.write_length(length);
+            writer.write_distance(distance);
+            for p in pos..pos + length {
+                matcher.insert(p);
+            }
+            pos += length;
+        } else {
+            writer.write_literal(u16::from(data[pos]));
+            matcher.insert(pos);
+            pos += 1;
+        }
+    }
+
+    writer.write_literal(256);
+    writer.finish()
+}
+
+/// Decompresses a raw DEFLATE stream (RFC 1951), returning the output and
+/// the number of input bytes consumed.
+///
+/// # Errors
+/// Returns an error if the stream is truncated or malformed.
+pub(crate) fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit_buffer: 0,
+        bit_count: 0,
+    };
+    let mut output = vec![];
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, &mut output)?,
+            1 => {
+                let (lengths, distances) = fixed_tables()?;
+                inflate_block(&mut reader, &mut output, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &lengths, &distances)?;
+            }
+            _ => bail!("invalid deflate block type"),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    Ok((output, reader.pos))
+}
+
+#[must_use]
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    (b << 16) | a
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= u64::from(value) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer.to_le_bytes()[0]);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn write_literal(&mut self, value: u16) {
+        let value = u32::from(value);
+        match value {
+            0..=143 => self.write_code(0x30 + value, 8),
+            144..=255 => self.write_code(0x190 + value - 144, 9),
+            256..=279 => self.write_code(value - 256, 7),
+            _ => self.write_code(0xc0 + value - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, length: usize) {
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&base| usize::from(base) <= length)
+            .unwrap_or(0);
+        let symbol = u16::try_from(257 + index).unwrap_or(256);
+        self.write_literal(symbol);
+        let extra = length - usize::from(LENGTH_BASE[index]);
+        self.write_bits(
+            u32::try_from(extra).unwrap_or(0),
+            u32::from(LENGTH_EXTRA[index]),
+        );
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| usize::from(base) <= distance)
+            .unwrap_or(0);
+        self.write_code(u32::try_from(index).unwrap_or(0), 5);
+        let extra = distance - usize::from(DISTANCE_BASE[index]);
+        self.write_bits(
+            u32::try_from(extra).unwrap_or(0),
+            u32::from(DISTANCE_EXTRA[index]),
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bit_buffer.to_le_bytes()[0]);
+        }
+        self.bytes
+    }
+}
+
+/// Finds earlier occurrences of the bytes at a position through chains of
+/// positions that share the same three-byte hash.
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Matcher {
+            data,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            prev: vec![usize::MAX; WINDOW_SIZE],
+        }
+    }
+
+    fn hash(&self, pos: usize) -> Option<usize> {
+        let bytes = self.data.get(pos..pos + MIN_MATCH)?;
+        let hash =
+            (usize::from(bytes[0]) << 10) ^ (usize::from(bytes[1]) << 5) ^ usize::from(bytes[2]);
+        Some(hash & ((1 << HASH_BITS) - 1))
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if let Some(hash) = self.hash(pos) {
+            self.prev[pos % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    fn longest_match(&self, pos: usize) -> (usize, usize) {
+        let Some(hash) = self.hash(pos) else {
+            return (0, 0);
+        };
+
+        let max_length = MAX_MATCH.min(self.data.len() - pos);
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+                break;
+            }
+
+            let length = self.data[candidate..]
+                .iter()
+                .zip(&self.data[pos..pos + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, pos - candidate);
+                if length == max_length {
+                    break;
+                }
+            }
+
+            let next = self.prev[candidate % WINDOW_SIZE];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+
+        best
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .context("deflate stream is truncated")?;
+            self.pos += 1;
+            self.bit_buffer |= u32::from(byte) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bit_buffer & ((1 << count) - 1);
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Discards the bits left in the current byte. Bytes are only loaded as
+    /// they are needed, so no more than seven bits are ever left over.
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + count)
+            .context("deflate stream is truncated")?;
+        self.pos += count;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            ensure!(left >= 0, "deflate Huffman code is over-subscribed");
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = u16::try_from(symbol)?;
+                *offset += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= i32::try_from(reader.bits(1)?)?;
+            let count = i32::from(count);
+            if code - first < count {
+                let symbol = usize::try_from(index + code - first)?;
+                return Ok(self.symbols[symbol]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        bail!("invalid deflate Huffman code")
+    }
+}
+
+fn fixed_tables() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = usize::try_from(reader.bits(5)?)? + 257;
+    let distance_count = usize::try_from(reader.bits(5)?)? + 1;
+    let code_length_count = usize::try_from(reader.bits(4)?)? + 4;
+    ensure!(
+        literal_count <= 286 && distance_count <= 30,
+        "deflate block has too many codes"
+    );
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = u8::try_from(reader.bits(3)?)?;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (u8::try_from(symbol)?, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .context("deflate code lengths repeat with no previous length")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, usize::try_from(repeat)?));
+    }
+    ensure!(
+        lengths.len() == literal_count + distance_count,
+        "deflate code lengths overrun"
+    );
+    ensure!(lengths[256] != 0, "deflate block has no end-of-block code");
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((
+        Huffman::new(literal_lengths)?,
+        Huffman::new(distance_lengths)?,
+    ))
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<()> {
+    reader.align_to_byte();
+    let header = reader.bytes(4)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    ensure!(
+        length == !complement,
+        "deflate stored block length is corrupt"
+    );
+    output.extend_from_slice(reader.bytes(usize::from(length))?);
+    Ok(())
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = usize::from(lengths.decode(reader)?);
+        match symbol {
+            0..=255 => output.push(u8::try_from(symbol)?),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length = usize::from(LENGTH_BASE[index])
+                    + usize::try_from(reader.bits(u32::from(LENGTH_EXTRA[index]))?)?;
+
+                let index = usize::from(distances.decode(reader)?);
+                ensure!(index < 30, "invalid deflate distance code");
+                let distance = usize::from(DISTANCE_BASE[index])
+                    + usize::try_from(reader.bits(u32::from(DISTANCE_EXTRA[index]))?)?;
+                ensure!(
+                    distance <= output.len(),
+                    "deflate distance reaches before start of output"
+                );
+
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => bail!("invalid deflate length code"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::random_unit;
+
+    const PANGRAMS: &[u8] = b"The quick brown fox jumps over the lazy dog. \
+        The quick brown fox jumps over the lazy dog. \
+        The quick brown fox jumps over the lazy dog. \
+        The quick brown fox jumps over the lazy dog. \
+        Pack my box with five dozen liquor jugs.";
+
+    fn round_trip(data: &[u8]) {
+        let compressed = zlib_compress(data);
+        assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn adler32_of_known_strings() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn round_trip_empty_input() {
+        round_trip(b"");
+    }
+
+    #[test]
+    fn round_trip_short_text() {
+        round_trip(b"a");
+        round_trip(b"hello, world");
+        round_trip(PANGRAMS);
+    }
+
+    #[test]
+    fn repetitive_data_compresses() {
+        let data = vec![7u8; 100_000];
+        let compressed = zlib_compress(&data);
+        assert!(compressed.len() < 1000);
+        assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn round_trip_random_data() {
+        let data: Vec<u8> = (0..50_000u64)
+            .map(|i| {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let byte = (random_unit(i) * 256.0) as u8;
+                byte
+            })
+            .collect();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn round_trip_matches_across_whole_window() {
+        let block: Vec<u8> = (0..=255).collect();
+        let mut data = block.repeat(2);
+        data.extend(vec![0u8; WINDOW_SIZE - data.len()]);
+        data.extend(&block);
+        data.extend(&block);
+        round_trip(&data);
+    }
+
+    #[test]
+    fn inflate_stored_block() {
+        let stream = b"\x78\x01\x01\x0c\x00\xf3\xff\x68\x65\x6c\x6c\x6f\x2c\x20\x77\x6f\x72\x6c\x64\x1d\x54\x04\x89";
+        assert_eq!(zlib_decompress(stream).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn inflate_fixed_huffman_block() {
+        let stream =
+            b"\x78\x01\xcb\x48\xcd\xc9\xc9\xd7\x51\x28\xcf\x2f\xca\x49\x01\x00\x1d\x54\x04\x89";
+        assert_eq!(zlib_decompress(stream).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn inflate_dynamic_huffman_block() {
+        let stream = b"\x78\xda\xcd\xcb\xc7\x01\x80\x20\x10\x05\xd1\x56\x7e\x05\xd4\xe2\xc1\x06\x40\x49\
+            \x06\x56\xb2\x50\xbd\x5b\x86\xe7\x79\xb3\x3a\x8d\x58\xfd\x76\x42\x25\xea\x01\x86\x5e\x1c\
+            \xf5\x7e\x32\xa8\xe9\x84\xc2\xf9\x92\x73\x60\x27\x2b\xb0\xfe\x03\x2f\x92\xdd\x3d\xa0\x18\
+            \x75\x5f\x1c\x8c\x6f\x9a\xd3\xd4\x01\x97\x8f\x95\x12\xbf\x36\x8b\x0f\x3a\x64\x4f\x34";
+        assert_eq!(zlib_decompress(stream).unwrap(), PANGRAMS);
+    }
+
+    #[test]
+    fn corrupt_streams_are_errors() {
+        let mut stream = zlib_compress(PANGRAMS);
+        let last = stream.len() - 1;
+        stream[last] ^= 0xff;
+        assert!(zlib_decompress(&stream).is_err());
+
+        let stream = zlib_compress(PANGRAMS);
+        assert!(zlib_decompress(&stream[..stream.len() / 2]).is_err());
+        assert!(zlib_decompress(b"\x78\x02\x00\x00\x00\x00").is_err());
+    }
+}
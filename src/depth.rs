@@ -0,0 +1,139 @@
+use bon::builder;
+
+use crate::REFLECTION_DEPTH;
+
+/// Limits on how far `World::color_at` follows a ray through reflections and
+/// refractions.
+///
+/// Reflections and refractions share one budget of `bounces`, and each kind
+/// can be capped further on its own. Tracing also stops once a secondary
+/// ray's `weight`, the product of the reflective and transparency factors
+/// along its path, falls below `cutoff`. A plain `usize` converts into a
+/// depth with that shared budget, no extra caps and no cutoff.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceDepth {
+    /// Bounces of either kind remaining.
+    pub bounces: usize,
+    /// Reflection bounces remaining.
+    pub reflection: usize,
+    /// Refraction bounces remaining.
+    pub refraction: usize,
+    /// Smallest weight worth tracing a secondary ray for.
+    pub cutoff: f32,
+    /// Share of the final color carried by the current ray.
+    pub weight: f32,
+}
+
+#[must_use]
+#[builder(finish_fn = build)]
+pub fn trace_depth(
+    #[builder(default = REFLECTION_DEPTH)] bounces: usize,
+    #[builder(default = bounces)] reflection: usize,
+    #[builder(default = bounces)] refraction: usize,
+    #[builder(default)] cutoff: f32,
+) -> TraceDepth {
+    TraceDepth {
+        bounces,
+        reflection,
+        refraction,
+        cutoff,
+        weight: 1.0,
+    }
+}
+
+impl Default for TraceDepth {
+    fn default() -> Self {
+        trace_depth().build()
+    }
+}
+
+impl From<usize> for TraceDepth {
+    fn from(depth: usize) -> Self {
+        trace_depth().bounces(depth).build()
+    }
+}
+
+impl TraceDepth {
+    /// Returns the depth for a ray reflected off a surface with the given
+    /// reflectivity, or `None` if it should not be traced.
+    #[must_use]
+    pub fn reflected(self, reflective: f32) -> Option<TraceDepth> {
+        let bounces = self.bounces.checked_sub(1)?;
+        let reflection = self.reflection.checked_sub(1)?;
+        let weight = self.secondary_weight(reflective)?;
+        Some(TraceDepth {
+            bounces,
+            reflection,
+            weight,
+            ..self
+        })
+    }
+
+    /// Returns the depth for a ray refracted through a surface with the
+    /// given transparency, or `None` if it should not be traced.
+    #[must_use]
+    pub fn refracted(self, transparency: f32) -> Option<TraceDepth> {
+        let bounces = self.bounces.checked_sub(1)?;
+        let refraction = self.refraction.checked_sub(1)?;
+        let weight = self.secondary_weight(transparency)?;
+        Some(TraceDepth {
+            bounces,
+            refraction,
+            weight,
+            ..self
+        })
+    }
+
+    fn secondary_weight(self, factor: f32) -> Option<f32> {
+        let weight = self.weight * factor.abs();
+        (weight >= self.cutoff).then_some(weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn default_depth_matches_reflection_depth() {
+        let d = TraceDepth::default();
+        assert_eq!(d, TraceDepth::from(REFLECTION_DEPTH));
+        assert_relative_eq!(d.cutoff, 0.0);
+        assert_relative_eq!(d.weight, 1.0);
+    }
+
+    #[test]
+    fn reflection_and_refraction_share_one_budget() {
+        let d = TraceDepth::from(3);
+        let d = d.reflected(0.5).unwrap().refracted(0.5).unwrap();
+        let d = d.reflected(0.5).unwrap();
+        assert_eq!(d.bounces, 0);
+        assert_eq!(d.reflected(0.5), None);
+        assert_eq!(d.refracted(0.5), None);
+    }
+
+    #[test]
+    fn reflection_and_refraction_can_be_capped_separately() {
+        let d = trace_depth().reflection(1).refraction(3).build();
+        let reflected = d.reflected(0.5).unwrap();
+        assert_eq!((reflected.reflection, reflected.refraction), (0, 3));
+        assert_eq!(reflected.reflected(0.5), None);
+
+        let refracted = reflected.refracted(0.5).unwrap();
+        assert_eq!((refracted.reflection, refracted.refraction), (0, 2));
+        assert_eq!(refracted.bounces, REFLECTION_DEPTH - 2);
+        assert_relative_eq!(refracted.weight, 0.25);
+    }
+
+    #[test]
+    fn rays_below_the_cutoff_are_not_traced() {
+        let d = trace_depth().bounces(20).cutoff(0.1).build();
+        let d = d.reflected(0.5).unwrap().reflected(0.5).unwrap();
+        assert_relative_eq!(d.weight, 0.25);
+        assert!(d.reflected(0.5).is_some());
+        assert_eq!(d.reflected(0.3), None);
+        assert_eq!(d.refracted(0.2), None);
+    }
+}
@@ -55,11 +55,26 @@ impl Intersection {
     pub fn prepare_computations(&self, ray: Ray, xs: &[Intersection]) -> Computations {
         let point = ray.position(self.time);
         let eyev = -ray.direction;
-        let normalv = self.object.normal_at_with_hit(point, Some(self));
-        let inside = normalv.dot(&eyev) < 0.0;
-        let normalv = if inside { -normalv } else { normalv };
-        let over_point = point + normalv * EPSILON;
-        let under_point = point - normalv * EPSILON;
+        let geometric = self.object.normal_at_with_hit(point, Some(self));
+        let inside = geometric.dot(&eyev) < 0.0;
+        let (normal_map, bump) = {
+            let inner = self.object.inner();
+            (
+                inner.material.normal_map.clone(),
+                inner.material.bump.clone(),
+            )
+        };
+        let normalv = normal_map.map_or(geometric, |normal_map| {
+            normal_map.perturb(&self.object, point, self, geometric)
+        });
+        let normalv = bump.map_or(normalv, |bump| bump.perturb(&self.object, point, normalv));
+        let (geometric, normalv) = if inside {
+            (-geometric, -normalv)
+        } else {
+            (geometric, normalv)
+        };
+        let over_point = point + geometric * EPSILON;
+        let under_point = point - geometric * EPSILON;
         let reflectv = ray.direction.reflect(&normalv);
 
         let (_, n1, n2) = xs.iter().fold(
@@ -147,7 +162,9 @@ mod tests {
 
     use super::*;
     use crate::{
-        EPSILON, Material, point, ray,
+        BumpMap, EPSILON, Material, NormalMap, color,
+        pattern::UvPattern,
+        point, ray,
         shape::{glass_sphere, plane, sphere, triangle},
         transform, vector,
     };
@@ -269,6 +286,47 @@ mod tests {
         assert_relative_eq!(comps.reflectv.z(), sqrt2_over_2, epsilon = EPSILON);
     }
 
+    #[test]
+    fn bump_map_perturbs_shading_normal_but_not_offset_points() {
+        let shape = plane()
+            .material(Material::builder().bump(BumpMap::new(|p| p.x())))
+            .build();
+        let r = ray(point(0, 1, 0), vector(0, -1, 0));
+        let i = intersection(1.0, shape);
+        let comps = i.prepare_computations(r, slice::from_ref(&i));
+        let expected = vector(-1, 1, 0).normalize();
+        assert_relative_eq!(comps.normalv.x(), expected.x(), epsilon = 0.001);
+        assert_relative_eq!(comps.normalv.y(), expected.y(), epsilon = 0.001);
+        assert_relative_eq!(comps.reflectv.x(), -1.0, epsilon = 0.001);
+        assert_relative_eq!(comps.reflectv.y(), 0.0, epsilon = 0.001);
+        assert_relative_eq!(comps.over_point.x(), 0.0, epsilon = EPSILON);
+        assert_relative_eq!(comps.over_point.y(), EPSILON, epsilon = EPSILON / 10.0);
+
+        let r = ray(point(0, -1, 0), vector(0, 1, 0));
+        let comps = i.prepare_computations(r, slice::from_ref(&i));
+        assert!(comps.inside);
+        assert_relative_eq!(comps.normalv.x(), -expected.x(), epsilon = 0.001);
+        assert_relative_eq!(comps.normalv.y(), -expected.y(), epsilon = 0.001);
+        assert_relative_eq!(comps.under_point.y(), EPSILON, epsilon = EPSILON / 10.0);
+    }
+
+    #[test]
+    fn normal_map_sets_shading_normal_from_texture_coordinates() {
+        let map = NormalMap::new(UvPattern::new(|_, _| color(1, 0.5, 1)));
+        let shape = triangle(point(0, 1, 0), point(-1, 0, 0), point(1, 0, 0))
+            .uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .material(Material::builder().normal_map(map))
+            .build();
+        let r = ray(point(0.0, 0.5, -2.0), vector(0, 0, 1));
+        let xs = shape.intersect(r);
+        let comps = xs[0].prepare_computations(r, &xs);
+        let expected = vector(1, 0, -1).normalize();
+        assert_relative_eq!(comps.normalv.x(), expected.x(), epsilon = EPSILON);
+        assert_relative_eq!(comps.normalv.y(), expected.y(), epsilon = EPSILON);
+        assert_relative_eq!(comps.normalv.z(), expected.z(), epsilon = EPSILON);
+        assert_relative_eq!(comps.over_point.z(), -EPSILON, epsilon = EPSILON / 10.0);
+    }
+
     #[test]
     fn finding_n1_and_n2_at_various_intersections() {
         let a = sphere()
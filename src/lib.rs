@@ -1,30 +1,52 @@
+mod aov;
+mod bounds;
+mod bump;
 mod camera;
 mod canvas;
 pub mod color;
+mod deflate;
+mod depth;
 mod intersection;
 mod light;
 mod material;
 mod matrix;
+mod noise;
 mod obj_parser;
 pub mod pattern;
 mod point;
 mod ray;
+mod sampling;
+mod scene;
 pub mod shape;
+mod tone;
 pub mod transform;
 mod vector;
 mod world;
 
-pub use camera::{Camera, camera};
-pub use canvas::{Canvas, canvas, canvas_with_pixels};
+pub use aov::{AovBuffers, Aovs};
+pub use bounds::{BoundingBox, bounding_box};
+pub use bump::{BumpMap, NormalMap};
+pub use camera::{Camera, PixelFilter, RenderStats, camera};
+pub use canvas::{
+    Canvas, ExrCompression, ExrImage, ExrLayer, ExrPixelType, PngBitDepth, PpmFormat, canvas,
+    canvas_with_pixels,
+};
 pub use color::{Color, color};
+pub use depth::{TraceDepth, trace_depth};
 pub use intersection::{Intersection, hit, intersection, intersection_with_uv};
-pub use light::{PointLight, point_light};
+pub use light::{
+    AreaLight, Attenuation, DirectionalLight, Light, PointLight, SpotLight, area_light,
+    directional_light, point_light, spot_light,
+};
 pub use material::{Material, material};
 pub use matrix::{Matrix, Matrix2, Matrix3, Matrix4, identity_matrix, matrix};
+pub use noise::Noise;
 pub use obj_parser::ObjParser;
 pub use point::{ORIGIN, Point, point};
 pub use ray::{Ray, ray};
+pub use scene::{Scene, SceneError};
 pub use shape::*;
+pub use tone::{ToneMapping, ToneOperator, linear_to_srgb, srgb_to_linear, tone_mapping};
 pub use vector::{Vector, vector};
 pub use world::{World, default_world};
 
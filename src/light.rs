@@ -1,10 +1,100 @@
-use crate::{Color, Point};
+use bon::builder;
+
+use crate::{
+    Color, EPSILON, Point, Vector,
+    sampling::{random_unit, seed},
+};
+
+/// A source of light in a `World`.
+///
+/// Shading asks the light for the points on it to sample, tests each of them
+/// for shadows, and averages the diffuse and specular terms over them.
+pub trait Light: Send + Sync {
+    /// Calls `f` with each position on the light that is sampled when
+    /// shading `point`. Implementations that jitter their samples should
+    /// derive the jitter from `point`, so every visit for the same point sees
+    /// the same samples. A light that visits no samples contributes nothing.
+    fn for_each_sample(&self, point: Point, f: &mut dyn FnMut(Point));
+
+    /// Returns the positions visited by [`Light::for_each_sample`] as a list.
+    /// Shading visits the samples directly instead, to avoid allocating.
+    fn sample_points(&self, point: Point) -> Vec<Point> {
+        let mut samples = vec![];
+        self.for_each_sample(point, &mut |sample| samples.push(sample));
+        samples
+    }
+
+    /// Returns the light's intensity as seen from `point`.
+    fn intensity_at(&self, point: Point) -> Color;
+
+    /// Returns how far a shadow ray from `point` towards `sample` travels
+    /// before it reaches the light. Objects beyond this distance cannot cast
+    /// a shadow on `point`.
+    fn shadow_distance(&self, point: Point, sample: Point) -> f32 {
+        (sample - point).magnitude()
+    }
+
+    /// Returns the factor by which light from `sample` is dimmed by the time
+    /// it reaches `point`. Lights that do not override this never fade.
+    fn attenuation(&self, _point: Point, _sample: Point) -> f32 {
+        1.0
+    }
+}
+
+/// How a light's intensity falls off with distance.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Attenuation {
+    /// The light is equally bright at any distance.
+    #[default]
+    None,
+    /// The intensity is divided by `constant + linear * d + quadratic * d²`.
+    Polynomial {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+    /// The physically based `1 / d²` falloff.
+    InverseSquare,
+}
+
+impl Attenuation {
+    /// Returns the fraction of a light's intensity that remains at `distance`.
+    /// Distances are clamped to at least `EPSILON`, so the falloff stays
+    /// finite and continuous as a point approaches the light. Polynomials
+    /// whose value is not positive leave the intensity unchanged.
+    #[must_use]
+    pub fn factor(&self, distance: f32) -> f32 {
+        let distance = distance.max(EPSILON);
+        let denominator = match *self {
+            Attenuation::None => return 1.0,
+            Attenuation::Polynomial {
+                constant,
+                linear,
+                quadratic,
+            } => constant + linear * distance + quadratic * distance * distance,
+            Attenuation::InverseSquare => distance * distance,
+        };
+
+        if denominator > 0.0 {
+            1.0 / denominator
+        } else {
+            1.0
+        }
+    }
+}
+
+impl<L: Light + 'static> From<L> for Box<dyn Light> {
+    fn from(light: L) -> Self {
+        Box::new(light)
+    }
+}
 
 #[must_use]
 pub fn point_light(position: Point, intensity: Color) -> PointLight {
     PointLight {
         position,
         intensity,
+        attenuation: Attenuation::None,
     }
 }
 
@@ -12,12 +102,235 @@ pub fn point_light(position: Point, intensity: Color) -> PointLight {
 pub struct PointLight {
     pub position: Point,
     pub intensity: Color,
+    pub attenuation: Attenuation,
+}
+
+impl PointLight {
+    #[must_use]
+    pub fn with_attenuation(self, attenuation: Attenuation) -> Self {
+        PointLight {
+            attenuation,
+            ..self
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn for_each_sample(&self, _point: Point, f: &mut dyn FnMut(Point)) {
+        f(self.position);
+    }
+
+    fn intensity_at(&self, _point: Point) -> Color {
+        self.intensity
+    }
+
+    fn attenuation(&self, point: Point, sample: Point) -> f32 {
+        self.attenuation.factor((sample - point).magnitude())
+    }
+}
+
+/// Creates a light that shines along `direction` from infinitely far away,
+/// like sunlight. Every point sees it from the same direction.
+#[must_use]
+pub fn directional_light(direction: Vector, intensity: Color) -> DirectionalLight {
+    DirectionalLight {
+        direction: direction.normalize(),
+        intensity,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Vector,
+    pub intensity: Color,
+}
+
+impl Light for DirectionalLight {
+    /// A directional light has no position, so its single sample is one unit
+    /// from `point` towards the light; only the direction to it is meaningful.
+    fn for_each_sample(&self, point: Point, f: &mut dyn FnMut(Point)) {
+        f(point - self.direction);
+    }
+
+    fn intensity_at(&self, _point: Point) -> Color {
+        self.intensity
+    }
+
+    fn shadow_distance(&self, _point: Point, _sample: Point) -> f32 {
+        f32::INFINITY
+    }
+}
+
+/// Creates a light at `position` shining along `direction`. Points within
+/// `inner_angle` of the axis receive the full intensity, points beyond
+/// `outer_angle` receive none, and the intensity falls off smoothly between
+/// the two. Both angles are measured from the axis in radians.
+#[must_use]
+#[builder(finish_fn = build)]
+pub fn spot_light(
+    #[builder(start_fn)] position: Point,
+    #[builder(start_fn)] direction: Vector,
+    #[builder(start_fn)] intensity: Color,
+    inner_angle: f32,
+    outer_angle: f32,
+    #[builder(default)] attenuation: Attenuation,
+) -> SpotLight {
+    let outer_angle = outer_angle.max(inner_angle);
+
+    SpotLight {
+        position,
+        direction: direction.normalize(),
+        intensity,
+        inner_angle,
+        outer_angle,
+        attenuation,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point,
+    pub direction: Vector,
+    pub intensity: Color,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    /// Returns how much of the light reaches `point`, from `0.0` outside the
+    /// outer cone to `1.0` inside the inner cone.
+    #[must_use]
+    pub fn falloff(&self, point: Point) -> f32 {
+        let cos_angle = (point - self.position).normalize().dot(&self.direction);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn for_each_sample(&self, _point: Point, f: &mut dyn FnMut(Point)) {
+        f(self.position);
+    }
+
+    fn intensity_at(&self, point: Point) -> Color {
+        self.intensity * self.falloff(point)
+    }
+
+    fn attenuation(&self, point: Point, sample: Point) -> f32 {
+        self.attenuation.factor((sample - point).magnitude())
+    }
+}
+
+/// Creates a rectangular light spanning `full_uvec` and `full_vvec` from
+/// `corner`, divided into `usteps` by `vsteps` cells that are each sampled
+/// once when shading.
+#[must_use]
+#[builder(finish_fn = build)]
+pub fn area_light(
+    #[builder(start_fn)] corner: Point,
+    #[builder(start_fn)] full_uvec: Vector,
+    #[builder(start_fn)] usteps: u16,
+    #[builder(start_fn)] full_vvec: Vector,
+    #[builder(start_fn)] vsteps: u16,
+    #[builder(start_fn)] intensity: Color,
+    #[builder(default = true)] jitter: bool,
+    #[builder(default)] attenuation: Attenuation,
+) -> AreaLight {
+    let usteps = usteps.max(1);
+    let vsteps = vsteps.max(1);
+    let position = corner + full_uvec * 0.5 + full_vvec * 0.5;
+
+    AreaLight {
+        corner,
+        uvec: full_uvec / f32::from(usteps),
+        usteps,
+        vvec: full_vvec / f32::from(vsteps),
+        vsteps,
+        intensity,
+        position,
+        jitter,
+        attenuation,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AreaLight {
+    pub corner: Point,
+    pub uvec: Vector,
+    pub usteps: u16,
+    pub vvec: Vector,
+    pub vsteps: u16,
+    pub intensity: Color,
+    pub position: Point,
+    pub jitter: bool,
+    pub attenuation: Attenuation,
+}
+
+impl AreaLight {
+    #[must_use]
+    pub fn samples(&self) -> usize {
+        usize::from(self.usteps) * usize::from(self.vsteps)
+    }
+
+    /// Returns a point inside cell `(u, v)`. Without jitter this is the centre
+    /// of the cell; with jitter it is a random point within the cell, seeded
+    /// from `seed_value` so that repeated calls agree.
+    #[must_use]
+    pub fn point_on_light(&self, u: u16, v: u16, seed_value: u64) -> Point {
+        let (ju, jv) = if self.jitter {
+            (
+                random_unit(seed(&[seed_value, u.into(), v.into(), 0])),
+                random_unit(seed(&[seed_value, u.into(), v.into(), 1])),
+            )
+        } else {
+            (0.5, 0.5)
+        };
+
+        self.corner + self.uvec * (f32::from(u) + ju) + self.vvec * (f32::from(v) + jv)
+    }
+}
+
+impl Light for AreaLight {
+    fn for_each_sample(&self, point: Point, f: &mut dyn FnMut(Point)) {
+        let seed_value = seed(&[
+            point.x().to_bits().into(),
+            point.y().to_bits().into(),
+            point.z().to_bits().into(),
+        ]);
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                f(self.point_on_light(u, v, seed_value));
+            }
+        }
+    }
+
+    fn intensity_at(&self, _point: Point) -> Color {
+        self.intensity
+    }
+
+    fn attenuation(&self, point: Point, sample: Point) -> f32 {
+        self.attenuation.factor((sample - point).magnitude())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::{FRAC_PI_3, FRAC_PI_4, FRAC_PI_6};
+
+    use approx::assert_relative_eq;
+
     use super::*;
-    use crate::{color, point};
+    use crate::{color, point, vector};
 
     #[test]
     fn point_light_has_position_and_intensity() {
@@ -27,4 +340,283 @@ mod tests {
         assert_eq!(light.position, position);
         assert_eq!(light.intensity, intensity);
     }
+
+    #[test]
+    fn creating_area_light() {
+        let corner = point(0, 0, 0);
+        let v1 = vector(2, 0, 0);
+        let v2 = vector(0, 0, 1);
+        let light = area_light(corner, v1, 4, v2, 2, color(1, 1, 1)).build();
+        assert_eq!(light.corner, corner);
+        assert_eq!(light.uvec, vector(0.5, 0, 0));
+        assert_eq!(light.usteps, 4);
+        assert_eq!(light.vvec, vector(0, 0, 0.5));
+        assert_eq!(light.vsteps, 2);
+        assert_eq!(light.samples(), 8);
+        assert_eq!(light.position, point(1, 0, 0.5));
+    }
+
+    #[test]
+    fn finding_single_point_on_area_light() {
+        let light = area_light(
+            point(0, 0, 0),
+            vector(2, 0, 0),
+            4,
+            vector(0, 0, 1),
+            2,
+            color(1, 1, 1),
+        )
+        .jitter(false)
+        .build();
+
+        let test_cases = [
+            (0, 0, point(0.25, 0, 0.25)),
+            (1, 0, point(0.75, 0, 0.25)),
+            (0, 1, point(0.25, 0, 0.75)),
+            (2, 0, point(1.25, 0, 0.25)),
+            (3, 1, point(1.75, 0, 0.75)),
+        ];
+
+        for (u, v, expected) in test_cases {
+            let p = light.point_on_light(u, v, 0);
+            assert_relative_eq!(p.x(), expected.x(), epsilon = EPSILON);
+            assert_relative_eq!(p.y(), expected.y(), epsilon = EPSILON);
+            assert_relative_eq!(p.z(), expected.z(), epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn jittered_point_stays_within_its_cell() {
+        let light = area_light(
+            point(0, 0, 0),
+            vector(2, 0, 0),
+            4,
+            vector(0, 0, 1),
+            2,
+            color(1, 1, 1),
+        )
+        .build();
+
+        for seed_value in 0..20 {
+            let p = light.point_on_light(2, 1, seed_value);
+            assert!((1.0..1.5).contains(&p.x()));
+            assert!((0.5..1.0).contains(&p.z()));
+        }
+    }
+
+    #[test]
+    fn area_light_samples_every_cell() {
+        let light = area_light(
+            point(-1, 2, 4),
+            vector(2, 0, 0),
+            2,
+            vector(0, 2, 0),
+            2,
+            color(1, 1, 1),
+        )
+        .jitter(false)
+        .build();
+        let samples = light.sample_points(point(0, 0, 0));
+        assert_eq!(
+            samples,
+            vec![
+                point(-0.5, 2.5, 4),
+                point(0.5, 2.5, 4),
+                point(-0.5, 3.5, 4),
+                point(0.5, 3.5, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn jittered_samples_are_deterministic_per_point() {
+        let light = area_light(
+            point(-1, 2, 4),
+            vector(2, 0, 0),
+            4,
+            vector(0, 2, 0),
+            4,
+            color(1, 1, 1),
+        )
+        .build();
+        let p = point(0.25, 0.5, 0.75);
+        assert_eq!(light.sample_points(p), light.sample_points(p));
+        assert_ne!(light.sample_points(p), light.sample_points(point(1, 2, 3)));
+    }
+
+    #[test]
+    fn point_light_has_single_sample() {
+        let light = point_light(point(1, 2, 3), color(1, 1, 1));
+        assert_eq!(light.sample_points(point(0, 0, 0)), vec![point(1, 2, 3)]);
+        assert_eq!(light.intensity_at(point(0, 0, 0)), color(1, 1, 1));
+    }
+
+    #[test]
+    fn creating_spot_light() {
+        let light = spot_light(point(0, 5, 0), vector(0, -2, 0), color(1, 1, 1))
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_4)
+            .build();
+        assert_eq!(light.position, point(0, 5, 0));
+        assert_eq!(light.direction, vector(0, -1, 0));
+        assert_relative_eq!(light.inner_angle, FRAC_PI_6, epsilon = EPSILON);
+        assert_relative_eq!(light.outer_angle, FRAC_PI_4, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn spot_light_falloff_across_cone() {
+        let light = spot_light(point(0, 0, 0), vector(0, 0, 1), color(1, 1, 1))
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_3)
+            .build();
+
+        assert_relative_eq!(light.falloff(point(0, 0, 5)), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(light.falloff(point(0, 1, 5)), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(light.falloff(point(0, 5, 1)), 0.0, epsilon = EPSILON);
+        assert_relative_eq!(light.falloff(point(0, 0, -5)), 0.0, epsilon = EPSILON);
+
+        let halfway = light.falloff(point(0, FRAC_PI_4.sin(), FRAC_PI_4.cos()));
+        assert!(halfway > 0.0 && halfway < 1.0);
+    }
+
+    #[test]
+    fn spot_light_falloff_is_monotonic() {
+        let light = spot_light(point(0, 0, 0), vector(0, 0, 1), color(1, 1, 1))
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_3)
+            .build();
+
+        let mut previous = 1.0;
+        for step in 0..=20 {
+            let angle = FRAC_PI_6 + (FRAC_PI_3 - FRAC_PI_6) * step as f32 / 20.0;
+            let falloff = light.falloff(point(0, angle.sin(), angle.cos()));
+            assert!(falloff <= previous + EPSILON);
+            previous = falloff;
+        }
+        assert_relative_eq!(previous, 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn spot_light_intensity_depends_on_point() {
+        let light = spot_light(point(0, 0, 0), vector(0, 0, 1), color(1, 0.5, 0.25))
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_4)
+            .build();
+        assert_eq!(light.intensity_at(point(0, 0, 3)), color(1, 0.5, 0.25));
+        assert_eq!(light.intensity_at(point(3, 0, 0)), color(0, 0, 0));
+        assert_eq!(light.sample_points(point(1, 2, 3)), vec![point(0, 0, 0)]);
+    }
+
+    #[test]
+    fn directional_light_has_constant_direction() {
+        let light = directional_light(vector(0, -2, 0), color(1, 1, 1));
+        for p in [point(0, 0, 0), point(100, -50, 3)] {
+            let samples = light.sample_points(p);
+            assert_eq!(samples.len(), 1);
+            assert_eq!((samples[0] - p).normalize(), vector(0, 1, 0));
+            assert!(light.shadow_distance(p, samples[0]).is_infinite());
+        }
+    }
+
+    #[test]
+    fn positional_lights_have_finite_shadow_distance() {
+        let light = point_light(point(0, 10, 0), color(1, 1, 1));
+        let p = point(0, 4, 0);
+        let samples = light.sample_points(p);
+        assert_relative_eq!(light.shadow_distance(p, samples[0]), 6.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn lights_do_not_attenuate_by_default() {
+        let light = point_light(point(0, 10, 0), color(1, 1, 1));
+        assert_eq!(light.attenuation, Attenuation::None);
+        assert_relative_eq!(
+            light.attenuation(point(0, -90, 0), light.position),
+            1.0,
+            epsilon = EPSILON
+        );
+    }
+
+    #[test]
+    fn polynomial_attenuation_factor() {
+        let attenuation = Attenuation::Polynomial {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        assert_relative_eq!(attenuation.factor(0.0), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(attenuation.factor(2.0), 1.0 / 3.0, epsilon = EPSILON);
+        assert_relative_eq!(attenuation.factor(4.0), 1.0 / 7.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn inverse_square_attenuation_factor() {
+        let attenuation = Attenuation::InverseSquare;
+        assert_relative_eq!(attenuation.factor(1.0), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(attenuation.factor(2.0), 0.25, epsilon = EPSILON);
+        assert_relative_eq!(attenuation.factor(10.0), 0.01, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn attenuation_clamps_distance_near_the_light() {
+        let attenuation = Attenuation::InverseSquare;
+        let at_light = attenuation.factor(0.0);
+        assert!(at_light.is_finite());
+        assert!(at_light > attenuation.factor(0.001));
+        assert_relative_eq!(at_light, attenuation.factor(EPSILON / 2.0));
+        assert_relative_eq!(at_light, attenuation.factor(-1.0));
+    }
+
+    #[test]
+    fn degenerate_attenuation_leaves_light_unchanged() {
+        let attenuation = Attenuation::Polynomial {
+            constant: 0.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+        assert_relative_eq!(attenuation.factor(5.0), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn point_light_attenuates_with_distance_to_sample() {
+        let light = point_light(point(0, 10, 0), color(1, 1, 1))
+            .with_attenuation(Attenuation::InverseSquare);
+        let p = point(0, 5, 0);
+        let samples = light.sample_points(p);
+        assert_relative_eq!(light.attenuation(p, samples[0]), 0.04, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn spot_and_area_lights_accept_attenuation() {
+        let spot = spot_light(point(0, 0, 0), vector(0, 0, 1), color(1, 1, 1))
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_4)
+            .attenuation(Attenuation::InverseSquare)
+            .build();
+        assert_relative_eq!(
+            spot.attenuation(point(0, 0, 4), spot.position),
+            0.0625,
+            epsilon = EPSILON
+        );
+
+        let area = area_light(
+            point(-1, 2, -1),
+            vector(2, 0, 0),
+            2,
+            vector(0, 0, 2),
+            2,
+            color(1, 1, 1),
+        )
+        .attenuation(Attenuation::Polynomial {
+            constant: 1.0,
+            linear: 1.0,
+            quadratic: 0.0,
+        })
+        .build();
+        assert_relative_eq!(
+            area.attenuation(point(0, 0, 0), point(0, 2, 0)),
+            1.0 / 3.0,
+            epsilon = EPSILON
+        );
+    }
 }
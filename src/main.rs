@@ -0,0 +1,423 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use anyhow::{Context, Result, bail};
+use ray_tracer::{
+    Camera, Canvas, Scene, ToneMapping, ToneOperator, TraceDepth, camera, tone_mapping,
+};
+
+const USAGE: &str = "\
+Usage: ray_tracer [OPTIONS] <SCENE>
+
+Renders a YAML scene file. PPM and PNG images are tone mapped and sRGB
+encoded; HDR, PFM and OpenEXR images keep linear radiance.
+
+Options:
+  -o, --output <PATH>    Image to write, or `-` for stdout [default: <SCENE>.png]
+  -f, --format <FORMAT>  ppm, png, hdr, pfm or exr [default: from the output extension]
+      --width <PIXELS>   Image width, keeping the scene's aspect ratio unless --height is given
+      --height <PIXELS>  Image height, keeping the scene's aspect ratio unless --width is given
+  -s, --samples <N>      Samples per pixel
+  -d, --depth <N>        Maximum reflection and refraction bounces
+      --tone <OPERATOR>  clamp, reinhard or aces tone curve for ppm and png [default: clamp]
+      --exposure <STOPS> Exposure adjustment for ppm and png [default: 0]
+  -j, --threads <N>      Render threads; 1 renders on the main thread [default: all cores]
+  -q, --quiet            Do not show a progress bar
+  -h, --help             Print this help";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ppm,
+    Png,
+    Hdr,
+    Pfm,
+    Exr,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "ppm" => Format::Ppm,
+            "png" => Format::Png,
+            "hdr" => Format::Hdr,
+            "pfm" => Format::Pfm,
+            "exr" => Format::Exr,
+            _ => bail!("unknown image format `{s}`"),
+        })
+    }
+}
+
+impl Format {
+    /// Writes `canvas`, applying `tone` only for the low dynamic range
+    /// formats.
+    fn write<W: Write>(self, canvas: &Canvas, writer: W, tone: &ToneMapping) -> Result<()> {
+        match self {
+            Format::Ppm => canvas.write_ppm(writer).tone(tone).write(),
+            Format::Png => canvas.write_png(writer).tone(tone).write(),
+            Format::Hdr => canvas.write_hdr(writer),
+            Format::Pfm => canvas.write_pfm(writer),
+            Format::Exr => canvas.write_exr(writer).write(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    scene: PathBuf,
+    output: Option<PathBuf>,
+    format: Option<Format>,
+    width: Option<u16>,
+    height: Option<u16>,
+    samples: Option<u16>,
+    depth: Option<usize>,
+    tone: Option<ToneOperator>,
+    exposure: Option<f32>,
+    threads: Option<usize>,
+    quiet: bool,
+    help: bool,
+}
+
+impl Options {
+    fn parse<I>(args: I) -> Result<Options>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut scene = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                if scene.replace(PathBuf::from(&arg)).is_some() {
+                    bail!("unexpected argument `{arg}`");
+                }
+                continue;
+            }
+
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .with_context(|| format!("`{flag}` needs a value"))
+            };
+
+            match flag {
+                "-o" | "--output" => options.output = Some(value()?.into()),
+                "-f" | "--format" => options.format = Some(value()?.parse()?),
+                "--width" => options.width = Some(number(flag, &value()?)?),
+                "--height" => options.height = Some(number(flag, &value()?)?),
+                "-s" | "--samples" => options.samples = Some(number(flag, &value()?)?),
+                "-d" | "--depth" => options.depth = Some(number(flag, &value()?)?),
+                "--tone" => options.tone = Some(tone_operator(&value()?)?),
+                "--exposure" => {
+                    let value = value()?;
+                    let exposure = value.parse().ok().filter(|e: &f32| e.is_finite());
+                    options.exposure = Some(exposure.with_context(|| {
+                        format!("`{flag}` expects a number of stops, not `{value}`")
+                    })?);
+                }
+                "-j" | "--threads" => options.threads = Some(number(flag, &value()?)?),
+                "-q" | "--quiet" => options.quiet = true,
+                "-h" | "--help" => options.help = true,
+                _ => bail!("unknown option `{flag}`"),
+            }
+        }
+
+        if !options.help {
+            options.scene = scene.context("no scene file given")?;
+        }
+        Ok(options)
+    }
+
+    fn output(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.scene.with_extension("png"))
+    }
+
+    fn format(&self) -> Result<Format> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+
+        let output = self.output();
+        if output == Path::new("-") {
+            return Ok(Format::Png);
+        }
+        output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .with_context(|| {
+                format!(
+                    "cannot tell the format of {}; use --format",
+                    output.display()
+                )
+            })?
+            .parse()
+    }
+
+    /// Returns the tone mapping for PPM and PNG output, which always ends
+    /// with sRGB encoding.
+    fn tone_mapping(&self) -> ToneMapping {
+        tone_mapping()
+            .operator(self.tone.unwrap_or_default())
+            .exposure(self.exposure.unwrap_or_default())
+            .build()
+    }
+
+    /// Rebuilds the scene's camera with the overrides given on the command
+    /// line. A single dimension keeps the scene's aspect ratio.
+    fn camera(&self, scene: &Camera) -> Result<Camera> {
+        let aspect = f32::from(scene.width) / f32::from(scene.height);
+        let (width, height) = match (self.width, self.height) {
+            (None, None) => (scene.width, scene.height),
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, scaled(width, 1.0 / aspect)?),
+            (None, Some(height)) => (scaled(height, aspect)?, height),
+        };
+        if width == 0 || height == 0 {
+            bail!("the image must be at least one pixel wide and tall");
+        }
+
+        Ok(camera(width, height)
+            .field_of_view(scene.field_of_view)
+            .transform(scene.transform)
+            .parallel(self.threads != Some(1))
+            .samples_per_pixel(self.samples.unwrap_or(scene.samples_per_pixel))
+            .filter(scene.filter)
+            .maybe_adaptive_threshold(scene.adaptive_threshold)
+            .adaptive_depth(scene.adaptive_depth)
+            .aovs(scene.aovs)
+            .depth(self.depth.map_or(scene.depth, TraceDepth::from))
+            .build())
+    }
+}
+
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("`{flag}` expects a whole number, not `{value}`"))
+}
+
+fn tone_operator(value: &str) -> Result<ToneOperator> {
+    Ok(match value.to_ascii_lowercase().as_str() {
+        "clamp" => ToneOperator::Clamp,
+        "reinhard" => ToneOperator::Reinhard,
+        "aces" => ToneOperator::Aces,
+        _ => bail!("unknown tone operator `{value}`"),
+    })
+}
+
+fn scaled(size: u16, factor: f32) -> Result<u16> {
+    let scaled = (f32::from(size) * factor).round().max(1.0);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    u16::try_from(scaled as u32).context("the image would be too large")
+}
+
+/// A progress bar on standard error that redraws whenever another percent
+/// of the image is finished.
+struct Progress {
+    start: Instant,
+    drawn: AtomicUsize,
+    lock: Mutex<()>,
+}
+
+impl Progress {
+    const WIDTH: usize = 40;
+
+    fn new() -> Self {
+        let progress = Progress {
+            start: Instant::now(),
+            drawn: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+        };
+        progress.draw(0);
+        progress
+    }
+
+    fn update(&self, finished: usize, total: usize) {
+        let percent = finished * 100 / total.max(1);
+        if percent <= self.drawn.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        if percent > self.drawn.load(Ordering::Relaxed) {
+            self.drawn.store(percent, Ordering::Relaxed);
+            self.draw(percent);
+        }
+    }
+
+    fn draw(&self, percent: usize) {
+        let filled = percent * Self::WIDTH / 100;
+        eprint!(
+            "\r[{}{}] {percent:>3}% {:>6.1}s",
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH - filled),
+            self.start.elapsed().as_secs_f32()
+        );
+    }
+
+    fn finish(&self) {
+        eprintln!();
+    }
+}
+
+fn main() -> Result<()> {
+    let options = Options::parse(env::args().skip(1))
+        .with_context(|| format!("invalid arguments\n\n{USAGE}"))?;
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let format = options.format()?;
+    let scene = Scene::load(&options.scene)?;
+    let camera = options.camera(&scene.camera)?;
+
+    if let Some(threads) = options.threads.filter(|&threads| threads > 1) {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .context("failed to start render threads")?;
+    }
+
+    let (canvas, _) = if options.quiet {
+        camera.render_with_stats(&scene.world)
+    } else {
+        let progress = Progress::new();
+        let result = camera.render_with_progress(&scene.world, |finished, total| {
+            progress.update(finished, total);
+        });
+        progress.finish();
+        result
+    };
+
+    let tone = options.tone_mapping();
+    let output = options.output();
+    if output == Path::new("-") {
+        format.write(&canvas, io::stdout().lock(), &tone)?;
+    } else {
+        let file = File::create(&output)
+            .with_context(|| format!("failed to create {}", output.display()))?;
+        let mut writer = BufWriter::new(file);
+        format.write(&canvas, &mut writer, &tone)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parsing_options() {
+        let options =
+            parse("scene.yml -o out.exr --width=320 -s 16 --depth 10 -j 1 --quiet").unwrap();
+        assert_eq!(
+            options,
+            Options {
+                scene: "scene.yml".into(),
+                output: Some("out.exr".into()),
+                width: Some(320),
+                samples: Some(16),
+                depth: Some(10),
+                threads: Some(1),
+                quiet: true,
+                ..Options::default()
+            }
+        );
+        assert_eq!(options.format().unwrap(), Format::Exr);
+    }
+
+    #[test]
+    fn rejecting_bad_arguments() {
+        assert!(parse("").is_err());
+        assert!(parse("a.yml b.yml").is_err());
+        assert!(parse("a.yml --samples").is_err());
+        assert!(parse("a.yml --samples many").is_err());
+        assert!(parse("a.yml --frobnicate").is_err());
+        assert!(parse("a.yml --tone sepia").is_err());
+        assert!(parse("a.yml --exposure bright").is_err());
+        assert!(parse("a.yml --exposure inf").is_err());
+        assert!(parse("--help").unwrap().help);
+    }
+
+    #[test]
+    fn choosing_the_output_format() {
+        let options = parse("scenes/cover.yml").unwrap();
+        assert_eq!(options.output(), PathBuf::from("scenes/cover.png"));
+        assert_eq!(options.format().unwrap(), Format::Png);
+
+        assert_eq!(parse("a.yml -o -").unwrap().format().unwrap(), Format::Png);
+        assert_eq!(
+            parse("a.yml -o out.img -f HDR").unwrap().format().unwrap(),
+            Format::Hdr
+        );
+        assert!(parse("a.yml -o out.img").unwrap().format().is_err());
+        assert!(parse("a.yml -o out.jpg").unwrap().format().is_err());
+    }
+
+    #[test]
+    fn choosing_the_tone_mapping() {
+        assert_eq!(
+            parse("a.yml").unwrap().tone_mapping(),
+            ToneMapping::default()
+        );
+
+        let tone = parse("a.yml --tone ACES --exposure=-1.5")
+            .unwrap()
+            .tone_mapping();
+        assert_eq!(tone.operator, ToneOperator::Aces);
+        assert!((tone.exposure + 1.5).abs() < f32::EPSILON);
+        assert!(tone.srgb);
+    }
+
+    #[test]
+    fn overriding_the_camera() {
+        let scene = camera(400, 200).field_of_view(1.0).build();
+
+        let c = parse("a.yml").unwrap().camera(&scene).unwrap();
+        assert_eq!(
+            c,
+            camera(400, 200).field_of_view(1.0).parallel(true).build()
+        );
+
+        let c = parse("a.yml --width 100 -s 4 -d 2 -j 1")
+            .unwrap()
+            .camera(&scene)
+            .unwrap();
+        assert_eq!((c.width, c.height), (100, 50));
+        assert_eq!(c.samples_per_pixel, 4);
+        assert_eq!(c.depth, TraceDepth::from(2));
+        assert!(!c.parallel);
+
+        let c = parse("a.yml --height 300").unwrap().camera(&scene).unwrap();
+        assert_eq!((c.width, c.height), (600, 300));
+
+        assert!(parse("a.yml --width 0").unwrap().camera(&scene).is_err());
+    }
+}
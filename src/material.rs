@@ -1,7 +1,7 @@
 use bon::Builder;
 
 use crate::{
-    Color, Point, PointLight, Shape, Vector,
+    BumpMap, Color, Light, NormalMap, Point, Shape, Vector,
     color::{BLACK, WHITE},
     pattern::Pattern,
 };
@@ -31,6 +31,11 @@ pub struct Material {
     #[builder(default = 1.0)]
     pub refractive_index: f32,
     pub pattern: Option<Pattern>,
+    /// Height field that perturbs the surface normal when a hit is shaded.
+    pub bump: Option<BumpMap>,
+    /// Tangent-space normals that replace the surface normal of shapes with
+    /// texture coordinates.
+    pub normal_map: Option<NormalMap>,
 }
 
 impl std::fmt::Debug for Material {
@@ -45,6 +50,8 @@ impl std::fmt::Debug for Material {
             .field("transparency", &self.transparency)
             .field("refractive_index", &self.refractive_index)
             .field("pattern", &self.pattern.as_ref().map(|_| "Pattern"))
+            .field("bump", &self.bump.as_ref().map(|_| "BumpMap"))
+            .field("normal_map", &self.normal_map.as_ref().map(|_| "NormalMap"))
             .finish()
     }
 }
@@ -60,99 +67,139 @@ impl PartialEq for Material {
 }
 
 impl Material {
+    /// Computes the full Phong colour at `point`. `light_intensity` is the
+    /// fraction of the light that reaches `point`, from `0.0` when it is
+    /// entirely in shadow to `1.0` when it is fully lit.
     #[must_use]
     pub fn lighting(
         &self,
         object: &Shape,
-        light: &PointLight,
+        light: &dyn Light,
         point: Point,
         eyev: Vector,
         normalv: Vector,
-        in_shadow: bool,
+        light_intensity: f32,
     ) -> Color {
         let color = self
             .pattern
             .as_ref()
             .map_or(self.color, |p| p.pattern_at_shape(object, point));
-        let effective_color = color * light.intensity;
-        let lightv = (light.position - point).normalize();
-        let ambient = effective_color * self.ambient;
+        let ambient = color * light.intensity_at(point) * self.ambient;
 
-        if in_shadow {
-            return ambient;
-        }
-
-        let light_dot_normal = lightv.dot(&normalv);
-        let (diffuse, specular) = if light_dot_normal < 0.0 {
-            (BLACK, BLACK)
-        } else {
-            let diffuse = effective_color * self.diffuse * light_dot_normal;
-
-            let reflectv = (-lightv).reflect(&normalv);
-            let reflect_dot_eye = reflectv.dot(&eyev);
-
-            let specular = if reflect_dot_eye <= 0.0 {
-                BLACK
-            } else {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                light.intensity * self.specular * factor
-            };
-
-            (diffuse, specular)
-        };
-
-        ambient + diffuse + specular
+        ambient + self.lighting_contribution(object, light, point, eyev, normalv, light_intensity)
     }
 
+    /// Computes the diffuse and specular terms at `point`, attenuated by the
+    /// distance to each sample point on `light`, averaged over those samples
+    /// and scaled by `light_intensity`.
     #[must_use]
     pub fn lighting_contribution(
         &self,
         object: &Shape,
-        light: &PointLight,
+        light: &dyn Light,
         point: Point,
         eyev: Vector,
         normalv: Vector,
-        in_shadow: bool,
+        light_intensity: f32,
     ) -> Color {
-        if in_shadow {
+        if light_intensity <= 0.0 {
             return BLACK;
         }
 
+        let shading = self.sample_shading(object, light, point, eyev, normalv);
+        let mut sum = BLACK;
+        let mut count = 0usize;
+        light.for_each_sample(point, &mut |sample| {
+            sum = sum + shading.at(sample);
+            count += 1;
+        });
+        if count == 0 {
+            return BLACK;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let count = count as f32;
+        sum * (light_intensity / count)
+    }
+
+    /// Prepares the diffuse and specular terms at `point` for evaluating
+    /// one sample of `light` at a time.
+    pub(crate) fn sample_shading<'a>(
+        &'a self,
+        object: &Shape,
+        light: &'a dyn Light,
+        point: Point,
+        eyev: Vector,
+        normalv: Vector,
+    ) -> SampleShading<'a> {
         let color = self
             .pattern
             .as_ref()
             .map_or(self.color, |p| p.pattern_at_shape(object, point));
-        let effective_color = color * light.intensity;
-        let lightv = (light.position - point).normalize();
+        let intensity = light.intensity_at(point);
+
+        SampleShading {
+            material: self,
+            light,
+            point,
+            eyev,
+            normalv,
+            effective_color: color * intensity,
+            intensity,
+        }
+    }
+}
 
-        let light_dot_normal = lightv.dot(&normalv);
+/// The diffuse and specular shading of one point by one light, evaluated per
+/// sample point on the light.
+pub(crate) struct SampleShading<'a> {
+    material: &'a Material,
+    light: &'a dyn Light,
+    point: Point,
+    eyev: Vector,
+    normalv: Vector,
+    effective_color: Color,
+    intensity: Color,
+}
+
+impl SampleShading<'_> {
+    /// Returns the diffuse and specular terms for light arriving from
+    /// `sample`, attenuated by its distance.
+    pub(crate) fn at(&self, sample: Point) -> Color {
+        let material = self.material;
+        let lightv = (sample - self.point).normalize();
+
+        let light_dot_normal = lightv.dot(&self.normalv);
         if light_dot_normal < 0.0 {
             return BLACK;
         }
 
-        let diffuse = effective_color * self.diffuse * light_dot_normal;
+        let diffuse = self.effective_color * material.diffuse * light_dot_normal;
 
-        let reflectv = (-lightv).reflect(&normalv);
-        let reflect_dot_eye = reflectv.dot(&eyev);
+        let reflectv = (-lightv).reflect(&self.normalv);
+        let reflect_dot_eye = reflectv.dot(&self.eyev);
 
         let specular = if reflect_dot_eye <= 0.0 {
             BLACK
         } else {
-            let factor = reflect_dot_eye.powf(self.shininess);
-            light.intensity * self.specular * factor
+            let factor = reflect_dot_eye.powf(material.shininess);
+            self.intensity * material.specular * factor
         };
 
-        diffuse + specular
+        (diffuse + specular) * self.light.attenuation(self.point, sample)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
     use approx::assert_relative_eq;
 
     use super::*;
     use crate::{
-        EPSILON, color, color::BLACK, pattern::stripe_pattern, point, point_light, sphere, vector,
+        Attenuation, EPSILON, area_light, color, color::BLACK, pattern::stripe_pattern, point,
+        point_light, sphere, vector,
     };
 
     #[test]
@@ -186,7 +233,7 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let result = m.lighting(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 1.9, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 1.9, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 1.9, epsilon = EPSILON);
@@ -201,7 +248,7 @@ mod tests {
         let eyev = vector(0.0, sqrt2_over_2, -sqrt2_over_2);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let result = m.lighting(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 1.0, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 1.0, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 1.0, epsilon = EPSILON);
@@ -215,7 +262,7 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 10, -10), color(1, 1, 1));
-        let result = m.lighting(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 0.7364, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 0.7364, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 0.7364, epsilon = EPSILON);
@@ -230,7 +277,7 @@ mod tests {
         let eyev = vector(0.0, -sqrt2_over_2, -sqrt2_over_2);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 10, -10), color(1, 1, 1));
-        let result = m.lighting(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 1.6364, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 1.6364, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 1.6364, epsilon = EPSILON);
@@ -244,7 +291,7 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, 10), color(1, 1, 1));
-        let result = m.lighting(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 0.1, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 0.1, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 0.1, epsilon = EPSILON);
@@ -258,8 +305,8 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let in_shadow = true;
-        let result = m.lighting(&object, &light, position, eyev, normalv, in_shadow);
+        let light_intensity = 0.0;
+        let result = m.lighting(&object, &light, position, eyev, normalv, light_intensity);
         assert_relative_eq!(result.red(), 0.1, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 0.1, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 0.1, epsilon = EPSILON);
@@ -268,7 +315,11 @@ mod tests {
     #[test]
     fn lighting_with_pattern_applied() {
         let m = Material::builder()
-            .pattern(stripe_pattern(color(1, 1, 1), color(0, 0, 0)).build())
+            .pattern(
+                stripe_pattern(color(1, 1, 1), color(0, 0, 0))
+                    .build()
+                    .unwrap(),
+            )
             .ambient(1.0)
             .diffuse(0.0)
             .specular(0.0)
@@ -277,8 +328,8 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let c1 = m.lighting(&object, &light, point(0.9, 0, 0), eyev, normalv, false);
-        let c2 = m.lighting(&object, &light, point(1.1, 0, 0), eyev, normalv, false);
+        let c1 = m.lighting(&object, &light, point(0.9, 0, 0), eyev, normalv, 1.0);
+        let c2 = m.lighting(&object, &light, point(1.1, 0, 0), eyev, normalv, 1.0);
         assert_eq!(c1, color(1, 1, 1));
         assert_eq!(c2, color(0, 0, 0));
     }
@@ -291,7 +342,7 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let result = m.lighting_contribution(&object, &light, position, eyev, normalv, false);
+        let result = m.lighting_contribution(&object, &light, position, eyev, normalv, 1.0);
         assert_relative_eq!(result.red(), 1.8, epsilon = EPSILON);
         assert_relative_eq!(result.green(), 1.8, epsilon = EPSILON);
         assert_relative_eq!(result.blue(), 1.8, epsilon = EPSILON);
@@ -305,7 +356,103 @@ mod tests {
         let eyev = vector(0, 0, -1);
         let normalv = vector(0, 0, -1);
         let light = point_light(point(0, 0, -10), color(1, 1, 1));
-        let result = m.lighting_contribution(&object, &light, position, eyev, normalv, true);
+        let result = m.lighting_contribution(&object, &light, position, eyev, normalv, 0.0);
         assert_eq!(result, BLACK);
     }
+
+    #[test]
+    fn lighting_uses_light_intensity_to_attenuate_color() {
+        let m = Material::builder()
+            .ambient(0.1)
+            .diffuse(0.9)
+            .specular(0.0)
+            .color(color(1, 1, 1))
+            .build();
+        let object = sphere().build();
+        let position = point(0, 0, -1);
+        let eyev = vector(0, 0, -1);
+        let normalv = vector(0, 0, -1);
+        let light = point_light(point(0, 0, -10), color(1, 1, 1));
+
+        let test_cases = [(1.0, 1.0), (0.5, 0.55), (0.0, 0.1)];
+        for (intensity, expected) in test_cases {
+            let result = m.lighting(&object, &light, position, eyev, normalv, intensity);
+            assert_relative_eq!(result.red(), expected, epsilon = EPSILON);
+            assert_relative_eq!(result.green(), expected, epsilon = EPSILON);
+            assert_relative_eq!(result.blue(), expected, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn lighting_samples_the_area_light() {
+        let light = area_light(
+            point(-0.5, -0.5, -5),
+            vector(1, 0, 0),
+            2,
+            vector(0, 1, 0),
+            2,
+            color(1, 1, 1),
+        )
+        .jitter(false)
+        .build();
+        let m = Material::builder()
+            .ambient(0.1)
+            .diffuse(0.9)
+            .specular(0.0)
+            .color(color(1, 1, 1))
+            .build();
+        let object = sphere().build();
+        let eye = point(0, 0, -5);
+
+        let test_cases = [
+            (point(0, 0, -1), 0.9965),
+            (point(0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2), 0.62318),
+        ];
+        for (position, expected) in test_cases {
+            let eyev = (eye - position).normalize();
+            let normalv = vector(position.x(), position.y(), position.z());
+            let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
+            assert_relative_eq!(result.red(), expected, epsilon = EPSILON);
+            assert_relative_eq!(result.green(), expected, epsilon = EPSILON);
+            assert_relative_eq!(result.blue(), expected, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn lighting_with_attenuated_light() {
+        let m = material();
+        let object = sphere().build();
+        let position = point(0, 0, 0);
+        let eyev = vector(0, 0, -1);
+        let normalv = vector(0, 0, -1);
+        let light = point_light(point(0, 0, -10), color(1, 1, 1)).with_attenuation(
+            Attenuation::Polynomial {
+                constant: 1.0,
+                linear: 0.1,
+                quadratic: 0.0,
+            },
+        );
+
+        let contribution = m.lighting_contribution(&object, &light, position, eyev, normalv, 1.0);
+        assert_relative_eq!(contribution.red(), 0.9, epsilon = EPSILON);
+
+        let result = m.lighting(&object, &light, position, eyev, normalv, 1.0);
+        assert_relative_eq!(result.red(), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(result.green(), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(result.blue(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn inverse_square_light_is_dimmer_further_away() {
+        let m = material();
+        let object = sphere().build();
+        let eyev = vector(0, 0, -1);
+        let normalv = vector(0, 0, -1);
+        let light = point_light(point(0, 0, -2), color(4, 4, 4))
+            .with_attenuation(Attenuation::InverseSquare);
+
+        let near = m.lighting_contribution(&object, &light, point(0, 0, 0), eyev, normalv, 1.0);
+        let far = m.lighting_contribution(&object, &light, point(0, 0, 2), eyev, normalv, 1.0);
+        assert_relative_eq!(far.red() * 4.0, near.red(), epsilon = EPSILON);
+    }
 }
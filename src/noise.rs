@@ -0,0 +1,232 @@
+use crate::{
+    Point, Vector, point,
+    sampling::{random_unit, seed},
+    vector,
+};
+
+/// Offsets that decorrelate the three noise samples in
+/// [`Noise::vector_at`].
+const VECTOR_OFFSETS: [(f32, f32, f32); 3] = [
+    (0.0, 0.0, 0.0),
+    (31.416, -47.853, 12.793),
+    (-89.231, 71.134, -56.479),
+];
+
+/// Three-dimensional gradient noise after Ken Perlin's improved noise.
+///
+/// The lattice gradients are picked through a permutation table shuffled
+/// from `seed`, so different seeds give unrelated but repeatable noise.
+/// Values are smooth, roughly in `-1.0..=1.0`, and zero at every integer
+/// lattice point.
+#[derive(Clone)]
+pub struct Noise {
+    permutation: [u8; 512],
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise::new(0)
+    }
+}
+
+impl Noise {
+    #[must_use]
+    pub fn new(noise_seed: u64) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| {
+            #[allow(clippy::cast_possible_truncation)]
+            let i = i as u8;
+            i
+        });
+        for i in (1..table.len()).rev() {
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let j = (random_unit(seed(&[noise_seed, i as u64])) * (i + 1) as f32) as usize;
+            table.swap(i, j.min(i));
+        }
+
+        Noise {
+            permutation: std::array::from_fn(|i| table[i % 256]),
+        }
+    }
+
+    /// Returns the noise at `p`.
+    #[must_use]
+    pub fn noise(&self, p: Point) -> f32 {
+        let (x, y, z) = (p.x(), p.y(), p.z());
+        let (fx, fy, fz) = (x.floor(), y.floor(), z.floor());
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let cell = |f: f32| (f as i32 & 255) as usize;
+        let (xi, yi, zi) = (cell(fx), cell(fy), cell(fz));
+        let (x, y, z) = (x - fx, y - fy, z - fz);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let hash = |i: usize| usize::from(p[i]);
+        let a = hash(xi) + yi;
+        let (aa, ab) = (hash(a) + zi, hash(a + 1) + zi);
+        let b = hash(xi + 1) + yi;
+        let (ba, bb) = (hash(b) + zi, hash(b + 1) + zi);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Returns fractal Brownian motion at `p`: `octaves` layers of noise,
+    /// each at twice the frequency and half the amplitude of the last,
+    /// normalised back into the range of a single layer.
+    #[must_use]
+    pub fn fbm(&self, p: Point, octaves: u32) -> f32 {
+        self.octaves(p, octaves, |n| n)
+    }
+
+    /// Like [`Noise::fbm`], but sums the absolute value of each layer, which
+    /// gives creases where the noise crosses zero. The result is roughly in
+    /// `0.0..=1.0`.
+    #[must_use]
+    pub fn turbulence(&self, p: Point, octaves: u32) -> f32 {
+        self.octaves(p, octaves, f32::abs)
+    }
+
+    /// Returns a vector whose components are independent noise values at
+    /// `p`, for displacing points.
+    #[must_use]
+    pub fn vector_at(&self, p: Point, octaves: u32) -> Vector {
+        let [x, y, z] =
+            VECTOR_OFFSETS.map(|(dx, dy, dz)| self.fbm(p + vector(dx, dy, dz), octaves));
+        vector(x, y, z)
+    }
+
+    fn octaves(&self, p: Point, octaves: u32, layer: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            let scaled = point(p.x() * frequency, p.y() * frequency, p.z() * frequency);
+            sum += layer(self.noise(scaled)) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Dots one of twelve gradient directions, picked by `hash`, with the
+/// offset `(x, y, z)` from its lattice point.
+fn grad(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..4 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::EPSILON;
+
+    fn samples() -> impl Iterator<Item = Point> {
+        (0..1000u16).map(|i| {
+            let t = f32::from(i);
+            point(t * 0.137 - 40.0, t * 0.071 + 3.3, 17.0 - t * 0.093)
+        })
+    }
+
+    #[test]
+    fn noise_is_zero_on_the_lattice() {
+        let noise = Noise::new(7);
+        assert_relative_eq!(noise.noise(point(0, 0, 0)), 0.0);
+        assert_relative_eq!(noise.noise(point(3, -2, 11)), 0.0);
+        assert_relative_eq!(noise.noise(point(-300, 0, 1)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_bounded_and_varies() {
+        let noise = Noise::default();
+        let values: Vec<f32> = samples().map(|p| noise.noise(p)).collect();
+        assert!(values.iter().all(|n| (-1.0..=1.0).contains(n)));
+        assert!(values.iter().any(|&n| n > 0.2));
+        assert!(values.iter().any(|&n| n < -0.2));
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let noise = Noise::default();
+        for p in samples() {
+            let nearby = p + vector(0.001, -0.001, 0.001);
+            assert_relative_eq!(noise.noise(p), noise.noise(nearby), epsilon = 0.01);
+        }
+    }
+
+    #[test]
+    fn seeds_give_repeatable_but_different_noise() {
+        let p = point(1.3, 2.7, -0.4);
+        assert_relative_eq!(Noise::new(5).noise(p), Noise::new(5).noise(p));
+        assert!(samples().any(|p| (Noise::new(5).noise(p) - Noise::new(6).noise(p)).abs() > 0.1));
+    }
+
+    #[test]
+    fn fbm_and_turbulence_combine_octaves() {
+        let noise = Noise::new(3);
+        let p = point(0.3, 1.7, 2.2);
+        assert_relative_eq!(noise.fbm(p, 1), noise.noise(p));
+        assert_relative_eq!(noise.turbulence(p, 1), noise.noise(p).abs());
+
+        let double = point(0.6, 3.4, 4.4);
+        let expected = (noise.noise(p) + noise.noise(double) * 0.5) / 1.5;
+        assert_relative_eq!(noise.fbm(p, 2), expected, epsilon = EPSILON);
+        assert!(samples().all(|p| (0.0..=1.0).contains(&noise.turbulence(p, 4))));
+    }
+
+    #[test]
+    fn noise_vectors_have_independent_components() {
+        let noise = Noise::default();
+        let p = point(0.5, 0.5, 0.5);
+        let v = noise.vector_at(p, 1);
+        assert_relative_eq!(v.x(), noise.noise(p));
+        assert!((v.x() - v.y()).abs() > EPSILON || (v.y() - v.z()).abs() > EPSILON);
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
 pub struct ObjParser {
     pub vertices: Vec<Point>,
     pub normals: Vec<Vector>,
+    pub uvs: Vec<(f32, f32)>,
     pub default_group: Shape,
     pub groups: HashMap<String, Shape>,
     pub ignored_lines: usize,
@@ -18,12 +19,14 @@ pub struct ObjParser {
 #[derive(Clone, Copy)]
 struct FaceVertex {
     vertex_idx: usize,
+    uv_idx: Option<usize>,
     normal_idx: Option<usize>,
 }
 
 fn parse_face_vertex(s: &str) -> Option<FaceVertex> {
     let parts: Vec<&str> = s.split('/').collect();
     let vertex_idx: usize = parts.first()?.parse().ok()?;
+    let uv_idx = parts.get(1).and_then(|part| part.parse().ok());
     let normal_idx = if parts.len() >= 3 && !parts[2].is_empty() {
         parts[2].parse().ok()
     } else {
@@ -31,43 +34,70 @@ fn parse_face_vertex(s: &str) -> Option<FaceVertex> {
     };
     Some(FaceVertex {
         vertex_idx,
+        uv_idx,
         normal_idx,
     })
 }
 
+/// A face vertex with its indices resolved.
+#[derive(Clone, Copy)]
+struct Corner {
+    point: Point,
+    uv: Option<(f32, f32)>,
+    normal: Option<Vector>,
+}
+
+/// Splits a polygonal face into a fan of triangles around its first vertex,
+/// or returns `None` if the face has fewer than three vertices or refers to
+/// a vertex, texture coordinate or normal that has not been defined.
 fn fan_triangulate(
     face_vertices: &[FaceVertex],
     vertices: &[Point],
     normals: &[Vector],
-) -> Vec<Shape> {
-    if face_vertices.len() < 3 {
-        return vec![];
+    uvs: &[(f32, f32)],
+) -> Option<Vec<Shape>> {
+    let corners = face_vertices
+        .iter()
+        .map(|face_vertex| {
+            Some(Corner {
+                point: *vertices.get(face_vertex.vertex_idx)?,
+                uv: match face_vertex.uv_idx {
+                    Some(idx) => Some(*uvs.get(idx)?),
+                    None => None,
+                },
+                normal: match face_vertex.normal_idx {
+                    Some(idx) => Some(*normals.get(idx)?),
+                    None => None,
+                },
+            })
+        })
+        .collect::<Option<Vec<Corner>>>()?;
+
+    if corners.len() < 3 {
+        return None;
     }
 
-    (1..face_vertices.len() - 1)
+    let triangles = (1..corners.len() - 1)
         .map(|i| {
-            let v1 = &face_vertices[0];
-            let v2 = &face_vertices[i];
-            let v3 = &face_vertices[i + 1];
-
-            let p1 = vertices[v1.vertex_idx];
-            let p2 = vertices[v2.vertex_idx];
-            let p3 = vertices[v3.vertex_idx];
-
-            match (v1.normal_idx, v2.normal_idx, v3.normal_idx) {
-                (Some(n1_idx), Some(n2_idx), Some(n3_idx)) => smooth_triangle(
-                    p1,
-                    p2,
-                    p3,
-                    normals[n1_idx],
-                    normals[n2_idx],
-                    normals[n3_idx],
-                )
-                .build(),
-                _ => triangle(p1, p2, p3).build(),
+            let (c1, c2, c3) = (corners[0], corners[i], corners[i + 1]);
+            let face_uvs = match (c1.uv, c2.uv, c3.uv) {
+                (Some(uv1), Some(uv2), Some(uv3)) => Some([uv1, uv2, uv3]),
+                _ => None,
+            };
+
+            match (c1.normal, c2.normal, c3.normal) {
+                (Some(n1), Some(n2), Some(n3)) => {
+                    smooth_triangle(c1.point, c2.point, c3.point, n1, n2, n3)
+                        .maybe_uvs(face_uvs)
+                        .build()
+                }
+                _ => triangle(c1.point, c2.point, c3.point)
+                    .maybe_uvs(face_uvs)
+                    .build(),
             }
         })
-        .collect()
+        .collect();
+    Some(triangles)
 }
 
 impl FromStr for ObjParser {
@@ -76,6 +106,7 @@ impl FromStr for ObjParser {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut vertices = vec![point(0, 0, 0)];
         let mut normals = vec![vector(0, 0, 0)];
+        let mut uvs = vec![(0.0, 0.0)];
         let default_group = group().build();
         let mut groups: HashMap<String, Shape> = HashMap::new();
         let mut current_group: Option<String> = None;
@@ -110,6 +141,13 @@ impl FromStr for ObjParser {
                         ignored_lines += 1;
                     }
                 }
+                "vt" if parts.len() >= 3 => {
+                    if let (Ok(u), Ok(v)) = (parts[1].parse::<f32>(), parts[2].parse::<f32>()) {
+                        uvs.push((u, v));
+                    } else {
+                        ignored_lines += 1;
+                    }
+                }
                 "g" if parts.len() >= 2 => {
                     let name = parts[1].to_string();
                     groups
@@ -123,8 +161,9 @@ impl FromStr for ObjParser {
                         .filter_map(|s| parse_face_vertex(s))
                         .collect();
 
-                    if face_vertices.len() >= 3 {
-                        let triangles = fan_triangulate(&face_vertices, &vertices, &normals);
+                    if let Some(triangles) =
+                        fan_triangulate(&face_vertices, &vertices, &normals, &uvs)
+                    {
                         let target_group = current_group
                             .as_ref()
                             .and_then(|name| groups.get(name))
@@ -144,10 +183,12 @@ impl FromStr for ObjParser {
         }
 
         let root_group = build_root_group(&default_group, &groups);
+        root_group.build_bvh();
 
         Ok(ObjParser {
             vertices,
             normals,
+            uvs,
             default_group,
             groups,
             ignored_lines,
@@ -480,4 +521,117 @@ f 1//3 3//2 2//1
         assert_eq!(t2.n2, parser.normals[2]);
         assert_eq!(t2.n3, parser.normals[1]);
     }
+
+    #[test]
+    fn faces_with_texture_coordinates() {
+        let file = "\
+v 0 1 0
+v -1 0 0
+v 1 0 0
+
+vt 0.5 1
+vt 0 0
+vt 1 0 0
+
+vn 0 0 -1
+
+f 1/1 2/2 3/3
+f 1/1/1 2/2/1 3/3/1
+f 1//1 2//1 3//1
+";
+        let parser: ObjParser = file.parse().unwrap();
+        assert_eq!(
+            parser.uvs,
+            vec![(0.0, 0.0), (0.5, 1.0), (0.0, 0.0), (1.0, 0.0)]
+        );
+        assert_eq!(parser.ignored_lines, 0);
+
+        let inner = parser.default_group.inner();
+        let group = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<crate::shape::Group>()
+            .expect("default_group should be a Group");
+
+        let children = group.children();
+        assert_eq!(children.len(), 3);
+
+        let flat = children[0].inner();
+        let flat = flat
+            .geometry
+            .as_any()
+            .downcast_ref::<Triangle>()
+            .expect("child should be Triangle");
+        assert_eq!(flat.uvs, Some([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)]));
+
+        let smooth = children[1].inner();
+        let smooth = smooth
+            .geometry
+            .as_any()
+            .downcast_ref::<crate::shape::SmoothTriangle>()
+            .expect("child should be SmoothTriangle");
+        assert_eq!(smooth.uvs, Some([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)]));
+
+        let untextured = children[2].inner();
+        let untextured = untextured
+            .geometry
+            .as_any()
+            .downcast_ref::<crate::shape::SmoothTriangle>()
+            .expect("child should be SmoothTriangle");
+        assert_eq!(untextured.uvs, None);
+    }
+
+    #[test]
+    fn faces_with_undefined_indices_are_ignored() {
+        let file = "\
+v 0 1 0
+v -1 0 0
+v 1 0 0
+
+vt 0 0
+
+f 1/5 2/6 3/7
+f 1 2 4
+f 1//2 2//2 3//2
+f 1/1 2/1 3/1
+";
+        let parser: ObjParser = file.parse().unwrap();
+        assert_eq!(parser.ignored_lines, 3);
+
+        let inner = parser.default_group.inner();
+        let group = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<crate::shape::Group>()
+            .expect("default_group should be a Group");
+        assert_eq!(group.children().len(), 1);
+    }
+
+    #[test]
+    fn parsed_groups_are_partitioned_into_bvh() {
+        let file = "\
+v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+g FirstGroup
+f 1 2 3
+f 1 3 4
+";
+        let parser: ObjParser = file.parse().unwrap();
+
+        let g1 = parser
+            .groups
+            .get("FirstGroup")
+            .expect("FirstGroup should exist");
+        let g1_inner = g1.inner();
+        let g1_group = g1_inner
+            .geometry
+            .as_any()
+            .downcast_ref::<crate::shape::Group>()
+            .expect("FirstGroup should be a Group");
+        assert!(g1_group.has_bvh());
+        assert_eq!(g1_group.children().len(), 2);
+    }
 }
@@ -1,97 +1,194 @@
 use std::sync::Arc;
 
+use anyhow::{Context, Result};
 use bon::builder;
 
-use crate::{Color, Matrix4, Point, Shape, identity_matrix};
+use crate::{Color, Matrix4, ORIGIN, Point, Shape, identity_matrix};
 
+mod image;
+mod noise;
+mod uv;
+
+pub use image::{TextureFilter, TextureWrap, cube_map_images, uv_image};
+pub use noise::{clouds_pattern, marble_pattern, perturbed, wood_pattern};
+pub use uv::{CubeFace, UvMapping, UvPattern, align_check, cube_map, texture_map, uv_checkers};
+
+/// A colour that varies over space, in its own space given by `transform`.
+///
+/// The pattern constructors accept sub-patterns as well as plain colours for
+/// their parts. A sub-pattern's transform is relative to the pattern it is
+/// nested in. Constructors fail if their transform is not invertible, so the
+/// inverse is computed once rather than for every point that is shaded.
 #[derive(Clone)]
 pub struct Pattern {
-    pub transform: Matrix4,
+    transform: Matrix4,
+    inverse_transform: Matrix4,
     point_to_color: Arc<dyn Fn(Point) -> Color + Send + Sync>,
 }
 
 impl Pattern {
+    /// Creates a pattern from a function of points in its own space.
+    ///
+    /// # Errors
+    /// Returns an error if `transform` is not invertible.
+    pub fn new(
+        transform: Matrix4,
+        point_to_color: impl Fn(Point) -> Color + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let inverse_transform = transform
+            .inverse()
+            .context("pattern transform is not invertible")?;
+        Ok(Pattern {
+            transform,
+            inverse_transform,
+            point_to_color: Arc::new(point_to_color),
+        })
+    }
+
+    /// Returns this pattern's transformation matrix.
+    #[must_use]
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    /// Returns the inverse of this pattern's transformation matrix.
+    #[must_use]
+    pub fn inverse_transform(&self) -> Matrix4 {
+        self.inverse_transform
+    }
+
     #[must_use]
     pub fn pattern_at(&self, point: Point) -> Color {
         (self.point_to_color)(point)
     }
 
     /// # Panics
-    /// Panics if the shape's or pattern's transform matrix is not invertible.
+    /// Panics if the shape's internal lock is poisoned.
     #[must_use]
     pub fn pattern_at_shape(&self, shape: &Shape, world_point: Point) -> Color {
         let object_point = shape.world_to_object(world_point);
-        let pattern_point = self.transform.inverse().expect("invertible") * object_point;
-        self.pattern_at(pattern_point)
+        self.pattern_at(self.inverse_transform * object_point)
+    }
+
+    /// Turns this pattern into a function of points in the space of a
+    /// pattern it is nested in, applying its own transform on the way.
+    fn nested(self) -> impl Fn(Point) -> Color + Send + Sync + 'static {
+        move |p| self.pattern_at(self.inverse_transform * p)
     }
 }
 
+impl From<Color> for Pattern {
+    fn from(color: Color) -> Self {
+        solid_pattern(color)
+    }
+}
+
+/// A pattern that is `color` everywhere, for nesting a plain colour where a
+/// pattern is expected.
 #[must_use]
+pub fn solid_pattern(color: Color) -> Pattern {
+    Pattern {
+        transform: identity_matrix(),
+        inverse_transform: identity_matrix(),
+        point_to_color: Arc::new(move |_| color),
+    }
+}
+
 #[builder(finish_fn = build)]
 pub fn stripe_pattern(
-    #[builder(start_fn)] a: Color,
-    #[builder(start_fn)] b: Color,
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
     #[builder(default = identity_matrix())] transform: Matrix4,
-) -> Pattern {
-    Pattern {
-        transform,
-        point_to_color: Arc::new(move |p| {
-            #[allow(clippy::cast_possible_truncation)]
-            let value = p.x().floor() as i32;
-            if value % 2 == 0 { a } else { b }
-        }),
-    }
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        #[allow(clippy::cast_possible_truncation)]
+        let value = p.x().floor() as i32;
+        if value % 2 == 0 { a(p) } else { b(p) }
+    })
 }
 
-#[must_use]
 #[builder(finish_fn = build)]
 pub fn gradient_pattern(
-    #[builder(start_fn)] a: Color,
-    #[builder(start_fn)] b: Color,
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
     #[builder(default = identity_matrix())] transform: Matrix4,
-) -> Pattern {
-    Pattern {
-        transform,
-        point_to_color: Arc::new(move |p| {
-            let distance = b - a;
-            let fraction = p.x() - p.x().floor();
-            a + distance * fraction
-        }),
-    }
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| blend(a(p), b(p), p.x() - p.x().floor()))
 }
 
-#[must_use]
 #[builder(finish_fn = build)]
 pub fn ring_pattern(
-    #[builder(start_fn)] a: Color,
-    #[builder(start_fn)] b: Color,
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
     #[builder(default = identity_matrix())] transform: Matrix4,
-) -> Pattern {
-    Pattern {
-        transform,
-        point_to_color: Arc::new(move |p| {
-            #[allow(clippy::cast_possible_truncation)]
-            let value = (p.x().powi(2) + p.z().powi(2)).sqrt().floor() as i32;
-            if value % 2 == 0 { a } else { b }
-        }),
-    }
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        #[allow(clippy::cast_possible_truncation)]
+        let value = (p.x().powi(2) + p.z().powi(2)).sqrt().floor() as i32;
+        if value % 2 == 0 { a(p) } else { b(p) }
+    })
 }
 
-#[must_use]
 #[builder(finish_fn = build)]
 pub fn checkers_pattern(
-    #[builder(start_fn)] a: Color,
-    #[builder(start_fn)] b: Color,
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
     #[builder(default = identity_matrix())] transform: Matrix4,
-) -> Pattern {
-    Pattern {
-        transform,
-        point_to_color: Arc::new(move |p| {
-            #[allow(clippy::cast_possible_truncation)]
-            let value = (p.x().floor() + p.y().floor() + p.z().floor()) as i32;
-            if value % 2 == 0 { a } else { b }
-        }),
-    }
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        #[allow(clippy::cast_possible_truncation)]
+        let value = (p.x().floor() + p.y().floor() + p.z().floor()) as i32;
+        if value % 2 == 0 { a(p) } else { b(p) }
+    })
+}
+
+/// Blends from `a` to `b` with the distance from the origin, repeating
+/// every unit, so that it forms concentric shells.
+#[builder(finish_fn = build)]
+pub fn radial_gradient_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        let distance = (p - ORIGIN).magnitude();
+        blend(a(p), b(p), distance - distance.floor())
+    })
+}
+
+/// Blends from `a` to `b` with the distance from the `y` axis, repeating
+/// every unit, like a [`ring_pattern`] with soft rings.
+#[builder(finish_fn = build)]
+pub fn ring_gradient_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        let distance = (p.x().powi(2) + p.z().powi(2)).sqrt();
+        blend(a(p), b(p), distance - distance.floor())
+    })
+}
+
+/// The average of `a` and `b` at every point.
+#[builder(finish_fn = build)]
+pub fn blended_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| blend(a(p), b(p), 0.5))
+}
+
+fn blend(a: Color, b: Color, t: f32) -> Color {
+    a + (b - a) * t
 }
 
 /// A test pattern that returns a color based on the point's coordinates.
@@ -100,6 +197,7 @@ pub fn checkers_pattern(
 pub fn test_pattern() -> Pattern {
     Pattern {
         transform: identity_matrix(),
+        inverse_transform: identity_matrix(),
         point_to_color: Arc::new(|p| crate::color(p.x(), p.y(), p.z())),
     }
 }
@@ -115,7 +213,7 @@ mod tests {
 
     #[test]
     fn stripe_pattern_constant_in_y() {
-        let pattern = stripe_pattern(WHITE, BLACK).build();
+        let pattern = stripe_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 1, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 2, 0)), WHITE);
@@ -123,7 +221,7 @@ mod tests {
 
     #[test]
     fn stripe_pattern_constant_in_z() {
-        let pattern = stripe_pattern(WHITE, BLACK).build();
+        let pattern = stripe_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 0, 1)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 0, 2)), WHITE);
@@ -131,7 +229,7 @@ mod tests {
 
     #[test]
     fn stripe_pattern_alternates_in_x() {
-        let pattern = stripe_pattern(WHITE, BLACK).build();
+        let pattern = stripe_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0.9, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(1, 0, 0)), BLACK);
@@ -143,7 +241,7 @@ mod tests {
     #[test]
     fn stripes_with_object_transformation() {
         let object = sphere().transform(transform::scaling(2, 2, 2)).build();
-        let pattern = stripe_pattern(WHITE, BLACK).build();
+        let pattern = stripe_pattern(WHITE, BLACK).build().unwrap();
         let c = pattern.pattern_at_shape(&object, point(1.5, 0, 0));
         assert_eq!(c, WHITE);
     }
@@ -153,7 +251,8 @@ mod tests {
         let object = sphere().build();
         let pattern = stripe_pattern(WHITE, BLACK)
             .transform(transform::scaling(2, 2, 2))
-            .build();
+            .build()
+            .unwrap();
         let c = pattern.pattern_at_shape(&object, point(1.5, 0, 0));
         assert_eq!(c, WHITE);
     }
@@ -163,7 +262,8 @@ mod tests {
         let object = sphere().transform(transform::scaling(2, 2, 2)).build();
         let pattern = stripe_pattern(WHITE, BLACK)
             .transform(transform::translation(0.5, 0, 0))
-            .build();
+            .build()
+            .unwrap();
         let c = pattern.pattern_at_shape(&object, point(2.5, 0, 0));
         assert_eq!(c, WHITE);
     }
@@ -171,15 +271,47 @@ mod tests {
     #[test]
     fn default_pattern_transformation() {
         let pattern = test_pattern();
-        assert_eq!(pattern.transform, identity_matrix());
+        assert_eq!(pattern.transform(), identity_matrix());
     }
 
     #[test]
     fn assigning_pattern_transformation() {
         let pattern = stripe_pattern(WHITE, BLACK)
             .transform(transform::translation(1, 2, 3))
-            .build();
-        assert_eq!(pattern.transform, transform::translation(1, 2, 3));
+            .build()
+            .unwrap();
+        assert_eq!(pattern.transform(), transform::translation(1, 2, 3));
+    }
+
+    #[test]
+    fn pattern_caches_its_inverse_transform() {
+        let pattern = stripe_pattern(WHITE, BLACK)
+            .transform(transform::scaling(2, 4, 8))
+            .build()
+            .unwrap();
+        assert_eq!(
+            pattern.inverse_transform(),
+            transform::scaling(0.5, 0.25, 0.125)
+        );
+        assert_eq!(test_pattern().inverse_transform(), identity_matrix());
+    }
+
+    #[test]
+    fn non_invertible_pattern_transform_is_an_error() {
+        let singular = transform::scaling(1, 0, 1);
+        assert!(
+            stripe_pattern(WHITE, BLACK)
+                .transform(singular)
+                .build()
+                .is_err()
+        );
+        assert!(Pattern::new(singular, |_| WHITE).is_err());
+        let e = checkers_pattern(WHITE, BLACK)
+            .transform(singular)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "pattern transform is not invertible");
     }
 
     #[test]
@@ -193,10 +325,8 @@ mod tests {
     #[test]
     fn pattern_with_pattern_transformation() {
         let shape = sphere().build();
-        let pattern = Pattern {
-            transform: transform::scaling(2, 2, 2),
-            point_to_color: Arc::new(|p| color(p.x(), p.y(), p.z())),
-        };
+        let pattern =
+            Pattern::new(transform::scaling(2, 2, 2), |p| color(p.x(), p.y(), p.z())).unwrap();
         let c = pattern.pattern_at_shape(&shape, point(2, 3, 4));
         assert_eq!(c, color(1, 1.5, 2));
     }
@@ -204,17 +334,17 @@ mod tests {
     #[test]
     fn pattern_with_both_object_and_pattern_transformation() {
         let shape = sphere().transform(transform::scaling(2, 2, 2)).build();
-        let pattern = Pattern {
-            transform: transform::translation(0.5, 1, 1.5),
-            point_to_color: Arc::new(|p| color(p.x(), p.y(), p.z())),
-        };
+        let pattern = Pattern::new(transform::translation(0.5, 1, 1.5), |p| {
+            color(p.x(), p.y(), p.z())
+        })
+        .unwrap();
         let c = pattern.pattern_at_shape(&shape, point(2.5, 3, 3.5));
         assert_eq!(c, color(0.75, 0.5, 0.25));
     }
 
     #[test]
     fn gradient_linearly_interpolates_between_colors() {
-        let pattern = gradient_pattern(WHITE, BLACK).build();
+        let pattern = gradient_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(
             pattern.pattern_at(point(0.25, 0, 0)),
@@ -229,7 +359,7 @@ mod tests {
 
     #[test]
     fn ring_extends_in_both_x_and_z() {
-        let pattern = ring_pattern(WHITE, BLACK).build();
+        let pattern = ring_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(1, 0, 0)), BLACK);
         assert_eq!(pattern.pattern_at(point(0, 0, 1)), BLACK);
@@ -238,7 +368,7 @@ mod tests {
 
     #[test]
     fn checkers_repeat_in_x() {
-        let pattern = checkers_pattern(WHITE, BLACK).build();
+        let pattern = checkers_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0.99, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(1.01, 0, 0)), BLACK);
@@ -246,7 +376,7 @@ mod tests {
 
     #[test]
     fn checkers_repeat_in_y() {
-        let pattern = checkers_pattern(WHITE, BLACK).build();
+        let pattern = checkers_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 0.99, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 1.01, 0)), BLACK);
@@ -254,9 +384,97 @@ mod tests {
 
     #[test]
     fn checkers_repeat_in_z() {
-        let pattern = checkers_pattern(WHITE, BLACK).build();
+        let pattern = checkers_pattern(WHITE, BLACK).build().unwrap();
         assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 0, 0.99)), WHITE);
         assert_eq!(pattern.pattern_at(point(0, 0, 1.01)), BLACK);
     }
+
+    #[test]
+    fn colors_convert_to_solid_patterns() {
+        let pattern = Pattern::from(color(0.2, 0.4, 0.6));
+        assert_eq!(pattern.transform(), identity_matrix());
+        assert_eq!(pattern.pattern_at(point(3, -7, 11)), color(0.2, 0.4, 0.6));
+        assert_eq!(solid_pattern(WHITE).pattern_at(point(0, 0, 0)), WHITE);
+    }
+
+    #[test]
+    fn stripes_of_nested_patterns() {
+        let red = color(1, 0, 0);
+        let pattern = stripe_pattern(checkers_pattern(WHITE, BLACK).build().unwrap(), red)
+            .build()
+            .unwrap();
+        assert_eq!(pattern.pattern_at(point(0.5, 0.5, 0.5)), WHITE);
+        assert_eq!(pattern.pattern_at(point(0.5, 1.5, 0.5)), BLACK);
+        assert_eq!(pattern.pattern_at(point(1.5, 0.5, 0.5)), red);
+        assert_eq!(pattern.pattern_at(point(1.5, 1.5, 0.5)), red);
+    }
+
+    #[test]
+    fn nested_patterns_keep_their_own_transform() {
+        let inner = stripe_pattern(WHITE, BLACK)
+            .transform(transform::scaling(0.25, 1, 1))
+            .build()
+            .unwrap();
+        let pattern = checkers_pattern(inner, color(1, 0, 0))
+            .transform(transform::scaling(2, 2, 2))
+            .build()
+            .unwrap();
+        let shape = sphere().build();
+        assert_eq!(pattern.pattern_at_shape(&shape, point(0.1, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at_shape(&shape, point(0.6, 0, 0)), BLACK);
+        assert_eq!(
+            pattern.pattern_at_shape(&shape, point(2.1, 0, 0)),
+            color(1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn gradients_of_nested_patterns() {
+        let pattern = gradient_pattern(test_pattern(), BLACK).build().unwrap();
+        assert_eq!(
+            pattern.pattern_at(point(0.5, 0.2, 0.4)),
+            color(0.25, 0.1, 0.2)
+        );
+    }
+
+    #[test]
+    fn blended_pattern_averages_both_patterns() {
+        let a = stripe_pattern(WHITE, BLACK).build().unwrap();
+        let b = stripe_pattern(WHITE, BLACK)
+            .transform(transform::rotation_y(std::f32::consts::FRAC_PI_2))
+            .build()
+            .unwrap();
+        let pattern = blended_pattern(a, b).build().unwrap();
+        assert_eq!(pattern.pattern_at(point(0.5, 0, -0.5)), WHITE);
+        assert_eq!(
+            pattern.pattern_at(point(1.5, 0, -0.5)),
+            color(0.5, 0.5, 0.5)
+        );
+        assert_eq!(pattern.pattern_at(point(0.5, 0, 0.5)), color(0.5, 0.5, 0.5));
+        assert_eq!(pattern.pattern_at(point(1.5, 0, 0.5)), BLACK);
+    }
+
+    #[test]
+    fn radial_gradient_blends_with_distance_from_the_origin() {
+        let pattern = radial_gradient_pattern(WHITE, BLACK).build().unwrap();
+        assert_eq!(pattern.pattern_at(point(0, 0, 0)), WHITE);
+        assert_eq!(
+            pattern.pattern_at(point(0, 0.25, 0)),
+            color(0.75, 0.75, 0.75)
+        );
+        assert_eq!(pattern.pattern_at(point(0.3, 0, 0.4)), color(0.5, 0.5, 0.5));
+        assert_eq!(pattern.pattern_at(point(0, -1, 0)), WHITE);
+    }
+
+    #[test]
+    fn ring_gradient_blends_with_distance_from_the_y_axis() {
+        let pattern = ring_gradient_pattern(WHITE, BLACK).build().unwrap();
+        assert_eq!(pattern.pattern_at(point(0, 5, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point(0.3, 7, 0.4)), color(0.5, 0.5, 0.5));
+        assert_eq!(
+            pattern.pattern_at(point(0, 0, 1.25)),
+            color(0.75, 0.75, 0.75)
+        );
+    }
 }
@@ -0,0 +1,261 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, ensure};
+use bon::builder;
+
+use super::{Pattern, UvPattern, cube_map};
+use crate::{Canvas, Color, Matrix4, color, identity_matrix, srgb_to_linear};
+
+/// How an image texture is sampled between its pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Uses the pixel the coordinates fall in.
+    #[default]
+    Nearest,
+    /// Blends the four pixels nearest the coordinates.
+    Bilinear,
+}
+
+/// What an image texture shows outside `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureWrap {
+    /// Tiles the image.
+    #[default]
+    Repeat,
+    /// Extends the pixels along the image's edges.
+    Clamp,
+}
+
+impl TextureWrap {
+    fn index(self, i: isize, size: usize) -> usize {
+        let size = size.cast_signed();
+        let i = match self {
+            TextureWrap::Repeat => i.rem_euclid(size),
+            TextureWrap::Clamp => i.clamp(0, size - 1),
+        };
+        i.cast_unsigned()
+    }
+}
+
+/// A UV pattern that samples `image`, with `u` running left to right and `v`
+/// bottom to top. Images written by the renderer hold linear values, but
+/// ordinary photographs and paintings are sRGB encoded, so set `srgb` to
+/// decode them.
+///
+/// # Errors
+/// Returns an error if the image is empty.
+#[builder(finish_fn = build)]
+pub fn uv_image(
+    #[builder(start_fn)] image: Canvas,
+    #[builder(default)] filter: TextureFilter,
+    #[builder(default)] wrap: TextureWrap,
+    #[builder(default)] srgb: bool,
+) -> Result<UvPattern> {
+    ensure!(
+        image.width > 0 && image.height > 0,
+        "texture image is empty"
+    );
+
+    #[allow(clippy::cast_precision_loss)]
+    let (width, height) = (image.width as f32, image.height as f32);
+    let texel = move |x: isize, y: isize| {
+        let x = wrap.index(x, image.width);
+        let y = wrap.index(y, image.height);
+        let pixel = image.pixel_at(x, y).unwrap_or_default();
+        if srgb {
+            color(
+                srgb_to_linear(pixel.red()),
+                srgb_to_linear(pixel.green()),
+                srgb_to_linear(pixel.blue()),
+            )
+        } else {
+            pixel
+        }
+    };
+
+    Ok(UvPattern::new(move |u, v| {
+        let x = u * width;
+        let y = (1.0 - v) * height;
+        #[allow(clippy::cast_possible_truncation)]
+        match filter {
+            TextureFilter::Nearest => texel(x.floor() as isize, y.floor() as isize),
+            TextureFilter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = lerp(texel(x0, y0), texel(x0 + 1, y0), fx);
+                let bottom = lerp(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), fx);
+                lerp(top, bottom, fy)
+            }
+        }
+    }))
+}
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    a + (b - a) * t
+}
+
+/// Builds a cube map, such as a skybox, from six images in `dir` named
+/// after the axis each face looks along: `negx` (left), `posx` (right),
+/// `posz` (front), `negz` (back), `posy` (up) and `negy` (down). Each name
+/// may have any extension that [`Canvas::open`] understands. The faces
+/// clamp at their edges so that they meet without seams.
+///
+/// # Errors
+/// Returns an error if a face is missing or cannot be read, or if
+/// `transform` is not invertible.
+#[builder(finish_fn = load)]
+pub fn cube_map_images(
+    #[builder(start_fn)] dir: &Path,
+    #[builder(default)] filter: TextureFilter,
+    #[builder(default)] srgb: bool,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    let face = |name: &str| -> Result<UvPattern> {
+        let path = ["ppm", "png", "hdr", "pfm", "exr"]
+            .into_iter()
+            .map(|extension| dir.join(name).with_extension(extension))
+            .find(|path| path.exists())
+            .with_context(|| format!("no `{name}` image in {}", dir.display()))?;
+        let image = Canvas::open(&path)?;
+        uv_image(image)
+            .filter(filter)
+            .wrap(TextureWrap::Clamp)
+            .srgb(srgb)
+            .build()
+            .with_context(|| format!("failed to use {} as a face", path.display()))
+    };
+
+    cube_map()
+        .left(face("negx")?)
+        .right(face("posx")?)
+        .front(face("posz")?)
+        .back(face("negz")?)
+        .up(face("posy")?)
+        .down(face("negy")?)
+        .transform(transform)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, canvas_with_pixels, point};
+
+    /// A 2x2 image with a different grey level in each pixel, in row order
+    /// from the top left.
+    fn quad() -> Canvas {
+        let pixels = [0.0, 0.25, 0.5, 1.0].map(|c| color(c, c, c)).to_vec();
+        canvas_with_pixels(2, 2, pixels)
+    }
+
+    fn grey(c: Color) -> f32 {
+        c.red()
+    }
+
+    #[test]
+    fn nearest_sampling_maps_uv_to_pixels() {
+        let pattern = uv_image(quad()).build().unwrap();
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.25, 0.75)), 0.0);
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.75, 0.75)), 0.25);
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.25, 0.25)), 0.5);
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.75, 0.25)), 1.0);
+    }
+
+    #[test]
+    fn wrapping_and_clamping_outside_the_image() {
+        let repeat = uv_image(quad()).build().unwrap();
+        assert_relative_eq!(grey(repeat.uv_pattern_at(1.25, 0.75)), 0.0);
+        assert_relative_eq!(grey(repeat.uv_pattern_at(-0.25, 1.75)), 0.25);
+
+        let clamp = uv_image(quad()).wrap(TextureWrap::Clamp).build().unwrap();
+        assert_relative_eq!(grey(clamp.uv_pattern_at(1.25, 0.75)), 0.25);
+        assert_relative_eq!(grey(clamp.uv_pattern_at(1.0, 0.0)), 1.0);
+        assert_relative_eq!(grey(clamp.uv_pattern_at(-3.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn bilinear_sampling_blends_neighbouring_pixels() {
+        let pattern = uv_image(quad())
+            .filter(TextureFilter::Bilinear)
+            .wrap(TextureWrap::Clamp)
+            .build()
+            .unwrap();
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.25, 0.75)), 0.0);
+        assert_relative_eq!(
+            grey(pattern.uv_pattern_at(0.5, 0.75)),
+            0.125,
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(
+            grey(pattern.uv_pattern_at(0.5, 0.5)),
+            0.4375,
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.0, 0.0)), 0.5);
+
+        let repeat = uv_image(quad())
+            .filter(TextureFilter::Bilinear)
+            .build()
+            .unwrap();
+        assert_relative_eq!(
+            grey(repeat.uv_pattern_at(0.0, 0.75)),
+            0.125,
+            epsilon = EPSILON
+        );
+    }
+
+    #[test]
+    fn decoding_srgb_images() {
+        let pattern = uv_image(quad()).srgb(true).build().unwrap();
+        assert_relative_eq!(
+            grey(pattern.uv_pattern_at(0.25, 0.25)),
+            srgb_to_linear(0.5),
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(grey(pattern.uv_pattern_at(0.75, 0.25)), 1.0);
+    }
+
+    #[test]
+    fn empty_images_are_an_error() {
+        assert!(uv_image(canvas_with_pixels(0, 0, vec![])).build().is_err());
+    }
+
+    #[test]
+    fn loading_a_cube_map_from_images() {
+        let dir = std::env::temp_dir().join(format!("cube-map-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let faces = [
+            ("negx", color(1, 0, 0)),
+            ("posx", color(0, 1, 0)),
+            ("posz", color(0, 0, 1)),
+            ("negz", color(1, 1, 0)),
+            ("posy", color(0, 1, 1)),
+            ("negy", color(1, 0, 1)),
+        ];
+        for (name, c) in faces {
+            let image = canvas_with_pixels(1, 1, vec![c]);
+            let file = File::create(dir.join(format!("{name}.pfm"))).unwrap();
+            image.write_pfm(file).unwrap();
+        }
+
+        let pattern = cube_map_images(&dir).load();
+        fs::remove_file(dir.join("negy.pfm")).unwrap();
+        let missing = cube_map_images(&dir).load();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let pattern = pattern.unwrap();
+        assert_eq!(pattern.pattern_at(point(-1, 0, 0)), color(1, 0, 0));
+        assert_eq!(pattern.pattern_at(point(1, 0.5, 0)), color(0, 1, 0));
+        assert_eq!(pattern.pattern_at(point(0, 0, 1)), color(0, 0, 1));
+        assert_eq!(pattern.pattern_at(point(0, 0, -1)), color(1, 1, 0));
+        assert_eq!(pattern.pattern_at(point(0, 1, 0)), color(0, 1, 1));
+        assert_eq!(pattern.pattern_at(point(0, -1, 0)), color(1, 0, 1));
+        assert!(missing.is_err());
+    }
+}
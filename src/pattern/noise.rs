@@ -0,0 +1,187 @@
+use std::f32::consts::PI;
+
+use anyhow::Result;
+use bon::builder;
+
+use super::{Pattern, blend};
+use crate::{Matrix4, Noise, identity_matrix};
+
+/// Veined stripes along `x` that blend from `a` to `b` and back, wavering
+/// with `turbulence` times the noise's turbulence.
+#[builder(finish_fn = build)]
+pub fn marble_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+    #[builder(default)] seed: u64,
+    #[builder(default = 4)] octaves: u32,
+    #[builder(default = 5.0)] turbulence: f32,
+) -> Result<Pattern> {
+    let noise = Noise::new(seed);
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        let phase = p.x() + turbulence * noise.turbulence(p, octaves);
+        blend(a(p), b(p), 0.5 - 0.5 * (phase * PI).cos())
+    })
+}
+
+/// Growth rings around the `y` axis, each blending from `a` to `b`, with
+/// their radius displaced by `turbulence` times fractal noise.
+#[builder(finish_fn = build)]
+pub fn wood_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+    #[builder(default)] seed: u64,
+    #[builder(default = 2)] octaves: u32,
+    #[builder(default = 0.2)] turbulence: f32,
+) -> Result<Pattern> {
+    let noise = Noise::new(seed);
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        let radius = (p.x().powi(2) + p.z().powi(2)).sqrt();
+        let ring = radius + turbulence * noise.fbm(p, octaves);
+        blend(a(p), b(p), ring - ring.floor())
+    })
+}
+
+/// Soft billows that blend from `a` where fractal noise is lowest to `b`
+/// where it is highest.
+#[builder(finish_fn = build)]
+pub fn clouds_pattern(
+    #[builder(start_fn, into)] a: Pattern,
+    #[builder(start_fn, into)] b: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+    #[builder(default)] seed: u64,
+    #[builder(default = 5)] octaves: u32,
+) -> Result<Pattern> {
+    let noise = Noise::new(seed);
+    let (a, b) = (a.nested(), b.nested());
+    Pattern::new(transform, move |p| {
+        let t = 0.5 + 0.5 * noise.fbm(p, octaves);
+        blend(a(p), b(p), t.clamp(0.0, 1.0))
+    })
+}
+
+/// Jitters each point by up to `scale` in every direction with fractal
+/// noise before looking it up in `pattern`, roughening its edges. The
+/// jitter happens in the perturbed pattern's space; `pattern`'s own
+/// transform still applies afterwards.
+#[builder(finish_fn = build)]
+pub fn perturbed(
+    #[builder(start_fn)] pattern: Pattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+    #[builder(default = 0.2)] scale: f32,
+    #[builder(default)] seed: u64,
+    #[builder(default = 1)] octaves: u32,
+) -> Result<Pattern> {
+    let noise = Noise::new(seed);
+    let pattern = pattern.nested();
+    Pattern::new(transform, move |p| {
+        pattern(p + noise.vector_at(p, octaves) * scale)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        Color, EPSILON,
+        color::{BLACK, WHITE},
+        pattern::{stripe_pattern, test_pattern},
+        point, transform,
+    };
+
+    fn grey(c: Color) -> f32 {
+        c.red()
+    }
+
+    #[test]
+    fn calm_marble_is_a_smooth_stripe() {
+        let pattern = marble_pattern(BLACK, WHITE)
+            .turbulence(0.0)
+            .build()
+            .unwrap();
+        assert_relative_eq!(grey(pattern.pattern_at(point(0, 3, -2))), 0.0);
+        assert_relative_eq!(
+            grey(pattern.pattern_at(point(0.5, 0, 0))),
+            0.5,
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(grey(pattern.pattern_at(point(1, 7, 7))), 1.0);
+        assert_relative_eq!(grey(pattern.pattern_at(point(2, 0, 0))), 0.0);
+    }
+
+    #[test]
+    fn turbulence_makes_marble_waver() {
+        let calm = marble_pattern(BLACK, WHITE)
+            .turbulence(0.0)
+            .build()
+            .unwrap();
+        let veined = marble_pattern(BLACK, WHITE).build().unwrap();
+        let p = point(0.3, 0.7, 1.9);
+        assert!((grey(calm.pattern_at(p)) - grey(veined.pattern_at(p))).abs() > EPSILON);
+
+        let reseeded = marble_pattern(BLACK, WHITE).seed(9).build().unwrap();
+        assert!((grey(veined.pattern_at(p)) - grey(reseeded.pattern_at(p))).abs() > EPSILON);
+    }
+
+    #[test]
+    fn calm_wood_is_concentric_rings() {
+        let pattern = wood_pattern(BLACK, WHITE).turbulence(0.0).build().unwrap();
+        assert_relative_eq!(grey(pattern.pattern_at(point(0, 5, 0))), 0.0);
+        assert_relative_eq!(
+            grey(pattern.pattern_at(point(0.25, 0, 0))),
+            0.25,
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(
+            grey(pattern.pattern_at(point(0, -3, 1.75))),
+            0.75,
+            epsilon = EPSILON
+        );
+    }
+
+    #[test]
+    fn clouds_stay_between_their_colors() {
+        let pattern = clouds_pattern(BLACK, WHITE).build().unwrap();
+        assert_relative_eq!(grey(pattern.pattern_at(point(2, 0, -1))), 0.5);
+        let values: Vec<f32> = (0..200u8)
+            .map(|i| grey(pattern.pattern_at(point(f32::from(i) * 0.113, 0.4, 0.9))))
+            .collect();
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        assert!(values.iter().any(|&v| v < 0.4));
+        assert!(values.iter().any(|&v| v > 0.6));
+    }
+
+    #[test]
+    fn perturbing_jitters_the_inner_pattern() {
+        let still = perturbed(test_pattern()).scale(0.0).build().unwrap();
+        let p = point(0.3, 0.6, 0.9);
+        assert_eq!(still.pattern_at(p), test_pattern().pattern_at(p));
+
+        let jittered = perturbed(test_pattern()).build().unwrap();
+        let c = jittered.pattern_at(p);
+        assert!(c != test_pattern().pattern_at(p));
+        assert!((c.red() - p.x()).abs() <= 0.2);
+        assert!((c.green() - p.y()).abs() <= 0.2);
+        assert!((c.blue() - p.z()).abs() <= 0.2);
+    }
+
+    #[test]
+    fn perturbing_keeps_the_inner_transform() {
+        let stripes = stripe_pattern(WHITE, BLACK)
+            .transform(transform::scaling(10, 1, 1))
+            .build()
+            .unwrap();
+        let pattern = perturbed(stripes)
+            .transform(transform::translation(1, 0, 0))
+            .build()
+            .unwrap();
+        assert_eq!(pattern.transform(), transform::translation(1, 0, 0));
+        assert_eq!(pattern.pattern_at(point(5, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point(15, 0, 0)), BLACK);
+    }
+}
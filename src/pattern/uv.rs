@@ -0,0 +1,400 @@
+use std::{
+    f32::consts::{PI, TAU},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use bon::builder;
+
+use super::Pattern;
+use crate::{Color, Matrix4, Point, identity_matrix};
+
+/// A two-dimensional pattern over texture coordinates `u` and `v`, each
+/// normally in `0.0..=1.0`, with `v` increasing upwards.
+#[derive(Clone)]
+pub struct UvPattern {
+    uv_to_color: Arc<dyn Fn(f32, f32) -> Color + Send + Sync>,
+}
+
+impl UvPattern {
+    pub fn new(uv_to_color: impl Fn(f32, f32) -> Color + Send + Sync + 'static) -> Self {
+        UvPattern {
+            uv_to_color: Arc::new(uv_to_color),
+        }
+    }
+
+    #[must_use]
+    pub fn uv_pattern_at(&self, u: f32, v: f32) -> Color {
+        (self.uv_to_color)(u, v)
+    }
+}
+
+/// A checkerboard with `width` squares across and `height` squares up.
+#[must_use]
+pub fn uv_checkers(width: f32, height: f32, a: Color, b: Color) -> UvPattern {
+    UvPattern::new(move |u, v| {
+        #[allow(clippy::cast_possible_truncation)]
+        let value = ((u * width).floor() + (v * height).floor()) as i32;
+        if value % 2 == 0 { a } else { b }
+    })
+}
+
+/// A solid color with a differently coloured square in each corner, useful
+/// for checking how a mapping orients its textures.
+#[must_use]
+#[builder(finish_fn = build)]
+pub fn align_check(
+    #[builder(start_fn)] main: Color,
+    upper_left: Color,
+    upper_right: Color,
+    lower_left: Color,
+    lower_right: Color,
+) -> UvPattern {
+    UvPattern::new(move |u, v| {
+        if v > 0.8 {
+            if u < 0.2 {
+                return upper_left;
+            }
+            if u > 0.8 {
+                return upper_right;
+            }
+        } else if v < 0.2 {
+            if u < 0.2 {
+                return lower_left;
+            }
+            if u > 0.8 {
+                return lower_right;
+            }
+        }
+        main
+    })
+}
+
+/// Projects a point in pattern space onto texture coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UvMapping {
+    /// Wraps around a unit sphere by longitude and latitude.
+    #[default]
+    Spherical,
+    /// Tiles the xz plane, repeating every unit.
+    Planar,
+    /// Wraps around the y axis, repeating every unit of height.
+    Cylindrical,
+    /// Puts a copy of the texture on each face of a `-1..=1` cube.
+    Cube,
+}
+
+impl UvMapping {
+    #[must_use]
+    pub fn map(self, p: Point) -> (f32, f32) {
+        match self {
+            UvMapping::Spherical => {
+                let radius = (p.x().powi(2) + p.y().powi(2) + p.z().powi(2)).sqrt();
+                let phi = (p.y() / radius).acos();
+                (longitude(p), 1.0 - phi / PI)
+            }
+            UvMapping::Planar => (p.x().rem_euclid(1.0), p.z().rem_euclid(1.0)),
+            UvMapping::Cylindrical => (longitude(p), p.y().rem_euclid(1.0)),
+            UvMapping::Cube => CubeFace::from_point(p).map(p),
+        }
+    }
+}
+
+/// Returns the angle around the y axis as a fraction of a turn, starting
+/// from `-z` and increasing anticlockwise when viewed from above.
+fn longitude(p: Point) -> f32 {
+    let theta = p.x().atan2(p.z());
+    1.0 - (theta / TAU + 0.5)
+}
+
+/// A face of the axis-aligned cube used by [`UvMapping::Cube`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+impl CubeFace {
+    /// Returns the face whose axis `p` lies furthest along.
+    #[must_use]
+    pub fn from_point(p: Point) -> CubeFace {
+        let coord = p.x().abs().max(p.y().abs()).max(p.z().abs());
+        if coord == p.x() {
+            CubeFace::Right
+        } else if coord == -p.x() {
+            CubeFace::Left
+        } else if coord == p.y() {
+            CubeFace::Up
+        } else if coord == -p.y() {
+            CubeFace::Down
+        } else if coord == p.z() {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+
+    /// Returns the texture coordinates of `p` on this face, oriented as if
+    /// looking at the face from outside the cube.
+    #[must_use]
+    pub fn map(self, p: Point) -> (f32, f32) {
+        let wrap = |c: f32| (c + 1.0).rem_euclid(2.0) / 2.0;
+        match self {
+            CubeFace::Front => (wrap(p.x()), wrap(p.y())),
+            CubeFace::Back => (wrap(-p.x()), wrap(p.y())),
+            CubeFace::Left => (wrap(p.z()), wrap(p.y())),
+            CubeFace::Right => (wrap(-p.z()), wrap(p.y())),
+            CubeFace::Up => (wrap(p.x()), wrap(-p.z())),
+            CubeFace::Down => (wrap(p.x()), wrap(p.z())),
+        }
+    }
+}
+
+/// Applies a two-dimensional pattern to a surface through a UV mapping.
+#[builder(finish_fn = build)]
+pub fn texture_map(
+    #[builder(start_fn)] uv_pattern: UvPattern,
+    #[builder(start_fn)] mapping: UvMapping,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    Pattern::new(transform, move |p| {
+        let (u, v) = mapping.map(p);
+        uv_pattern.uv_pattern_at(u, v)
+    })
+}
+
+/// Wraps a cube with a different two-dimensional pattern on each face.
+#[builder(finish_fn = build)]
+pub fn cube_map(
+    left: UvPattern,
+    right: UvPattern,
+    front: UvPattern,
+    back: UvPattern,
+    up: UvPattern,
+    down: UvPattern,
+    #[builder(default = identity_matrix())] transform: Matrix4,
+) -> Result<Pattern> {
+    Pattern::new(transform, move |p| {
+        let face = CubeFace::from_point(p);
+        let pattern = match face {
+            CubeFace::Left => &left,
+            CubeFace::Right => &right,
+            CubeFace::Front => &front,
+            CubeFace::Back => &back,
+            CubeFace::Up => &up,
+            CubeFace::Down => &down,
+        };
+        let (u, v) = face.map(p);
+        pattern.uv_pattern_at(u, v)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        EPSILON, color,
+        color::{BLACK, WHITE},
+        point,
+    };
+
+    fn assert_uv(actual: (f32, f32), expected: (f32, f32)) {
+        assert_relative_eq!(actual.0, expected.0, epsilon = EPSILON);
+        assert_relative_eq!(actual.1, expected.1, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn checker_pattern_in_2d() {
+        let checkers = uv_checkers(2.0, 2.0, BLACK, WHITE);
+        assert_eq!(checkers.uv_pattern_at(0.0, 0.0), BLACK);
+        assert_eq!(checkers.uv_pattern_at(0.5, 0.0), WHITE);
+        assert_eq!(checkers.uv_pattern_at(0.0, 0.5), WHITE);
+        assert_eq!(checkers.uv_pattern_at(0.5, 0.5), BLACK);
+        assert_eq!(checkers.uv_pattern_at(1.0, 1.0), BLACK);
+    }
+
+    #[test]
+    fn spherical_mapping_on_3d_point() {
+        let cases = [
+            (point(0, 0, -1), (0.0, 0.5)),
+            (point(1, 0, 0), (0.25, 0.5)),
+            (point(0, 0, 1), (0.5, 0.5)),
+            (point(-1, 0, 0), (0.75, 0.5)),
+            (point(0, 1, 0), (0.5, 1.0)),
+            (point(0, -1, 0), (0.5, 0.0)),
+            (point(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0), (0.25, 0.75)),
+        ];
+        for (p, uv) in cases {
+            assert_uv(UvMapping::Spherical.map(p), uv);
+        }
+    }
+
+    #[test]
+    fn texture_mapping_pattern_with_spherical_map() {
+        let pattern = texture_map(uv_checkers(16.0, 8.0, BLACK, WHITE), UvMapping::Spherical)
+            .build()
+            .unwrap();
+        let cases = [
+            (point(0.4315, 0.4670, 0.7719), WHITE),
+            (point(-0.9654, 0.2552, -0.0534), BLACK),
+            (point(0.1039, 0.7090, 0.6975), WHITE),
+            (point(-0.4986, -0.7856, -0.3663), BLACK),
+            (point(-0.0317, -0.9395, 0.3411), BLACK),
+            (point(0.4809, -0.7721, 0.4154), BLACK),
+            (point(0.0285, -0.9612, -0.2745), BLACK),
+            (point(-0.5734, -0.2162, -0.7903), WHITE),
+            (point(0.7688, -0.1470, 0.6223), BLACK),
+            (point(-0.7652, 0.2175, 0.6060), BLACK),
+        ];
+        for (p, expected) in cases {
+            assert_eq!(pattern.pattern_at(p), expected);
+        }
+    }
+
+    #[test]
+    fn planar_mapping_on_3d_point() {
+        let cases = [
+            (point(0.25, 0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0, -0.25), (0.25, 0.75)),
+            (point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (point(1.25, 0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0, -1.75), (0.25, 0.25)),
+            (point(1, 0, -1), (0.0, 0.0)),
+            (point(0, 0, 0), (0.0, 0.0)),
+        ];
+        for (p, uv) in cases {
+            assert_uv(UvMapping::Planar.map(p), uv);
+        }
+    }
+
+    #[test]
+    fn cylindrical_mapping_on_3d_point() {
+        let s = FRAC_1_SQRT_2;
+        let cases = [
+            (point(0, 0, -1), (0.0, 0.0)),
+            (point(0, 0.5, -1), (0.0, 0.5)),
+            (point(0, 1, -1), (0.0, 0.0)),
+            (point(s, 0.5, -s), (0.125, 0.5)),
+            (point(1, 0.5, 0), (0.25, 0.5)),
+            (point(s, 0.5, s), (0.375, 0.5)),
+            (point(0, -0.25, 1), (0.5, 0.75)),
+            (point(-s, 0.5, s), (0.625, 0.5)),
+            (point(-1, 1.25, 0), (0.75, 0.25)),
+            (point(-s, 0.5, -s), (0.875, 0.5)),
+        ];
+        for (p, uv) in cases {
+            assert_uv(UvMapping::Cylindrical.map(p), uv);
+        }
+    }
+
+    #[test]
+    fn layout_of_align_check_pattern() {
+        let main = color(1, 1, 1);
+        let ul = color(1, 0, 0);
+        let ur = color(1, 1, 0);
+        let bl = color(0, 1, 0);
+        let br = color(0, 1, 1);
+        let pattern = align_check(main)
+            .upper_left(ul)
+            .upper_right(ur)
+            .lower_left(bl)
+            .lower_right(br)
+            .build();
+        assert_eq!(pattern.uv_pattern_at(0.5, 0.5), main);
+        assert_eq!(pattern.uv_pattern_at(0.1, 0.9), ul);
+        assert_eq!(pattern.uv_pattern_at(0.9, 0.9), ur);
+        assert_eq!(pattern.uv_pattern_at(0.1, 0.1), bl);
+        assert_eq!(pattern.uv_pattern_at(0.9, 0.1), br);
+    }
+
+    #[test]
+    fn identifying_face_of_cube_from_point() {
+        let cases = [
+            (point(-1, 0.5, -0.25), CubeFace::Left),
+            (point(1.1, -0.75, 0.8), CubeFace::Right),
+            (point(0.1, 0.6, 0.9), CubeFace::Front),
+            (point(-0.7, 0, -2), CubeFace::Back),
+            (point(0.5, 1, 0.9), CubeFace::Up),
+            (point(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+        for (p, face) in cases {
+            assert_eq!(CubeFace::from_point(p), face);
+        }
+    }
+
+    #[test]
+    fn uv_mapping_each_face_of_cube() {
+        let cases = [
+            (CubeFace::Front, point(-0.5, 0.5, 1), point(0.5, -0.5, 1)),
+            (CubeFace::Back, point(0.5, 0.5, -1), point(-0.5, -0.5, -1)),
+            (CubeFace::Left, point(-1, 0.5, -0.5), point(-1, -0.5, 0.5)),
+            (CubeFace::Right, point(1, 0.5, 0.5), point(1, -0.5, -0.5)),
+            (CubeFace::Up, point(-0.5, 1, -0.5), point(0.5, 1, 0.5)),
+            (CubeFace::Down, point(-0.5, -1, 0.5), point(0.5, -1, -0.5)),
+        ];
+        for (face, upper_left, lower_right) in cases {
+            assert_uv(face.map(upper_left), (0.25, 0.75));
+            assert_uv(face.map(lower_right), (0.75, 0.25));
+            assert_uv(UvMapping::Cube.map(upper_left), (0.25, 0.75));
+        }
+    }
+
+    #[test]
+    fn finding_colors_on_mapped_cube() {
+        let red = color(1, 0, 0);
+        let yellow = color(1, 1, 0);
+        let brown = color(1, 0.5, 0);
+        let green = color(0, 1, 0);
+        let cyan = color(0, 1, 1);
+        let blue = color(0, 0, 1);
+        let purple = color(1, 0, 1);
+        let white = color(1, 1, 1);
+        let face = |main, ul, ur, bl, br| {
+            align_check(main)
+                .upper_left(ul)
+                .upper_right(ur)
+                .lower_left(bl)
+                .lower_right(br)
+                .build()
+        };
+        let pattern = cube_map()
+            .left(face(yellow, cyan, red, blue, brown))
+            .front(face(cyan, red, yellow, brown, green))
+            .right(face(red, yellow, purple, green, white))
+            .back(face(green, purple, cyan, white, blue))
+            .up(face(brown, cyan, purple, red, yellow))
+            .down(face(purple, brown, green, blue, white))
+            .build()
+            .unwrap();
+
+        let cases = [
+            (point(-1, 0, 0), yellow),
+            (point(-1, 0.9, -0.9), cyan),
+            (point(-1, 0.9, 0.9), red),
+            (point(-1, -0.9, -0.9), blue),
+            (point(-1, -0.9, 0.9), brown),
+            (point(0, 0, 1), cyan),
+            (point(-0.9, 0.9, 1), red),
+            (point(0.9, -0.9, 1), green),
+            (point(1, 0, 0), red),
+            (point(1, 0.9, 0.9), yellow),
+            (point(0, 0, -1), green),
+            (point(0.9, 0.9, -1), purple),
+            (point(0, 1, 0), brown),
+            (point(-0.9, 1, -0.9), cyan),
+            (point(0, -1, 0), purple),
+            (point(0.9, -1, -0.9), white),
+        ];
+        for (p, expected) in cases {
+            assert_eq!(pattern.pattern_at(p), expected, "at {p:?}");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+/// Deterministic pseudo-random numbers derived from a seed.
+///
+/// Every sample is a pure function of its seed, so renders come out
+/// identical whether pixels are traced sequentially or in parallel.
+#[must_use]
+pub(crate) fn random_unit(seed: u64) -> f32 {
+    // SplitMix64 finaliser
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    // Keep the top 24 bits so the result is exactly representable in f32
+    // and always strictly less than one.
+    #[allow(clippy::cast_precision_loss)]
+    let value = (z >> 40) as f32 / (1u64 << 24) as f32;
+    value
+}
+
+/// Combines several integers into a single seed for [`random_unit`].
+#[must_use]
+pub(crate) fn seed(parts: &[u64]) -> u64 {
+    parts.iter().fold(0xCBF2_9CE4_8422_2325, |acc, &part| {
+        (acc ^ part).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_unit_is_in_unit_interval() {
+        for i in 0..1000 {
+            let value = random_unit(i);
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn random_unit_is_deterministic() {
+        assert_eq!(random_unit(42).to_bits(), random_unit(42).to_bits());
+    }
+
+    #[test]
+    fn different_seeds_give_different_values() {
+        assert_ne!(
+            random_unit(seed(&[1, 2, 3])).to_bits(),
+            random_unit(seed(&[3, 2, 1])).to_bits()
+        );
+    }
+}
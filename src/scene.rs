@@ -0,0 +1,1317 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    Attenuation, BumpMap, Camera, Canvas, Color, Light, Material, Matrix4, NormalMap, ObjParser,
+    Point, Shape, Vector, World, area_light, camera, color, directional_light, identity_matrix,
+    material,
+    pattern::{
+        Pattern, TextureFilter, TextureWrap, UvMapping, UvPattern, align_check, blended_pattern,
+        checkers_pattern, clouds_pattern, cube_map, cube_map_images, gradient_pattern,
+        marble_pattern, perturbed, radial_gradient_pattern, ring_gradient_pattern, ring_pattern,
+        stripe_pattern, texture_map, uv_checkers, uv_image, wood_pattern,
+    },
+    point, point_light,
+    shape::{cone, cube, cylinder, group, plane, sphere},
+    spot_light, transform, vector,
+};
+
+mod yaml;
+
+use yaml::{Node, Value};
+
+/// A world and the camera to render it with, loaded from a scene file.
+///
+/// Scene files use the YAML layout from *The Ray Tracer Challenge*: a list
+/// of `add:` entries for the camera, lights and shapes (`sphere`, `plane`,
+/// `cube`, `cylinder`, `cone`, `group` and `obj`), and `define:` entries that
+/// name materials and transforms for reuse. A definition's `extend:` names
+/// another definition to start from. Transforms are lists of `[translate, x,
+/// y, z]`, `[scale, x, y, z]`, `[rotate-x, radians]` (and `-y`, `-z`),
+/// `[shear, xy, xz, yx, yz, zx, zy]` and defined names, applied in order.
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+}
+
+/// A problem in a scene file, with the 1-based line and column where it was
+/// found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SceneError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    /// Reads a scene file. OBJ files it refers to are found relative to the
+    /// directory containing it.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, or a [`SceneError`] if
+    /// its contents are not a valid scene.
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("failed to read scene {}", path.display()))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::from_yaml(&source, base_dir)
+            .with_context(|| format!("invalid scene {}", path.display()))
+    }
+
+    /// Builds a scene from YAML source, resolving OBJ files relative to
+    /// `base_dir`.
+    ///
+    /// # Errors
+    /// Returns an error describing the first problem found in the scene.
+    pub fn from_yaml(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let document = yaml::parse(source)?;
+        let mut loader = Loader {
+            base_dir: base_dir.to_path_buf(),
+            definitions: HashMap::new(),
+            camera: None,
+            lights: vec![],
+            objects: vec![],
+        };
+
+        let entries = match &document.value {
+            Value::Null => &[][..],
+            _ => document.as_sequence()?,
+        };
+        for entry in entries {
+            loader.entry(entry)?;
+        }
+
+        let camera = loader
+            .camera
+            .ok_or_else(|| document.error("scene has no camera"))?;
+        let mut world = World::builder()
+            .lights(loader.lights)
+            .objects(loader.objects)
+            .build();
+        world.build_bvh();
+
+        Ok(Scene { world, camera })
+    }
+}
+
+impl FromStr for Scene {
+    type Err = SceneError;
+
+    /// Parses a scene, resolving OBJ files relative to the working directory.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scene::from_yaml(s, Path::new(""))
+    }
+}
+
+enum Definition {
+    Material(Box<Material>),
+    Transform(Matrix4),
+}
+
+struct Loader {
+    base_dir: PathBuf,
+    definitions: HashMap<String, Definition>,
+    camera: Option<Camera>,
+    lights: Vec<Box<dyn Light>>,
+    objects: Vec<Shape>,
+}
+
+const SHAPE_KEYS: [&str; 3] = ["add", "material", "transform"];
+const FACES: [&str; 6] = ["left", "right", "front", "back", "up", "down"];
+
+impl Loader {
+    fn entry(&mut self, entry: &Node) -> Result<(), SceneError> {
+        if let Some(name) = entry.get("define") {
+            return self.define(entry, name);
+        }
+
+        let kind = entry
+            .get("add")
+            .ok_or_else(|| entry.error("expected `add` or `define`"))?;
+        match kind.as_str()? {
+            "camera" => {
+                if self.camera.is_some() {
+                    return Err(kind.error("scene has more than one camera"));
+                }
+                self.camera = Some(camera_from(entry)?);
+            }
+            "light" => self.lights.push(light_from(entry)?),
+            _ => {
+                let object = self.shape(entry, &material())?;
+                self.objects.push(object);
+            }
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, entry: &Node, name: &Node) -> Result<(), SceneError> {
+        check_keys(entry, &["define", "extend", "value"], "definition")?;
+        let value = required(entry, "value")?;
+        let base = entry
+            .get("extend")
+            .map(|base| self.definition(base))
+            .transpose()?;
+
+        let definition = match (&value.value, base) {
+            (Value::Mapping(_), None) => {
+                Definition::Material(Box::new(self.material(value, material())?))
+            }
+            (Value::Mapping(_), Some(Definition::Material(base))) => {
+                Definition::Material(Box::new(self.material(value, (**base).clone())?))
+            }
+            (Value::Sequence(_), None) => Definition::Transform(self.transform(value)?),
+            (Value::Sequence(_), Some(Definition::Transform(base))) => {
+                Definition::Transform(self.transform(value)? * *base)
+            }
+            (Value::Mapping(_) | Value::Sequence(_), Some(_)) => {
+                return Err(required(entry, "extend")?
+                    .error("cannot extend a definition of a different kind"));
+            }
+            _ => return Err(value.error("expected a material or a list of transforms")),
+        };
+
+        self.definitions
+            .insert(name.as_str()?.to_string(), definition);
+        Ok(())
+    }
+
+    fn definition(&self, name: &Node) -> Result<&Definition, SceneError> {
+        let key = name.as_str()?;
+        self.definitions
+            .get(key)
+            .ok_or_else(|| name.error(format!("`{key}` has not been defined")))
+    }
+
+    fn shape(&self, entry: &Node, inherited: &Material) -> Result<Shape, SceneError> {
+        let kind = required(entry, "add")?;
+        let transform = entry
+            .get("transform")
+            .map(|t| self.transform(t))
+            .transpose()?
+            .unwrap_or_else(identity_matrix);
+        let material = entry
+            .get("material")
+            .map(|m| self.material(m, inherited.clone()))
+            .transpose()?;
+        let has_material = material.is_some();
+        let material = material.unwrap_or_else(|| inherited.clone());
+
+        let shape = match kind.as_str()? {
+            "sphere" => {
+                check_keys(entry, &SHAPE_KEYS, "sphere")?;
+                sphere().transform(transform).material(material).build()
+            }
+            "plane" => {
+                check_keys(entry, &SHAPE_KEYS, "plane")?;
+                plane().transform(transform).material(material).build()
+            }
+            "cube" => {
+                check_keys(entry, &SHAPE_KEYS, "cube")?;
+                cube().transform(transform).material(material).build()
+            }
+            name @ ("cylinder" | "cone") => {
+                check_keys(
+                    entry,
+                    &[&SHAPE_KEYS[..], &["min", "max", "closed"]].concat(),
+                    name,
+                )?;
+                let minimum = optional(entry, "min", Node::as_f32)?.unwrap_or(f32::NEG_INFINITY);
+                let maximum = optional(entry, "max", Node::as_f32)?.unwrap_or(f32::INFINITY);
+                let closed = optional(entry, "closed", Node::as_bool)?.unwrap_or(false);
+                if name == "cylinder" {
+                    cylinder()
+                        .transform(transform)
+                        .material(material)
+                        .minimum(minimum)
+                        .maximum(maximum)
+                        .closed(closed)
+                        .build()
+                } else {
+                    cone()
+                        .transform(transform)
+                        .material(material)
+                        .minimum(minimum)
+                        .maximum(maximum)
+                        .closed(closed)
+                        .build()
+                }
+            }
+            "group" => {
+                check_keys(entry, &[&SHAPE_KEYS[..], &["children"]].concat(), "group")?;
+                let g = group().transform(transform).build();
+                if let Some(children) = entry.get("children") {
+                    for child in children.as_sequence()? {
+                        g.add_child(self.shape(child, &material)?);
+                    }
+                }
+                g
+            }
+            "obj" => {
+                check_keys(entry, &[&SHAPE_KEYS[..], &["file"]].concat(), "obj")?;
+                let file = required(entry, "file")?;
+                let path = self.base_dir.join(file.as_str()?);
+                let source = fs::read_to_string(&path)
+                    .map_err(|e| file.error(format!("failed to read {}: {e}", path.display())))?;
+                // Parsing skips lines it cannot use rather than failing.
+                let Ok(parser) = source.parse::<ObjParser>();
+                let shape = parser.as_ref().clone();
+                shape.set_transform(transform);
+                if has_material {
+                    shape.set_material(material);
+                }
+                shape
+            }
+            other => return Err(kind.error(format!("unknown kind of object `{other}`"))),
+        };
+        Ok(shape)
+    }
+
+    /// Applies a material definition to `base`. `node` is either the name of
+    /// a defined material or a mapping of material properties.
+    fn material(&self, node: &Node, base: Material) -> Result<Material, SceneError> {
+        if let Value::Scalar(_) = node.value {
+            return match self.definition(node)? {
+                Definition::Material(material) => Ok((**material).clone()),
+                Definition::Transform(_) => Err(node.error("expected a material")),
+            };
+        }
+
+        let mut material = base;
+        for (key, value) in node.as_mapping()? {
+            match key.as_str()? {
+                "color" => material.color = color_from(value)?,
+                "ambient" => material.ambient = value.as_f32()?,
+                "diffuse" => material.diffuse = value.as_f32()?,
+                "specular" => material.specular = value.as_f32()?,
+                "shininess" => material.shininess = value.as_f32()?,
+                "reflective" => material.reflective = value.as_f32()?,
+                "transparency" => material.transparency = value.as_f32()?,
+                "refractive-index" => material.refractive_index = value.as_f32()?,
+                "pattern" => material.pattern = Some(self.pattern(value)?),
+                "bump" => material.bump = Some(self.bump(value)?),
+                "normal-map" => material.normal_map = Some(self.normal_map(value)?),
+                other => return Err(key.error(format!("unknown material property `{other}`"))),
+            }
+        }
+        Ok(material)
+    }
+
+    /// Builds a bump map whose heights come from the luminance of a pattern.
+    fn bump(&self, node: &Node) -> Result<BumpMap, SceneError> {
+        check_keys(node, &["pattern", "scale"], "bump map")?;
+        let bump = BumpMap::from_pattern(self.pattern(required(node, "pattern")?)?);
+        Ok(bump.with_scale(optional(node, "scale", Node::as_f32)?.unwrap_or(1.0)))
+    }
+
+    /// Builds a tangent-space normal map from a UV pattern, usually an image.
+    fn normal_map(&self, node: &Node) -> Result<NormalMap, SceneError> {
+        check_keys(node, &["uv-pattern", "strength"], "normal map")?;
+        let normal_map = NormalMap::new(self.uv_pattern(required(node, "uv-pattern")?)?);
+        Ok(normal_map.with_strength(optional(node, "strength", Node::as_f32)?.unwrap_or(1.0)))
+    }
+
+    fn pattern(&self, node: &Node) -> Result<Pattern, SceneError> {
+        let transform = node
+            .get("transform")
+            .map(|t| self.transform(t))
+            .transpose()?
+            .unwrap_or_else(identity_matrix);
+
+        let kind = required(node, "type")?;
+        let name = kind.as_str()?;
+        if name == "map" {
+            return self.texture_map(node, transform);
+        }
+
+        if name == "perturbed" {
+            check_keys(
+                node,
+                &["type", "pattern", "scale", "seed", "octaves", "transform"],
+                "perturbed pattern",
+            )?;
+            let pattern = perturbed(self.pattern(required(node, "pattern")?)?)
+                .transform(transform)
+                .maybe_scale(optional(node, "scale", Node::as_f32)?)
+                .maybe_seed(optional(node, "seed", Node::as_u16)?.map(u64::from))
+                .maybe_octaves(optional(node, "octaves", Node::as_u16)?.map(u32::from))
+                .build();
+            return built(node, pattern);
+        }
+
+        let noise_keys: &[&str] = match name {
+            "marble" | "wood" => &["seed", "octaves", "turbulence"],
+            "clouds" => &["seed", "octaves"],
+            _ => &[],
+        };
+        check_keys(
+            node,
+            &[&["type", "colors", "transform"][..], noise_keys].concat(),
+            "pattern",
+        )?;
+        let [a, b] = self.pattern_pair(required(node, "colors")?)?;
+        let seed = optional(node, "seed", Node::as_u16)?.map(u64::from);
+        let octaves = optional(node, "octaves", Node::as_u16)?.map(u32::from);
+        let turbulence = optional(node, "turbulence", Node::as_f32)?;
+        let pattern = match name {
+            "stripes" => stripe_pattern(a, b).transform(transform).build(),
+            "gradient" => gradient_pattern(a, b).transform(transform).build(),
+            "rings" => ring_pattern(a, b).transform(transform).build(),
+            "checkers" => checkers_pattern(a, b).transform(transform).build(),
+            "radial-gradient" => radial_gradient_pattern(a, b).transform(transform).build(),
+            "ring-gradient" => ring_gradient_pattern(a, b).transform(transform).build(),
+            "blended" => blended_pattern(a, b).transform(transform).build(),
+            "marble" => marble_pattern(a, b)
+                .transform(transform)
+                .maybe_seed(seed)
+                .maybe_octaves(octaves)
+                .maybe_turbulence(turbulence)
+                .build(),
+            "wood" => wood_pattern(a, b)
+                .transform(transform)
+                .maybe_seed(seed)
+                .maybe_octaves(octaves)
+                .maybe_turbulence(turbulence)
+                .build(),
+            "clouds" => clouds_pattern(a, b)
+                .transform(transform)
+                .maybe_seed(seed)
+                .maybe_octaves(octaves)
+                .build(),
+            other => return Err(kind.error(format!("unknown pattern type `{other}`"))),
+        };
+        built(node, pattern)
+    }
+
+    /// Reads the two parts of a pattern, each either a colour or a nested
+    /// pattern.
+    fn pattern_pair(&self, node: &Node) -> Result<[Pattern; 2], SceneError> {
+        let part = |node: &Node| match node.value {
+            Value::Mapping(_) => self.pattern(node),
+            _ => color_from(node).map(Pattern::from),
+        };
+        match node.as_sequence()? {
+            [a, b] => Ok([part(a)?, part(b)?]),
+            _ => Err(node.error("expected two colors or patterns")),
+        }
+    }
+
+    /// Builds a `map` pattern: a UV pattern wrapped onto the surface by a
+    /// mapping, or a different UV pattern for each face of a cube.
+    fn texture_map(&self, node: &Node, transform: Matrix4) -> Result<Pattern, SceneError> {
+        let mapping_node = required(node, "mapping")?;
+        let mapping = match mapping_node.as_str()? {
+            "spherical" => UvMapping::Spherical,
+            "planar" => UvMapping::Planar,
+            "cylindrical" => UvMapping::Cylindrical,
+            "cube" => UvMapping::Cube,
+            other => return Err(mapping_node.error(format!("unknown mapping `{other}`"))),
+        };
+
+        if let Some(images) = node.get("images").filter(|_| mapping == UvMapping::Cube) {
+            check_keys(
+                node,
+                &["type", "mapping", "images", "filter", "srgb", "transform"],
+                "cube map",
+            )?;
+            return cube_map_images(&self.base_dir.join(images.as_str()?))
+                .filter(optional(node, "filter", texture_filter)?.unwrap_or_default())
+                .srgb(optional(node, "srgb", Node::as_bool)?.unwrap_or(false))
+                .transform(transform)
+                .load()
+                .map_err(|e| images.error(format!("{e:#}")));
+        }
+
+        if mapping == UvMapping::Cube && node.get("uv-pattern").is_none() {
+            check_keys(
+                node,
+                &[&["type", "mapping", "transform"][..], &FACES].concat(),
+                "cube map",
+            )?;
+            let [left, right, front, back, up, down] =
+                FACES.map(|face| required(node, face).and_then(|n| self.uv_pattern(n)));
+            let pattern = cube_map()
+                .left(left?)
+                .right(right?)
+                .front(front?)
+                .back(back?)
+                .up(up?)
+                .down(down?)
+                .transform(transform)
+                .build();
+            return built(node, pattern);
+        }
+
+        check_keys(
+            node,
+            &["type", "mapping", "uv-pattern", "transform"],
+            "texture map",
+        )?;
+        let uv_pattern = self.uv_pattern(required(node, "uv-pattern")?)?;
+        built(
+            node,
+            texture_map(uv_pattern, mapping)
+                .transform(transform)
+                .build(),
+        )
+    }
+
+    fn uv_pattern(&self, node: &Node) -> Result<UvPattern, SceneError> {
+        let kind = required(node, "type")?;
+        match kind.as_str()? {
+            "checkers" => {
+                check_keys(node, &["type", "width", "height", "colors"], "UV checkers")?;
+                let [a, b] = color_pair(required(node, "colors")?)?;
+                Ok(uv_checkers(
+                    required(node, "width")?.as_f32()?,
+                    required(node, "height")?.as_f32()?,
+                    a,
+                    b,
+                ))
+            }
+            "align-check" => {
+                check_keys(node, &["type", "colors"], "align check")?;
+                let colors = required(node, "colors")?;
+                check_keys(
+                    colors,
+                    &[
+                        "main",
+                        "upper-left",
+                        "upper-right",
+                        "lower-left",
+                        "lower-right",
+                    ],
+                    "align check colors",
+                )?;
+                let color = |key| required(colors, key).and_then(color_from);
+                Ok(align_check(color("main")?)
+                    .upper_left(color("upper-left")?)
+                    .upper_right(color("upper-right")?)
+                    .lower_left(color("lower-left")?)
+                    .lower_right(color("lower-right")?)
+                    .build())
+            }
+            "image" => {
+                check_keys(
+                    node,
+                    &["type", "file", "filter", "wrap", "srgb"],
+                    "UV image",
+                )?;
+                let file = required(node, "file")?;
+                let image = Canvas::open(self.base_dir.join(file.as_str()?))
+                    .map_err(|e| file.error(format!("{e:#}")))?;
+                uv_image(image)
+                    .filter(optional(node, "filter", texture_filter)?.unwrap_or_default())
+                    .wrap(optional(node, "wrap", texture_wrap)?.unwrap_or_default())
+                    .srgb(optional(node, "srgb", Node::as_bool)?.unwrap_or(false))
+                    .build()
+                    .map_err(|e| file.error(format!("{e:#}")))
+            }
+            other => Err(kind.error(format!("unknown UV pattern type `{other}`"))),
+        }
+    }
+
+    /// Combines a list of transforms, each applied after the ones before it.
+    fn transform(&self, node: &Node) -> Result<Matrix4, SceneError> {
+        let mut result = identity_matrix();
+        for item in node.as_sequence()? {
+            let step = match &item.value {
+                Value::Scalar(_) => match self.definition(item)? {
+                    Definition::Transform(matrix) => *matrix,
+                    Definition::Material(_) => return Err(item.error("expected a transform")),
+                },
+                _ => transform_step(item)?,
+            };
+            result = step * result;
+        }
+
+        if !result.is_invertible() {
+            return Err(node.error("transform is not invertible"));
+        }
+        Ok(result)
+    }
+}
+
+fn transform_step(item: &Node) -> Result<Matrix4, SceneError> {
+    let Some((operation, arguments)) = item.as_sequence()?.split_first() else {
+        return Err(item.error("expected a transform such as `[translate, x, y, z]`"));
+    };
+    let arguments = arguments
+        .iter()
+        .map(Node::as_f32)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let name = operation.as_str()?;
+    let expected = match name {
+        "translate" | "scale" => 3,
+        "rotate-x" | "rotate-y" | "rotate-z" => 1,
+        "shear" => 6,
+        _ => return Err(operation.error(format!("unknown transform `{name}`"))),
+    };
+    if arguments.len() != expected {
+        return Err(item.error(format!(
+            "`{name}` takes {expected} values but {} were given",
+            arguments.len()
+        )));
+    }
+
+    let a = &arguments;
+    Ok(match name {
+        "translate" => transform::translation(a[0], a[1], a[2]),
+        "scale" => transform::scaling(a[0], a[1], a[2]),
+        "rotate-x" => transform::rotation_x(a[0]),
+        "rotate-y" => transform::rotation_y(a[0]),
+        "rotate-z" => transform::rotation_z(a[0]),
+        _ => transform::shearing(a[0], a[1], a[2], a[3], a[4], a[5]),
+    })
+}
+
+fn camera_from(entry: &Node) -> Result<Camera, SceneError> {
+    check_keys(
+        entry,
+        &[
+            "add",
+            "width",
+            "height",
+            "field-of-view",
+            "from",
+            "to",
+            "up",
+        ],
+        "camera",
+    )?;
+    let width = required(entry, "width")?.as_u16()?;
+    let height = required(entry, "height")?.as_u16()?;
+    let from = point_from(required(entry, "from")?)?;
+    let to = point_from(required(entry, "to")?)?;
+    let up = vector_from(required(entry, "up")?)?;
+
+    let view = transform::view_transform(from, to, up);
+    if !view.is_invertible() {
+        return Err(required(entry, "up")?.error("camera `up` is parallel to its view direction"));
+    }
+
+    Ok(camera(width, height)
+        .field_of_view(required(entry, "field-of-view")?.as_f32()?)
+        .transform(view)
+        .build())
+}
+
+fn light_from(entry: &Node) -> Result<Box<dyn Light>, SceneError> {
+    let kind = match entry.get("type") {
+        Some(kind) => kind.as_str()?,
+        None if entry.get("corner").is_some() => "area",
+        None => "point",
+    };
+    let intensity = color_from(required(entry, "intensity")?)?;
+    let attenuation = optional(entry, "attenuation", attenuation_from)?.unwrap_or_default();
+
+    let light: Box<dyn Light> = match kind {
+        "point" => {
+            check_keys(
+                entry,
+                &["add", "type", "at", "intensity", "attenuation"],
+                "point light",
+            )?;
+            point_light(point_from(required(entry, "at")?)?, intensity)
+                .with_attenuation(attenuation)
+                .into()
+        }
+        "area" => {
+            check_keys(
+                entry,
+                &[
+                    "add",
+                    "type",
+                    "corner",
+                    "uvec",
+                    "usteps",
+                    "vvec",
+                    "vsteps",
+                    "jitter",
+                    "intensity",
+                    "attenuation",
+                ],
+                "area light",
+            )?;
+            area_light(
+                point_from(required(entry, "corner")?)?,
+                vector_from(required(entry, "uvec")?)?,
+                required(entry, "usteps")?.as_u16()?,
+                vector_from(required(entry, "vvec")?)?,
+                required(entry, "vsteps")?.as_u16()?,
+                intensity,
+            )
+            .jitter(optional(entry, "jitter", Node::as_bool)?.unwrap_or(true))
+            .attenuation(attenuation)
+            .build()
+            .into()
+        }
+        "spot" => {
+            check_keys(
+                entry,
+                &[
+                    "add",
+                    "type",
+                    "at",
+                    "direction",
+                    "inner-angle",
+                    "outer-angle",
+                    "intensity",
+                    "attenuation",
+                ],
+                "spot light",
+            )?;
+            spot_light(
+                point_from(required(entry, "at")?)?,
+                vector_from(required(entry, "direction")?)?,
+                intensity,
+            )
+            .inner_angle(required(entry, "inner-angle")?.as_f32()?)
+            .outer_angle(required(entry, "outer-angle")?.as_f32()?)
+            .attenuation(attenuation)
+            .build()
+            .into()
+        }
+        "directional" => {
+            check_keys(
+                entry,
+                &["add", "type", "direction", "intensity"],
+                "directional light",
+            )?;
+            directional_light(vector_from(required(entry, "direction")?)?, intensity).into()
+        }
+        other => {
+            return Err(required(entry, "type")?.error(format!("unknown kind of light `{other}`")));
+        }
+    };
+    Ok(light)
+}
+
+fn attenuation_from(node: &Node) -> Result<Attenuation, SceneError> {
+    if let Value::Sequence(_) = node.value {
+        let [constant, linear, quadratic] = triple(node)?;
+        return Ok(Attenuation::Polynomial {
+            constant,
+            linear,
+            quadratic,
+        });
+    }
+
+    match node.as_str()? {
+        "none" => Ok(Attenuation::None),
+        "inverse-square" => Ok(Attenuation::InverseSquare),
+        _ => {
+            Err(node.error("expected `none`, `inverse-square` or `[constant, linear, quadratic]`"))
+        }
+    }
+}
+
+fn texture_filter(node: &Node) -> Result<TextureFilter, SceneError> {
+    match node.as_str()? {
+        "nearest" => Ok(TextureFilter::Nearest),
+        "bilinear" => Ok(TextureFilter::Bilinear),
+        _ => Err(node.error("expected `nearest` or `bilinear`")),
+    }
+}
+
+fn texture_wrap(node: &Node) -> Result<TextureWrap, SceneError> {
+    match node.as_str()? {
+        "repeat" => Ok(TextureWrap::Repeat),
+        "clamp" => Ok(TextureWrap::Clamp),
+        _ => Err(node.error("expected `repeat` or `clamp`")),
+    }
+}
+
+/// Reports a pattern that could not be built at its `transform`, the only
+/// part of a pattern that its constructor rejects.
+fn built(node: &Node, pattern: Result<Pattern>) -> Result<Pattern, SceneError> {
+    pattern.map_err(|e| {
+        node.get("transform")
+            .unwrap_or(node)
+            .error(format!("{e:#}"))
+    })
+}
+
+fn color_pair(node: &Node) -> Result<[Color; 2], SceneError> {
+    match node.as_sequence()? {
+        [a, b] => Ok([color_from(a)?, color_from(b)?]),
+        _ => Err(node.error("expected two colors")),
+    }
+}
+
+fn triple(node: &Node) -> Result<[f32; 3], SceneError> {
+    match node.as_sequence()? {
+        [x, y, z] => Ok([x.as_f32()?, y.as_f32()?, z.as_f32()?]),
+        _ => Err(node.error("expected a list of three numbers")),
+    }
+}
+
+fn color_from(node: &Node) -> Result<Color, SceneError> {
+    let [r, g, b] = triple(node)?;
+    Ok(color(r, g, b))
+}
+
+fn point_from(node: &Node) -> Result<Point, SceneError> {
+    let [x, y, z] = triple(node)?;
+    Ok(point(x, y, z))
+}
+
+fn vector_from(node: &Node) -> Result<Vector, SceneError> {
+    let [x, y, z] = triple(node)?;
+    Ok(vector(x, y, z))
+}
+
+fn required<'a>(entry: &'a Node, key: &str) -> Result<&'a Node, SceneError> {
+    entry
+        .get(key)
+        .ok_or_else(|| entry.error(format!("missing `{key}`")))
+}
+
+fn optional<T>(
+    entry: &Node,
+    key: &str,
+    convert: impl Fn(&Node) -> Result<T, SceneError>,
+) -> Result<Option<T>, SceneError> {
+    entry.get(key).map(convert).transpose()
+}
+
+/// Rejects keys that `what` does not understand, which are usually typos.
+fn check_keys(entry: &Node, allowed: &[&str], what: &str) -> Result<(), SceneError> {
+    for (key, _) in entry.as_mapping()? {
+        let name = key.as_str()?;
+        if !allowed.contains(&name) {
+            return Err(key.error(format!("unknown key `{name}` for {what}")));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{EPSILON, ray, shape::Group};
+
+    const CAMERA: &str = "\
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [0, 1.5, -5]
+  to: [0, 1, 0]
+  up: [0, 1, 0]
+";
+
+    fn scene(objects: &str) -> Result<Scene, SceneError> {
+        format!("{CAMERA}{objects}").parse()
+    }
+
+    fn error(objects: &str) -> SceneError {
+        scene(objects).err().expect("scene should not load")
+    }
+
+    #[test]
+    fn loading_a_camera() {
+        let s = scene("").unwrap();
+        assert_eq!((s.camera.width, s.camera.height), (100, 50));
+        assert_relative_eq!(s.camera.field_of_view, 0.785);
+        assert_eq!(
+            s.camera.transform,
+            transform::view_transform(point(0, 1.5, -5), point(0, 1, 0), vector(0, 1, 0))
+        );
+        assert!(s.world.objects.is_empty());
+    }
+
+    #[test]
+    fn loading_lights() {
+        let s = scene(
+            "
+- add: light
+  at: [-10, 10, -10]
+  intensity: [1, 1, 1]
+- add: light
+  corner: [-1, 2, 4]
+  uvec: [2, 0, 0]
+  vvec: [0, 2, 0]
+  usteps: 4
+  vsteps: 2
+  jitter: false
+  intensity: [1.5, 1.5, 1.5]
+- add: light
+  type: directional
+  direction: [0, -1, 0]
+  intensity: [0.5, 0.5, 0.5]
+",
+        )
+        .unwrap();
+
+        assert_eq!(s.world.lights.len(), 3);
+        let p = point(0, 0, 0);
+        assert_eq!(
+            s.world.lights[0].sample_points(p),
+            vec![point(-10, 10, -10)]
+        );
+        assert_eq!(s.world.lights[1].sample_points(p).len(), 8);
+        assert_eq!(s.world.lights[1].intensity_at(p), color(1.5, 1.5, 1.5));
+        assert_eq!(s.world.lights[2].sample_points(p), vec![point(0, 1, 0)]);
+    }
+
+    #[test]
+    fn loading_shapes_with_materials_and_transforms() {
+        let s = scene(
+            "
+- add: sphere
+  material:
+    color: [1, 0.2, 1]
+    diffuse: 0.7
+    refractive-index: 1.5
+  transform:
+    - [scale, 2, 2, 2]
+    - [translate, 0, 1, 0]
+- add: cylinder
+  min: 0
+  max: 2
+  closed: true
+",
+        )
+        .unwrap();
+
+        let sphere = &s.world.objects[0];
+        let material = sphere.material();
+        assert_eq!(material.color, color(1, 0.2, 1));
+        assert_relative_eq!(material.diffuse, 0.7);
+        assert_relative_eq!(material.refractive_index, 1.5);
+        assert_eq!(
+            sphere.transform(),
+            transform::translation(0, 1, 0) * transform::scaling(2, 2, 2)
+        );
+
+        let xs = s.world.objects[1].intersect(ray(point(0, 5, 0), vector(0, -1, 0)));
+        assert_eq!(xs.len(), 2);
+        let nearest = xs.iter().map(|i| i.time).fold(f32::INFINITY, f32::min);
+        assert_relative_eq!(nearest, 3.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn definitions_can_extend_each_other() {
+        let s = scene(
+            "
+- define: white-material
+  value:
+    color: [1, 1, 1]
+    diffuse: 0.7
+    reflective: 0.1
+- define: blue-material
+  extend: white-material
+  value:
+    color: [0.537, 0.831, 0.914]
+- define: standard-transform
+  value:
+    - [scale, 0.5, 0.5, 0.5]
+    - [translate, 1, -1, 1]
+- define: large-object
+  value:
+    - [scale, 3.5, 3.5, 3.5]
+    - standard-transform
+- add: cube
+  material: blue-material
+  transform:
+    - large-object
+    - [translate, 8.5, 1.5, -0.5]
+",
+        )
+        .unwrap();
+
+        let cube = &s.world.objects[0];
+        let material = cube.material();
+        assert_eq!(material.color, color(0.537, 0.831, 0.914));
+        assert_relative_eq!(material.diffuse, 0.7);
+        assert_relative_eq!(material.reflective, 0.1);
+        assert_eq!(
+            cube.transform(),
+            transform::translation(8.5, 1.5, -0.5)
+                * transform::translation(1, -1, 1)
+                * transform::scaling(0.5, 0.5, 0.5)
+                * transform::scaling(3.5, 3.5, 3.5)
+        );
+    }
+
+    #[test]
+    fn loading_a_pattern() {
+        let s = scene(
+            "
+- add: plane
+  material:
+    pattern:
+      type: stripes
+      colors:
+        - [1, 1, 1]
+        - [0, 0, 0]
+      transform:
+        - [scale, 2, 2, 2]
+",
+        )
+        .unwrap();
+
+        let plane = &s.world.objects[0];
+        let pattern = plane.material().pattern.unwrap();
+        assert_eq!(
+            pattern.pattern_at_shape(plane, point(1, 0, 0)),
+            color(1, 1, 1)
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(plane, point(2.5, 0, 0)),
+            color(0, 0, 0)
+        );
+
+        let e = error(
+            "- add: plane\n  material:\n    pattern:\n      type: stripes\n      colors: [[1, 1, 1], [0, 0, 0]]\n      transform:\n        - [scale, 0, 1, 1]\n",
+        );
+        assert_eq!(e.message, "transform is not invertible");
+    }
+
+    #[test]
+    fn loading_nested_patterns() {
+        let s = scene(
+            "
+- add: plane
+  material:
+    pattern:
+      type: stripes
+      colors:
+        - type: checkers
+          colors: [[1, 1, 1], [0, 0, 0]]
+          transform:
+            - [scale, 0.5, 0.5, 0.5]
+        - type: blended
+          colors:
+            - [1, 0, 0]
+            - type: ring-gradient
+              colors: [[0, 0, 1], [0, 0, 1]]
+",
+        )
+        .unwrap();
+
+        let pattern = s.world.objects[0].material().pattern.unwrap();
+        assert_eq!(pattern.pattern_at(point(0.25, 0, 0)), color(1, 1, 1));
+        assert_eq!(pattern.pattern_at(point(0.75, 0, 0)), color(0, 0, 0));
+        assert_eq!(pattern.pattern_at(point(1.5, 0, 0)), color(0.5, 0, 0.5));
+
+        let e = error(
+            "- add: plane\n  material:\n    pattern: { type: stripes, colors: [[1, 1, 1]] }\n",
+        );
+        assert_eq!(e.message, "expected two colors or patterns");
+    }
+
+    #[test]
+    fn loading_noise_patterns() {
+        let s = scene(
+            "
+- add: plane
+  material:
+    pattern:
+      type: marble
+      colors: [[0, 0, 0], [1, 1, 1]]
+      turbulence: 0
+- add: plane
+  material:
+    pattern:
+      type: perturbed
+      scale: 0
+      pattern:
+        type: stripes
+        colors: [[1, 1, 1], [0, 0, 0]]
+- add: plane
+  material:
+    pattern:
+      type: clouds
+      colors: [[0, 0, 0], [1, 1, 1]]
+      seed: 3
+      octaves: 2
+",
+        )
+        .unwrap();
+
+        let pattern = |i: usize| s.world.objects[i].material().pattern.unwrap();
+        assert_eq!(pattern(0).pattern_at(point(1, 0, 0)), color(1, 1, 1));
+        assert_eq!(pattern(1).pattern_at(point(1.5, 0, 0)), color(0, 0, 0));
+        let p = point(0.3, 0.2, 0.1);
+        assert_eq!(
+            pattern(2).pattern_at(p),
+            clouds_pattern(color(0, 0, 0), color(1, 1, 1))
+                .seed(3)
+                .octaves(2)
+                .build()
+                .unwrap()
+                .pattern_at(p)
+        );
+
+        let e = error(
+            "- add: plane\n  material:\n    pattern: { type: clouds, colors: [[0, 0, 0], [1, 1, 1]], turbulence: 1 }\n",
+        );
+        assert!(e.message.contains("turbulence"), "{}", e.message);
+    }
+
+    #[test]
+    fn loading_texture_maps() {
+        let s = scene(
+            "
+- add: sphere
+  material:
+    pattern:
+      type: map
+      mapping: spherical
+      uv-pattern:
+        type: checkers
+        width: 16
+        height: 8
+        colors: [[0, 0, 0], [1, 1, 1]]
+- add: cube
+  material:
+    pattern:
+      type: map
+      mapping: cube
+      left: { type: checkers, width: 1, height: 1, colors: [[1, 0, 0], [1, 0, 0]] }
+      right: { type: checkers, width: 1, height: 1, colors: [[0, 1, 0], [0, 1, 0]] }
+      front: { type: checkers, width: 1, height: 1, colors: [[0, 0, 1], [0, 0, 1]] }
+      back: { type: checkers, width: 1, height: 1, colors: [[1, 1, 0], [1, 1, 0]] }
+      up: { type: checkers, width: 1, height: 1, colors: [[0, 1, 1], [0, 1, 1]] }
+      down:
+        type: align-check
+        colors:
+          main: [1, 1, 1]
+          upper-left: [1, 0, 0]
+          upper-right: [1, 1, 0]
+          lower-left: [0, 1, 0]
+          lower-right: [0, 1, 1]
+",
+        )
+        .unwrap();
+
+        let sphere = &s.world.objects[0];
+        let pattern = sphere.material().pattern.unwrap();
+        let p = point(0.4315, 0.4670, 0.7719);
+        assert_eq!(pattern.pattern_at_shape(sphere, p), color(1, 1, 1));
+
+        let cube = &s.world.objects[1];
+        let pattern = cube.material().pattern.unwrap();
+        assert_eq!(
+            pattern.pattern_at_shape(cube, point(-1, 0, 0)),
+            color(1, 0, 0)
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(cube, point(0, 1, 0)),
+            color(0, 1, 1)
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(cube, point(0, -1, 0)),
+            color(1, 1, 1)
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(cube, point(-0.9, -1, 0.9)),
+            color(1, 0, 0)
+        );
+
+        let e = error("- add: cube\n  material:\n    pattern: { type: map, mapping: cube }\n");
+        assert_eq!(e.message, "missing `left`");
+    }
+
+    #[test]
+    fn loading_image_textures() {
+        let dir = std::env::temp_dir().join(format!("scene-image-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("earth.ppm"), "P3\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let source = format!(
+            "{CAMERA}
+- add: sphere
+  material:
+    pattern:
+      type: map
+      mapping: spherical
+      uv-pattern:
+        type: image
+        file: earth.ppm
+        filter: nearest
+        wrap: clamp
+"
+        );
+
+        let s = Scene::from_yaml(&source, &dir).unwrap();
+        let missing = Scene::from_yaml(&source.replace("earth", "mars"), &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let sphere = &s.world.objects[0];
+        let pattern = sphere.material().pattern.unwrap();
+        assert_eq!(
+            pattern.pattern_at_shape(sphere, point(1, 0, 0)),
+            color(1, 0, 0)
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(sphere, point(-1, 0, 0)),
+            color(0, 0, 1)
+        );
+        assert_eq!(missing.err().unwrap().line, 16);
+    }
+
+    #[test]
+    fn loading_a_bump_map() {
+        let s = scene(
+            "
+- add: plane
+  material:
+    bump:
+      scale: 0.5
+      pattern:
+        type: gradient
+        colors: [[0, 0, 0], [1, 1, 1]]
+        transform:
+          - [scale, 4, 1, 1]
+",
+        )
+        .unwrap();
+
+        let bump = s.world.objects[0].material().bump.unwrap();
+        assert_relative_eq!(bump.scale(), 0.5);
+        assert_eq!(bump.transform(), transform::scaling(4, 1, 1));
+
+        let e = error("- add: plane\n  material:\n    bump: { scale: 2 }\n");
+        assert_eq!(e.message, "missing `pattern`");
+    }
+
+    #[test]
+    fn loading_a_normal_map() {
+        let s = scene(
+            "
+- add: plane
+  material:
+    normal-map:
+      strength: 0.5
+      uv-pattern:
+        type: checkers
+        width: 2
+        height: 2
+        colors: [[1, 0.5, 1], [0.5, 0.5, 1]]
+",
+        )
+        .unwrap();
+
+        let normal_map = s.world.objects[0].material().normal_map.unwrap();
+        assert_relative_eq!(normal_map.strength, 0.5);
+        assert_eq!(normal_map.normal_at(0.1, 0.1), vector(0.5, 0, 1));
+
+        let e = error("- add: plane\n  material:\n    normal-map: { strength: 2 }\n");
+        assert_eq!(e.message, "missing `uv-pattern`");
+    }
+
+    #[test]
+    fn group_children_inherit_its_material() {
+        let s = scene(
+            "
+- add: group
+  material:
+    color: [1, 0, 0]
+  transform:
+    - [translate, 0, 2, 0]
+  children:
+    - add: sphere
+    - add: cube
+      material:
+        color: [0, 0, 1]
+",
+        )
+        .unwrap();
+
+        let g = &s.world.objects[0];
+        let inner = g.inner();
+        let children = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<Group>()
+            .unwrap()
+            .children();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].material().color, color(1, 0, 0));
+        assert_eq!(children[1].material().color, color(0, 0, 1));
+        assert_eq!(children[0].parent().as_ref(), Some(g));
+    }
+
+    #[test]
+    fn loading_an_obj_file() {
+        let dir = std::env::temp_dir().join(format!("scene-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tri.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let source = format!("{CAMERA}- add: obj\n  file: tri.obj\n  material:\n    ambient: 1\n");
+
+        let s = Scene::from_yaml(&source, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let xs = s.world.intersect(ray(point(0.2, 0.2, -1), vector(0, 0, 1)));
+        assert_eq!(xs.len(), 1);
+        assert_relative_eq!(xs[0].object.material().ambient, 1.0);
+
+        let e = error("- add: obj\n  file: missing.obj\n");
+        assert_eq!((e.line, e.column), (9, 9));
+        let cause = fs::read_to_string("missing.obj").unwrap_err();
+        assert_eq!(e.message, format!("failed to read missing.obj: {cause}"));
+    }
+
+    #[test]
+    fn loading_a_scene_file() {
+        let s = Scene::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/cover.yml")).unwrap();
+        assert_eq!((s.camera.width, s.camera.height), (800, 800));
+        assert_eq!(s.world.lights.len(), 2);
+        assert_eq!(s.world.objects.len(), 19);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let e = error("- add: sphere\n  materail: {}\n");
+        assert_eq!((e.line, e.column), (9, 3));
+        assert_eq!(e.message, "unknown key `materail` for sphere");
+
+        let e = error("- add: cube\n  material: shiny\n");
+        assert_eq!((e.line, e.column), (9, 13));
+        assert_eq!(e.message, "`shiny` has not been defined");
+
+        let e = error("- add: cube\n  transform:\n    - [scale, 1, 2]\n");
+        assert_eq!((e.line, e.column), (10, 7));
+
+        let e = error("- add: cube\n  transform:\n    - [scale, 0, 1, 1]\n");
+        assert_eq!((e.line, e.column), (10, 5));
+        assert_eq!(e.message, "transform is not invertible");
+
+        let e = error("- add: light\n  at: [0, 0]\n  intensity: [1, 1, 1]\n");
+        assert_eq!((e.line, e.column), (9, 7));
+
+        let e = error("- add: teapot\n");
+        assert_eq!(
+            e.to_string(),
+            "line 8, column 8: unknown kind of object `teapot`"
+        );
+
+        let e = "- add: light\n  at: [0, 0, 0]\n  intensity: [1, 1, 1]\n"
+            .parse::<Scene>()
+            .err()
+            .unwrap();
+        assert_eq!(e.message, "scene has no camera");
+    }
+}
@@ -0,0 +1,671 @@
+//! A parser for the subset of YAML used by scene files: block mappings and
+//! sequences, flow `[...]` and `{...}` collections, plain and quoted scalars,
+//! and `#` comments. Every node remembers where it started so that errors
+//! can point at the offending text.
+
+use super::SceneError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Scalar(String),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Node {
+    pub value: Value,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Node {
+    fn new(value: Value, line: usize, column: usize) -> Node {
+        Node {
+            value,
+            line,
+            column,
+        }
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Result<&str, SceneError> {
+        match &self.value {
+            Value::Scalar(s) => Ok(s),
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    pub(crate) fn as_f32(&self) -> Result<f32, SceneError> {
+        self.as_str()
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| self.error("expected a number"))
+    }
+
+    pub(crate) fn as_u16(&self) -> Result<u16, SceneError> {
+        self.as_str()
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| self.error("expected a whole number from 0 to 65535"))
+    }
+
+    pub(crate) fn as_bool(&self) -> Result<bool, SceneError> {
+        match self.as_str() {
+            Ok("true") => Ok(true),
+            Ok("false") => Ok(false),
+            _ => Err(self.error("expected `true` or `false`")),
+        }
+    }
+
+    pub(crate) fn as_sequence(&self) -> Result<&[Node], SceneError> {
+        match &self.value {
+            Value::Sequence(items) => Ok(items),
+            _ => Err(self.error("expected a list")),
+        }
+    }
+
+    pub(crate) fn as_mapping(&self) -> Result<&[(Node, Node)], SceneError> {
+        match &self.value {
+            Value::Mapping(entries) => Ok(entries),
+            _ => Err(self.error("expected a mapping of keys to values")),
+        }
+    }
+
+    /// Returns the value for `key` if this node is a mapping that has it.
+    pub(crate) fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Mapping(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(&k.value, Value::Scalar(s) if s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a YAML document into a tree of nodes. An empty document is `Null`.
+pub(crate) fn parse(source: &str) -> Result<Node, SceneError> {
+    let mut parser = Parser {
+        lines: lines(source)?,
+        pos: 0,
+    };
+    let Some(first) = parser.lines.first() else {
+        return Ok(Node::new(Value::Null, 1, 1));
+    };
+
+    let node = parser.block(first.indent)?;
+    if let Some(line) = parser.peek() {
+        return Err(line.error(0, "unexpected indentation"));
+    }
+    Ok(node)
+}
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+impl Line<'_> {
+    fn error(&self, offset: usize, message: impl Into<String>) -> SceneError {
+        SceneError {
+            line: self.number,
+            column: self.indent + offset + 1,
+            message: message.into(),
+        }
+    }
+}
+
+/// Splits the source into non-blank lines with comments removed.
+fn lines(source: &str) -> Result<Vec<Line<'_>>, SceneError> {
+    let mut lines = vec![];
+    for (index, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start_matches(' ');
+        let indent = text.len() - content.len();
+        let line = Line {
+            number: index + 1,
+            indent,
+            text: content,
+        };
+
+        if content.starts_with('\t') {
+            return Err(line.error(0, "tabs cannot be used for indentation"));
+        }
+        if content.is_empty() || (indent == 0 && (content == "---" || content == "...")) {
+            continue;
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match quote {
+            None if c == '#' && previous.is_whitespace() => return &line[..index],
+            None if starts_quote(c, previous) => quote = Some(c),
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some('\'') if c == '\'' && chars.next_if(|&(_, next)| next == '\'').is_some() => {}
+            Some(q) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Quotes only open a scalar at the start of a token, so apostrophes inside
+/// plain text are left alone.
+fn starts_quote(c: char, previous: char) -> bool {
+    (c == '"' || c == '\'') && (previous.is_whitespace() || "[{,".contains(previous))
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` into the key, and the value with its offset from the
+/// start of `text`. Flow collections and quoted scalars are never keys
+/// unless the key is quoted on its own. The key keeps its quotes; see
+/// [`unquote_key`].
+fn split_key(text: &str) -> Option<(&str, &str, usize)> {
+    if text.starts_with('[') || text.starts_with('{') {
+        return None;
+    }
+
+    let mut quote = None;
+    let bytes = text.as_bytes();
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if index == 0 => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, ':') if bytes.get(index + 1).is_none_or(|&b| b == b' ') => {
+                let key = text[..index].trim_end();
+                let rest = text[index + 1..].trim_start();
+                return Some((key, rest, text.len() - rest.len()));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Strips the quotes from a key returned by [`split_key`], or returns
+/// `None` if it opens a quote that it does not close at its very end.
+fn unquote_key(key: &str) -> Option<&str> {
+    match key.chars().next() {
+        Some(quote @ ('"' | '\'')) => key.strip_prefix(quote)?.strip_suffix(quote),
+        _ => Some(key),
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Line<'a>> {
+        self.lines.get(self.pos).copied()
+    }
+
+    /// Parses the node starting at the current line, which is indented by
+    /// `indent`.
+    fn block(&mut self, indent: usize) -> Result<Node, SceneError> {
+        let line = self.lines[self.pos];
+        if is_sequence_item(line.text) {
+            self.sequence(indent)
+        } else if split_key(line.text).is_some() {
+            self.mapping(indent)
+        } else {
+            self.inline()
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Node, SceneError> {
+        let first = self.lines[self.pos];
+        let mut items = vec![];
+
+        while let Some(line) = self.peek() {
+            if line.indent != indent || !is_sequence_item(line.text) {
+                break;
+            }
+
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent, &line, 1)?);
+            } else {
+                let offset = line.text.len() - rest.len();
+                self.lines[self.pos] = Line {
+                    number: line.number,
+                    indent: indent + offset,
+                    text: rest,
+                };
+                items.push(self.block(indent + offset)?);
+            }
+            self.check_indentation(indent)?;
+        }
+
+        Ok(Node::new(
+            Value::Sequence(items),
+            first.number,
+            first.indent + 1,
+        ))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Node, SceneError> {
+        let first = self.lines[self.pos];
+        let mut entries: Vec<(Node, Node)> = vec![];
+
+        while let Some(line) = self.peek() {
+            if line.indent != indent {
+                break;
+            }
+
+            let Some((key, rest, offset)) = split_key(line.text) else {
+                return Err(line.error(0, "expected `key: value`"));
+            };
+            let Some(key) = unquote_key(key).map(str::to_string) else {
+                return Err(line.error(0, format!("malformed quoted key `{key}`")));
+            };
+            if entries
+                .iter()
+                .any(|(k, _)| matches!(&k.value, Value::Scalar(s) if *s == key))
+            {
+                return Err(line.error(0, format!("duplicate key `{key}`")));
+            }
+            let key = Node::new(Value::Scalar(key), line.number, indent + 1);
+
+            let value = if rest.is_empty() {
+                self.pos += 1;
+                match self.peek() {
+                    Some(next) if next.indent == indent && is_sequence_item(next.text) => {
+                        self.sequence(indent)?
+                    }
+                    _ => self.nested(indent, &line, offset)?,
+                }
+            } else {
+                self.lines[self.pos] = Line {
+                    number: line.number,
+                    indent: indent + offset,
+                    text: rest,
+                };
+                self.inline()?
+            };
+
+            entries.push((key, value));
+            self.check_indentation(indent)?;
+        }
+
+        Ok(Node::new(
+            Value::Mapping(entries),
+            first.number,
+            first.indent + 1,
+        ))
+    }
+
+    /// Parses the block indented under `parent`, or returns `Null` positioned
+    /// after `offset` on the parent line when there is none.
+    fn nested(&mut self, indent: usize, parent: &Line, offset: usize) -> Result<Node, SceneError> {
+        match self.peek() {
+            Some(next) if next.indent > indent => self.block(next.indent),
+            _ => Ok(Node::new(
+                Value::Null,
+                parent.number,
+                parent.indent + offset + 1,
+            )),
+        }
+    }
+
+    fn check_indentation(&self, indent: usize) -> Result<(), SceneError> {
+        match self.peek() {
+            Some(next) if next.indent > indent => Err(next.error(0, "unexpected indentation")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses a scalar or flow collection, which may continue onto following
+    /// lines until its brackets are balanced.
+    fn inline(&mut self) -> Result<Node, SceneError> {
+        let line = self.lines[self.pos];
+        self.pos += 1;
+
+        let mut chars = positioned(&line);
+        if line.text.starts_with('[') || line.text.starts_with('{') {
+            while flow_depth(&chars) > 0 {
+                let Some(next) = self.peek() else {
+                    break;
+                };
+                chars.push((' ', next.number, next.indent));
+                chars.extend(positioned(&next));
+                self.pos += 1;
+            }
+        } else if !line.text.starts_with('"') && !line.text.starts_with('\'') {
+            return Ok(plain(line.text.to_string(), line.number, line.indent + 1));
+        }
+
+        let end = chars
+            .last()
+            .map_or((line.number, line.indent + 1), |&(_, l, c)| (l, c + 1));
+        let mut flow = Flow { chars, pos: 0, end };
+        let node = flow.value()?;
+        flow.skip_spaces();
+        if let Some((c, line, column)) = flow.peek() {
+            return Err(SceneError {
+                line,
+                column,
+                message: format!("unexpected `{c}`"),
+            });
+        }
+        Ok(node)
+    }
+}
+
+fn positioned(line: &Line) -> Vec<(char, usize, usize)> {
+    line.text
+        .char_indices()
+        .map(|(index, c)| (c, line.number, line.indent + index + 1))
+        .collect()
+}
+
+fn flow_depth(chars: &[(char, usize, usize)]) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut previous = ' ';
+    for &(c, _, _) in chars {
+        match (quote, c) {
+            (None, _) if starts_quote(c, previous) => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            _ => {}
+        }
+        previous = c;
+    }
+    depth
+}
+
+fn plain(text: String, line: usize, column: usize) -> Node {
+    let value = match text.as_str() {
+        "" | "~" | "null" => Value::Null,
+        _ => Value::Scalar(text),
+    };
+    Node::new(value, line, column)
+}
+
+/// Parser for flow collections and quoted scalars.
+struct Flow {
+    chars: Vec<(char, usize, usize)>,
+    pos: usize,
+    end: (usize, usize),
+}
+
+impl Flow {
+    fn peek(&self) -> Option<(char, usize, usize)> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|(c, _, _)| c == ' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn error_here(&self, message: impl Into<String>) -> SceneError {
+        let (line, column) = self.peek().map_or(self.end, |(_, l, c)| (l, c));
+        SceneError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, SceneError> {
+        self.skip_spaces();
+        let Some((c, line, column)) = self.peek() else {
+            return Err(self.error_here("expected a value"));
+        };
+
+        match c {
+            '[' => {
+                self.pos += 1;
+                let mut items = vec![];
+                while !self.close(']', items.is_empty())? {
+                    items.push(self.value()?);
+                }
+                Ok(Node::new(Value::Sequence(items), line, column))
+            }
+            '{' => {
+                self.pos += 1;
+                let mut entries = vec![];
+                while !self.close('}', entries.is_empty())? {
+                    let key = self.value()?;
+                    self.skip_spaces();
+                    if self.peek().is_none_or(|(c, _, _)| c != ':') {
+                        return Err(self.error_here("expected `:`"));
+                    }
+                    self.pos += 1;
+                    entries.push((key, self.value()?));
+                }
+                Ok(Node::new(Value::Mapping(entries), line, column))
+            }
+            '"' | '\'' => self.quoted(c),
+            _ => {
+                let start = self.pos;
+                while let Some((c, _, _)) = self.peek() {
+                    let next = self.chars.get(self.pos + 1).map(|&(c, _, _)| c);
+                    let ends_key = c == ':' && next.is_none_or(|n| " ,]}".contains(n));
+                    if ",]}".contains(c) || ends_key {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos]
+                    .iter()
+                    .map(|&(c, _, _)| c)
+                    .collect();
+                Ok(plain(text.trim_end().to_string(), line, column))
+            }
+        }
+    }
+
+    /// Consumes the separator before the next collection item, returning
+    /// `true` once the closing bracket has been reached.
+    fn close(&mut self, bracket: char, first: bool) -> Result<bool, SceneError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some((c, _, _)) if c == bracket => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some((',', _, _)) if !first => {
+                self.pos += 1;
+                self.skip_spaces();
+                if self.peek().is_some_and(|(c, _, _)| c == bracket) {
+                    self.pos += 1;
+                    return Ok(true);
+                }
+                Ok(false)
+            }
+            _ if first => Ok(false),
+            _ => Err(self.error_here(format!("expected `,` or `{bracket}`"))),
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<Node, SceneError> {
+        let (_, line, column) = self.chars[self.pos];
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let Some((c, _, _)) = self.peek() else {
+                return Err(self.error_here("unterminated string"));
+            };
+            self.pos += 1;
+            match c {
+                '\'' if quote == '\'' => {
+                    if self.peek().is_some_and(|(c, _, _)| c == '\'') {
+                        self.pos += 1;
+                        text.push('\'');
+                    } else {
+                        break;
+                    }
+                }
+                '"' if quote == '"' => break,
+                '\\' if quote == '"' => {
+                    let Some((escaped, _, _)) = self.peek() else {
+                        return Err(self.error_here("unterminated string"));
+                    };
+                    self.pos += 1;
+                    text.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                }
+                _ => text.push(c),
+            }
+        }
+        Ok(Node::new(Value::Scalar(text), line, column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(node: &Node) -> &str {
+        node.as_str().unwrap()
+    }
+
+    #[test]
+    fn parsing_block_mappings_and_sequences() {
+        let doc = parse(
+            "\
+# the camera
+- add: camera
+  width: 100
+  from: [ -6, 6, -10 ]   # comment after a value
+
+- define: standard-transform
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5, 0.5, 0.5 ]
+",
+        )
+        .unwrap();
+
+        let items = doc.as_sequence().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(scalar(items[0].get("add").unwrap()), "camera");
+        assert_eq!(items[0].get("width").unwrap().as_f32().unwrap(), 100.0);
+        let from = items[0].get("from").unwrap().as_sequence().unwrap();
+        assert_eq!(from.len(), 3);
+        assert_eq!(from[2].as_f32().unwrap(), -10.0);
+
+        let value = items[1].get("value").unwrap().as_sequence().unwrap();
+        assert_eq!(scalar(&value[1].as_sequence().unwrap()[0]), "scale");
+    }
+
+    #[test]
+    fn nodes_record_their_position() {
+        let doc = parse("- add: sphere\n  material:\n    color: [1, 0, 0]\n").unwrap();
+        let item = &doc.as_sequence().unwrap()[0];
+        assert_eq!((item.line, item.column), (1, 3));
+        let color = item.get("material").unwrap().get("color").unwrap();
+        assert_eq!((color.line, color.column), (3, 12));
+        let blue = &color.as_sequence().unwrap()[2];
+        assert_eq!((blue.line, blue.column), (3, 19));
+    }
+
+    #[test]
+    fn sequences_may_share_the_indentation_of_their_key() {
+        let doc = parse("transform:\n- [rotate-x, 1]\n- [scale, 2, 2, 2]\nname: x\n").unwrap();
+        assert_eq!(
+            doc.get("transform").unwrap().as_sequence().unwrap().len(),
+            2
+        );
+        assert_eq!(scalar(doc.get("name").unwrap()), "x");
+    }
+
+    #[test]
+    fn parsing_flow_collections_and_quoted_scalars() {
+        let doc = parse(
+            "\
+pattern: { type: stripes, colors: [[1, 0, 0], [0, 0, 1]] }
+file: \"models/my teapot.obj\"
+note: 'it''s # not a comment'
+empty: []
+multi: [ 1,
+   2, 3 ]
+",
+        )
+        .unwrap();
+        let pattern = doc.get("pattern").unwrap();
+        assert_eq!(scalar(pattern.get("type").unwrap()), "stripes");
+        assert_eq!(
+            pattern.get("colors").unwrap().as_sequence().unwrap().len(),
+            2
+        );
+        assert_eq!(scalar(doc.get("file").unwrap()), "models/my teapot.obj");
+        assert_eq!(scalar(doc.get("note").unwrap()), "it's # not a comment");
+        assert!(doc.get("empty").unwrap().as_sequence().unwrap().is_empty());
+        assert_eq!(doc.get("multi").unwrap().as_sequence().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn missing_values_are_null() {
+        let doc = parse("a:\nb: ~\n").unwrap();
+        assert_eq!(doc.get("a").unwrap().value, Value::Null);
+        assert_eq!(doc.get("b").unwrap().value, Value::Null);
+        assert_eq!(parse("# nothing\n").unwrap().value, Value::Null);
+    }
+
+    #[test]
+    fn syntax_errors_report_line_and_column() {
+        let error = parse("- add: cube\n  width: 1\n    height: 2\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 5));
+
+        let error = parse("a: 1\na: 2\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.message.contains("duplicate"));
+
+        let error = parse("a: [1, 2\nb: 3\n").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = parse("a: [1, 2] 3\n").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 11));
+
+        let error = parse("a: 1\n\tb: 2\n").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn quoted_keys_must_be_closed_at_their_end() {
+        let doc = parse("\"a b\": 1\n'é': 2\n").unwrap();
+        assert_eq!(scalar(doc.get("a b").unwrap()), "1");
+        assert_eq!(scalar(doc.get("é").unwrap()), "2");
+
+        let error = parse("x: 0\n\"a\"é: 1\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.message.contains("malformed quoted key"));
+
+        assert!(parse("'a\": 1\n").is_err());
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
     any::Any,
     fmt,
+    hash::{Hash, Hasher},
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak},
 };
 
-use crate::{Intersection, Material, Matrix4, Point, Ray, Vector, identity_matrix, material};
+use crate::{
+    BoundingBox, Intersection, Material, Matrix4, Point, Ray, Vector, identity_matrix, material,
+};
 
+mod bvh;
 mod cone;
 mod csg;
 mod cube;
@@ -16,6 +20,7 @@ mod smooth_triangle;
 mod sphere;
 mod triangle;
 
+pub(crate) use bvh::Bvh;
 pub use cone::cone;
 pub use csg::{Csg, CsgOperation, csg};
 pub use cube::cube;
@@ -29,9 +34,32 @@ pub use triangle::{Triangle, triangle};
 pub type ShapeRef = Arc<RwLock<ShapeInner>>;
 pub type WeakShapeRef = Weak<RwLock<ShapeInner>>;
 
+/// Texture coordinates at a point on a surface, with the object-space
+/// directions in which `u` and `v` increase there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TangentFrame {
+    pub u: f32,
+    pub v: f32,
+    pub tangent: Vector,
+    pub bitangent: Vector,
+}
+
 pub trait Geometry: Send + Sync {
     fn local_intersection(&self, shape: &Shape, ray: Ray) -> Vec<Intersection>;
     fn local_normal_at(&self, point: Point, hit: Option<&Intersection>) -> Vector;
+
+    /// Returns the object-space bounds of this geometry. Geometries that do
+    /// not override this are treated as unbounded and are never culled.
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::infinite()
+    }
+
+    /// Returns the texture coordinates and object-space tangent frame at a
+    /// hit, for geometries that carry texture coordinates.
+    fn local_tangent_frame(&self, _hit: &Intersection) -> Option<TangentFrame> {
+        None
+    }
+
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -203,6 +231,26 @@ impl Shape {
         }
     }
 
+    /// Converts a direction, such as a surface tangent, from object space to
+    /// world space, recursively taking into consideration any parent objects
+    /// between the two spaces. Unlike a normal, the result is not
+    /// normalized.
+    ///
+    /// # Panics
+    /// Panics if the internal lock is poisoned.
+    #[must_use]
+    pub fn vector_to_world(&self, vector: Vector) -> Vector {
+        let inner = self.inner_ref.read().expect("shape lock poisoned");
+        let vector = inner.transform * vector;
+        drop(inner);
+
+        if let Some(parent) = self.parent() {
+            parent.vector_to_world(vector)
+        } else {
+            vector
+        }
+    }
+
     /// Computes the intersections between a ray and this shape.
     ///
     /// # Panics
@@ -233,7 +281,33 @@ impl Shape {
         self.normal_to_world(local_normal)
     }
 
+    /// Returns the bounds of this shape in its own object space.
+    ///
+    /// # Panics
+    /// Panics if the internal lock is poisoned.
+    #[must_use]
+    pub fn bounds(&self) -> BoundingBox {
+        self.inner_ref
+            .read()
+            .expect("shape lock poisoned")
+            .geometry
+            .bounds()
+    }
+
+    /// Returns the bounds of this shape in its parent's space, i.e. its
+    /// object-space bounds pushed through its transform. For a shape with no
+    /// parent this is its world-space bounds.
+    ///
+    /// # Panics
+    /// Panics if the internal lock is poisoned.
+    #[must_use]
+    pub fn parent_space_bounds(&self) -> BoundingBox {
+        let inner = self.inner_ref.read().expect("shape lock poisoned");
+        inner.geometry.bounds().transform(inner.transform)
+    }
+
     /// Adds a child shape to this group. Sets the child's parent to this shape.
+    /// Any bounding volume hierarchy previously built for the group is discarded.
     ///
     /// # Panics
     /// Panics if this shape is not a Group.
@@ -247,6 +321,64 @@ impl Shape {
 
         child.set_parent(self.downgrade());
         group.children.push(child);
+        group.bvh = None;
+    }
+
+    /// Partitions the children of this shape, and of every group nested below
+    /// it, into bounding volume hierarchies. Shapes other than groups and CSG
+    /// are left untouched.
+    ///
+    /// The hierarchy captures the children's bounds at the time it is built,
+    /// so it must be rebuilt after a child is transformed or a nested group
+    /// gains children.
+    ///
+    /// # Panics
+    /// Panics if the internal lock is poisoned.
+    pub fn build_bvh(&self) {
+        let inner = self.inner();
+        let geometry = inner.geometry.as_any();
+
+        if let Some(csg) = geometry.downcast_ref::<Csg>() {
+            csg.left.build_bvh();
+            csg.right.build_bvh();
+            return;
+        }
+
+        let Some(group) = geometry.downcast_ref::<Group>() else {
+            return;
+        };
+        for child in group.children() {
+            child.build_bvh();
+        }
+        let bvh = Bvh::build(group.children());
+        drop(inner);
+
+        let mut inner = self.inner_mut();
+        if let Some(group) = inner.geometry.as_any_mut().downcast_mut::<Group>() {
+            group.bvh = Some(bvh);
+        }
+    }
+
+    /// Calls `f` with every shape below this one that is neither a group nor
+    /// a CSG operation, depth first, or with this shape itself if it is
+    /// neither.
+    ///
+    /// # Panics
+    /// Panics if the internal lock is poisoned.
+    pub(crate) fn for_each_leaf(&self, f: &mut impl FnMut(&Shape)) {
+        let inner = self.inner();
+        let geometry = inner.geometry.as_any();
+
+        if let Some(csg) = geometry.downcast_ref::<Csg>() {
+            csg.left.for_each_leaf(f);
+            csg.right.for_each_leaf(f);
+        } else if let Some(group) = geometry.downcast_ref::<Group>() {
+            for child in group.children() {
+                child.for_each_leaf(f);
+            }
+        } else {
+            f(self);
+        }
     }
 }
 
@@ -256,6 +388,14 @@ impl PartialEq for Shape {
     }
 }
 
+impl Eq for Shape {}
+
+impl Hash for Shape {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.inner_ref).hash(state);
+    }
+}
+
 impl<G: Geometry + 'static> From<G> for Shape {
     fn from(geometry: G) -> Self {
         Shape::new(geometry)
@@ -266,16 +406,16 @@ impl<G: Geometry + 'static> From<G> for Shape {
 mod tests {
     use std::{
         any::Any,
-        f32::consts::FRAC_1_SQRT_2,
+        f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2},
         sync::{Arc, RwLock},
     };
 
     use approx::assert_relative_eq;
 
-    use super::{Geometry, Shape};
+    use super::{Geometry, Shape, plane, sphere};
     use crate::{
-        EPSILON, Intersection, Material, Point, Ray, Vector, identity_matrix, material, point, ray,
-        transform, vector,
+        BoundingBox, EPSILON, Intersection, Material, Point, Ray, Vector, identity_matrix,
+        material, point, ray, transform, vector,
     };
 
     struct TestShape {
@@ -395,6 +535,36 @@ mod tests {
         assert_relative_eq!(n.z(), -0.24254, epsilon = EPSILON);
     }
 
+    #[test]
+    fn test_shape_has_infinite_bounds_by_default() {
+        let (s, _) = test_shape();
+        assert_eq!(s.bounds(), BoundingBox::infinite());
+    }
+
+    #[test]
+    fn querying_shape_bounding_box_in_parent_space() {
+        let s = sphere()
+            .transform(transform::translation(1, -3, 5) * transform::scaling(0.5, 2, 4))
+            .build();
+        let b = s.parent_space_bounds();
+        assert_eq!(b.min, point(0.5, -5, 1));
+        assert_eq!(b.max, point(1.5, -1, 9));
+    }
+
+    #[test]
+    fn parent_space_bounds_of_infinite_shape_stay_infinite() {
+        let p = plane()
+            .transform(transform::translation(0, 2, 0) * transform::rotation_z(FRAC_PI_2))
+            .build();
+        let b = p.parent_space_bounds();
+        assert_relative_eq!(b.min.x(), 0.0, epsilon = EPSILON);
+        assert_relative_eq!(b.max.x(), 0.0, epsilon = EPSILON);
+        assert_eq!(b.min.y(), f32::NEG_INFINITY);
+        assert_eq!(b.max.y(), f32::INFINITY);
+        assert_eq!(b.min.z(), f32::NEG_INFINITY);
+        assert_eq!(b.max.z(), f32::INFINITY);
+    }
+
     #[test]
     fn shape_has_parent_attribute() {
         let (s, _) = test_shape();
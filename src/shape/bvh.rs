@@ -0,0 +1,357 @@
+use crate::{BoundingBox, Intersection, Ray, shape::Shape};
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// A bounding volume hierarchy over a slice of shapes, built with a binned
+/// surface-area heuristic. The hierarchy stores indices into the slice it was
+/// built from, so the same slice must be passed back to [`Bvh::intersect`].
+/// It also remembers which shapes that slice held so callers can tell when
+/// it has gone stale.
+pub(crate) struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+    bounds: BoundingBox,
+    shapes: Vec<Shape>,
+}
+
+enum Node {
+    Leaf {
+        bounds: BoundingBox,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Item {
+    index: usize,
+    bounds: BoundingBox,
+    centroid: [f32; 3],
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    bounds: BoundingBox,
+    count: usize,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over `shapes` using their parent-space bounds.
+    /// Shapes with infinite bounds are kept aside and tested against every
+    /// ray; shapes with empty bounds are dropped since they can never be hit.
+    #[must_use]
+    pub(crate) fn build(shapes: &[Shape]) -> Self {
+        let mut items = vec![];
+        let mut unbounded = vec![];
+        let mut bounds = BoundingBox::empty();
+
+        for (index, shape) in shapes.iter().enumerate() {
+            let shape_bounds = shape.parent_space_bounds();
+            bounds.add_box(&shape_bounds);
+
+            if shape_bounds.is_empty() {
+                continue;
+            }
+
+            if shape_bounds.is_finite() {
+                let centroid = shape_bounds.centroid();
+                items.push(Item {
+                    index,
+                    bounds: shape_bounds,
+                    centroid: [centroid.x(), centroid.y(), centroid.z()],
+                });
+            } else {
+                unbounded.push(index);
+            }
+        }
+
+        let mut bvh = Bvh {
+            nodes: vec![],
+            indices: Vec::with_capacity(items.len()),
+            unbounded,
+            bounds,
+            shapes: shapes.to_vec(),
+        };
+
+        if !items.is_empty() {
+            bvh.build_node(&mut items);
+        }
+
+        bvh
+    }
+
+    /// Returns the combined parent-space bounds of every shape in the hierarchy.
+    #[must_use]
+    pub(crate) fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    /// Returns `true` if `shapes` holds the same shapes, in the same order,
+    /// as the slice the hierarchy was built from.
+    #[must_use]
+    pub(crate) fn is_built_from(&self, shapes: &[Shape]) -> bool {
+        self.shapes == shapes
+    }
+
+    fn build_node(&mut self, items: &mut [Item]) -> usize {
+        let bounds = items.iter().fold(BoundingBox::empty(), |mut acc, item| {
+            acc.add_box(&item.bounds);
+            acc
+        });
+
+        match find_split(items, &bounds) {
+            Some(mid) => {
+                let node = self.nodes.len();
+                self.nodes.push(Node::Interior {
+                    bounds,
+                    left: 0,
+                    right: 0,
+                });
+
+                let (left_items, right_items) = items.split_at_mut(mid);
+                let left = self.build_node(left_items);
+                let right = self.build_node(right_items);
+                self.nodes[node] = Node::Interior {
+                    bounds,
+                    left,
+                    right,
+                };
+                node
+            }
+            None => {
+                let start = self.indices.len();
+                self.indices.extend(items.iter().map(|item| item.index));
+                self.nodes.push(Node::Leaf {
+                    bounds,
+                    start,
+                    count: items.len(),
+                });
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Intersects `ray` with every shape whose bounds it passes through.
+    /// The returned intersections are not sorted.
+    #[must_use]
+    pub(crate) fn intersect(&self, shapes: &[Shape], ray: Ray) -> Vec<Intersection> {
+        debug_assert_eq!(self.shapes.len(), shapes.len(), "bvh is stale");
+
+        let mut intersections: Vec<Intersection> = self
+            .unbounded
+            .iter()
+            .flat_map(|&index| shapes[index].intersect(ray))
+            .collect();
+
+        if self.nodes.is_empty() {
+            return intersections;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.bounds().intersects(ray) {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { start, count, .. } => {
+                    for &index in &self.indices[start..start + count] {
+                        intersections.extend(shapes[index].intersect(ray));
+                    }
+                }
+                Node::Interior { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+
+        intersections
+    }
+}
+
+/// Partitions `items` in place along the cheapest surface-area-heuristic
+/// split and returns the index of the first item on the right-hand side,
+/// or `None` if the items should stay together in a leaf.
+fn find_split(items: &mut [Item], bounds: &BoundingBox) -> Option<usize> {
+    if items.len() <= 1 {
+        return None;
+    }
+
+    let mut centroid_min = [f32::INFINITY; 3];
+    let mut centroid_max = [f32::NEG_INFINITY; 3];
+    for item in items.iter() {
+        for axis in 0..3 {
+            centroid_min[axis] = centroid_min[axis].min(item.centroid[axis]);
+            centroid_max[axis] = centroid_max[axis].max(item.centroid[axis]);
+        }
+    }
+
+    let axis = (0..3)
+        .max_by(|&a, &b| {
+            let extent_a = centroid_max[a] - centroid_min[a];
+            let extent_b = centroid_max[b] - centroid_min[b];
+            extent_a.total_cmp(&extent_b)
+        })
+        .unwrap_or(0);
+    let extent = centroid_max[axis] - centroid_min[axis];
+    if extent <= 0.0 {
+        return None;
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let bin_for = |item: &Item| {
+        let offset = (item.centroid[axis] - centroid_min[axis]) / extent;
+        ((offset * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)
+    };
+
+    let mut bins = [Bin::default(); BIN_COUNT];
+    for item in items.iter() {
+        let bin = &mut bins[bin_for(item)];
+        bin.bounds.add_box(&item.bounds);
+        bin.count += 1;
+    }
+
+    let parent_area = bounds.surface_area();
+    let mut best: Option<(usize, f32)> = None;
+    for split in 1..BIN_COUNT {
+        let (left, right) = bins.split_at(split);
+        let (left_bounds, left_count) = merge_bins(left);
+        let (right_bounds, right_count) = merge_bins(right);
+        if left_count == 0 || right_count == 0 {
+            continue;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let cost = TRAVERSAL_COST
+            + (left_bounds.surface_area() * left_count as f32
+                + right_bounds.surface_area() * right_count as f32)
+                / parent_area;
+
+        if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+            best = Some((split, cost));
+        }
+    }
+
+    let (split, cost) = best?;
+    #[allow(clippy::cast_precision_loss)]
+    let leaf_cost = items.len() as f32;
+    if items.len() <= MAX_LEAF_SIZE && leaf_cost <= cost {
+        return None;
+    }
+
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if bin_for(&items[i]) < split {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    Some(mid)
+}
+
+fn merge_bins(bins: &[Bin]) -> (BoundingBox, usize) {
+    bins.iter()
+        .fold((BoundingBox::empty(), 0), |(mut bounds, count), bin| {
+            bounds.add_box(&bin.bounds);
+            (bounds, count + bin.count)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{point, ray, shape::plane, sphere, transform, vector};
+
+    fn row_of_spheres(count: usize) -> Vec<Shape> {
+        (0..count)
+            .map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let x = i as f32 * 3.0;
+                sphere().transform(transform::translation(x, 0, 0)).build()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_bounds_contain_all_shapes() {
+        let shapes = row_of_spheres(3);
+        let bvh = Bvh::build(&shapes);
+        assert_eq!(bvh.bounds().min, point(-1, -1, -1));
+        assert_eq!(bvh.bounds().max, point(7, 1, 1));
+    }
+
+    #[test]
+    fn bvh_splits_distant_shapes() {
+        let shapes = row_of_spheres(16);
+        let bvh = Bvh::build(&shapes);
+        assert!(bvh.nodes.len() > 1);
+        assert!(matches!(bvh.nodes[0], Node::Interior { .. }));
+        assert_eq!(bvh.indices.len(), 16);
+    }
+
+    #[test]
+    fn bvh_keeps_single_shape_in_leaf() {
+        let shapes = row_of_spheres(1);
+        let bvh = Bvh::build(&shapes);
+        assert_eq!(bvh.nodes.len(), 1);
+        assert!(matches!(bvh.nodes[0], Node::Leaf { count: 1, .. }));
+    }
+
+    #[test]
+    fn bvh_intersect_only_tests_shapes_along_ray() {
+        let shapes = row_of_spheres(16);
+        let bvh = Bvh::build(&shapes);
+        let r = ray(point(9, 0, -5), vector(0, 0, 1));
+        let xs = bvh.intersect(&shapes, r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].object, shapes[3]);
+        assert_eq!(xs[1].object, shapes[3]);
+    }
+
+    #[test]
+    fn bvh_matches_brute_force_intersection() {
+        let shapes = row_of_spheres(16);
+        let bvh = Bvh::build(&shapes);
+        let r = ray(point(-5, 0.5, 0), vector(1, 0, 0));
+        let xs = bvh.intersect(&shapes, r);
+        let expected: Vec<Intersection> = shapes.iter().flat_map(|s| s.intersect(r)).collect();
+        assert_eq!(xs.len(), expected.len());
+        assert_eq!(xs.len(), 32);
+    }
+
+    #[test]
+    fn bvh_always_tests_unbounded_shapes() {
+        let mut shapes = row_of_spheres(8);
+        shapes.push(plane().transform(transform::translation(0, -1, 0)).build());
+        let bvh = Bvh::build(&shapes);
+        assert_eq!(bvh.unbounded, vec![8]);
+
+        let r = ray(point(100, 5, 0), vector(0, -1, 0));
+        let xs = bvh.intersect(&shapes, r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].object, shapes[8]);
+    }
+}
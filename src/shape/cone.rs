@@ -3,8 +3,9 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, bounding_box, identity_matrix, material,
     matrix::Matrix4,
+    point,
     point::Point,
     ray::Ray,
     shape::{Geometry, Shape},
@@ -153,6 +154,14 @@ impl Geometry for Cone {
         }
     }
 
+    fn bounds(&self) -> BoundingBox {
+        let limit = self.minimum.abs().max(self.maximum.abs());
+        bounding_box(
+            point(-limit, self.minimum, -limit),
+            point(limit, self.maximum, limit),
+        )
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -273,4 +282,23 @@ mod tests {
         assert_relative_eq!(n.y(), 1.0 / sqrt2, epsilon = EPSILON);
         assert_relative_eq!(n.z(), 0.0, epsilon = EPSILON);
     }
+
+    #[test]
+    fn unbounded_cone_has_bounding_box() {
+        let shape = cone().build();
+        let b = shape.bounds();
+        assert_eq!(
+            b.min,
+            point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)
+        );
+        assert_eq!(b.max, point(f32::INFINITY, f32::INFINITY, f32::INFINITY));
+    }
+
+    #[test]
+    fn bounded_cone_has_bounding_box() {
+        let shape = cone().minimum(-5.0).maximum(3.0).build();
+        let b = shape.bounds();
+        assert_eq!(b.min, point(-5, -5, -5));
+        assert_eq!(b.max, point(5, 3, 5));
+    }
 }
@@ -3,7 +3,7 @@ use std::any::Any;
 use ord_subset::OrdSubsetSliceExt;
 
 use crate::{
-    Intersection, Point, Ray, Vector,
+    BoundingBox, Intersection, Point, Ray, Vector,
     shape::{Geometry, Group, Shape},
 };
 
@@ -49,6 +49,12 @@ impl Geometry for Csg {
         panic!("CSG shapes delegate normals to children")
     }
 
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = self.left.parent_space_bounds();
+        bounds.add_box(&self.right.parent_space_bounds());
+        bounds
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -329,4 +335,16 @@ mod tests {
         };
         csg_shape.local_normal_at(point(0, 0, 0), None);
     }
+
+    #[test]
+    fn csg_has_bounding_box_containing_its_children() {
+        let left = sphere().build();
+        let right = sphere()
+            .transform(transform::translation(2.0, 3.0, 4.0))
+            .build();
+        let shape = csg(CsgOperation::Difference, &left, &right);
+        let b = shape.bounds();
+        assert_eq!(b.min, point(-1, -1, -1));
+        assert_eq!(b.max, point(3, 4, 5));
+    }
 }
@@ -3,8 +3,9 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, bounding_box, identity_matrix, material,
     matrix::Matrix4,
+    point,
     point::Point,
     ray::Ray,
     shape::{Geometry, Shape},
@@ -90,6 +91,10 @@ impl Geometry for Cube {
         }
     }
 
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(point(-1, -1, -1), point(1, 1, 1))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -278,4 +283,12 @@ mod tests {
         let n = c.normal_at(point(-1.0, -1.0, -1.0));
         assert_eq!(n, vector(-1, 0, 0));
     }
+
+    #[test]
+    fn cube_has_bounding_box() {
+        let c = cube().build();
+        let b = c.inner().geometry.bounds();
+        assert_eq!(b.min, point(-1, -1, -1));
+        assert_eq!(b.max, point(1, 1, 1));
+    }
 }
@@ -3,8 +3,9 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, bounding_box, identity_matrix, material,
     matrix::Matrix4,
+    point,
     point::Point,
     ray::Ray,
     shape::{Geometry, Shape},
@@ -137,6 +138,10 @@ impl Geometry for Cylinder {
         }
     }
 
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(point(-1, self.minimum, -1), point(1, self.maximum, 1))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -402,4 +407,21 @@ mod tests {
         let n = cyl.normal_at(point(0.0, 2.0, 0.5));
         assert_eq!(n, vector(0, 1, 0));
     }
+
+    #[test]
+    fn unbounded_cylinder_has_bounding_box() {
+        let c = cylinder().build();
+        let b = c.bounds();
+        assert_eq!(b.min, point(-1, f32::NEG_INFINITY, -1));
+        assert_eq!(b.max, point(1, f32::INFINITY, 1));
+        assert!(!b.is_finite());
+    }
+
+    #[test]
+    fn bounded_cylinder_has_bounding_box() {
+        let c = cylinder().minimum(-5.0).maximum(3.0).build();
+        let b = c.bounds();
+        assert_eq!(b.min, point(-1, -5, -1));
+        assert_eq!(b.max, point(1, 3, 1));
+    }
 }
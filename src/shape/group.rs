@@ -4,22 +4,26 @@ use bon::builder;
 use ord_subset::OrdSubsetSliceExt;
 
 use crate::{
-    Intersection, Material, Point, Ray, Vector, identity_matrix, material,
+    BoundingBox, Intersection, Material, Point, Ray, Vector, identity_matrix, material,
     matrix::Matrix4,
-    shape::{Geometry, Shape},
+    shape::{Bvh, Geometry, Shape},
 };
 
 pub struct Group {
     pub children: Vec<Shape>,
+    pub(crate) bvh: Option<Bvh>,
 }
 
 impl Geometry for Group {
     fn local_intersection(&self, _shape: &Shape, ray: Ray) -> Vec<Intersection> {
-        let mut intersections: Vec<Intersection> = self
-            .children
-            .iter()
-            .flat_map(|child| child.intersect(ray))
-            .collect();
+        let mut intersections: Vec<Intersection> = match &self.bvh {
+            Some(bvh) => bvh.intersect(&self.children, ray),
+            None => self
+                .children
+                .iter()
+                .flat_map(|child| child.intersect(ray))
+                .collect(),
+        };
         intersections.ord_subset_sort_by_key(|i| i.time);
         intersections
     }
@@ -28,6 +32,19 @@ impl Geometry for Group {
         panic!("Groups do not have surface normals")
     }
 
+    fn bounds(&self) -> BoundingBox {
+        if let Some(bvh) = &self.bvh {
+            return bvh.bounds();
+        }
+
+        self.children
+            .iter()
+            .fold(BoundingBox::empty(), |mut bounds, child| {
+                bounds.add_box(&child.parent_space_bounds());
+                bounds
+            })
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -43,7 +60,10 @@ pub fn group(
     #[builder(default = identity_matrix())] transform: Matrix4,
     #[builder(default = material(), into)] material: Material,
 ) -> Shape {
-    let shape = Shape::new(Group { children: vec![] });
+    let shape = Shape::new(Group {
+        children: vec![],
+        bvh: None,
+    });
     shape.set_transform(transform);
     shape.set_material(material);
     shape
@@ -59,6 +79,11 @@ impl Group {
     pub fn is_empty(&self) -> bool {
         self.children.is_empty()
     }
+
+    #[must_use]
+    pub fn has_bvh(&self) -> bool {
+        self.bvh.is_some()
+    }
 }
 
 #[cfg(test)]
@@ -68,7 +93,11 @@ mod tests {
     use approx::assert_relative_eq;
 
     use super::*;
-    use crate::{EPSILON, point, ray, shape::sphere, transform, vector};
+    use crate::{
+        EPSILON, point, ray,
+        shape::{cube, sphere},
+        transform, vector,
+    };
 
     #[test]
     fn creating_new_group() {
@@ -194,7 +223,117 @@ mod tests {
     #[test]
     #[should_panic(expected = "Groups do not have surface normals")]
     fn group_local_normal_at_panics() {
-        let group = Group { children: vec![] };
+        let group = Group {
+            children: vec![],
+            bvh: None,
+        };
         group.local_normal_at(point(0, 0, 0), None);
     }
+
+    #[test]
+    fn group_has_bounding_box_containing_children() {
+        let s = sphere()
+            .transform(transform::translation(2, 5, -3) * transform::scaling(2, 2, 2))
+            .build();
+        let c = cube()
+            .transform(transform::translation(-4, -1, 4) * transform::scaling(0.5, 1, 0.5))
+            .build();
+        let g = group().build();
+        g.add_child(s);
+        g.add_child(c);
+
+        let b = g.inner().geometry.bounds();
+        assert_relative_eq!(b.min.x(), -4.5, epsilon = EPSILON);
+        assert_relative_eq!(b.min.y(), -2.0, epsilon = EPSILON);
+        assert_relative_eq!(b.min.z(), -5.0, epsilon = EPSILON);
+        assert_relative_eq!(b.max.x(), 4.0, epsilon = EPSILON);
+        assert_relative_eq!(b.max.y(), 7.0, epsilon = EPSILON);
+        assert_relative_eq!(b.max.z(), 4.5, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn building_bvh_for_group() {
+        let g = group().build();
+        for i in 0..10 {
+            g.add_child(
+                sphere()
+                    .transform(transform::translation(i * 3, 0, 0))
+                    .build(),
+            );
+        }
+        g.build_bvh();
+
+        let inner = g.inner();
+        let group_geom = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<Group>()
+            .expect("Should be a Group");
+        assert!(group_geom.has_bvh());
+        assert_eq!(group_geom.children().len(), 10);
+    }
+
+    #[test]
+    fn building_bvh_recurses_into_child_groups() {
+        let outer = group().build();
+        let inner_group = group().build();
+        inner_group.add_child(sphere().build());
+        outer.add_child(inner_group.clone());
+        outer.build_bvh();
+
+        let inner = inner_group.inner();
+        let group_geom = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<Group>()
+            .expect("Should be a Group");
+        assert!(group_geom.has_bvh());
+    }
+
+    #[test]
+    fn adding_child_discards_bvh() {
+        let g = group().build();
+        g.add_child(sphere().build());
+        g.build_bvh();
+        g.add_child(sphere().build());
+
+        let inner = g.inner();
+        let group_geom = inner
+            .geometry
+            .as_any()
+            .downcast_ref::<Group>()
+            .expect("Should be a Group");
+        assert!(!group_geom.has_bvh());
+    }
+
+    #[test]
+    fn intersecting_group_with_bvh_matches_brute_force() {
+        let g = group().transform(transform::rotation_y(0.3)).build();
+        for x in 0..6 {
+            for z in 0..6 {
+                g.add_child(
+                    sphere()
+                        .transform(
+                            transform::translation(x * 2, 0, z * 2)
+                                * transform::scaling(0.8, 0.8, 0.8),
+                        )
+                        .build(),
+                );
+            }
+        }
+
+        let rays = [
+            ray(point(-5, 0, -5), vector(1, 0, 1).normalize()),
+            ray(point(4, 10, 4), vector(0, -1, 0)),
+            ray(point(-3, 0.2, 3), vector(1, 0, 0)),
+            ray(point(50, 50, 50), vector(0, 1, 0)),
+        ];
+        let unaccelerated: Vec<_> = rays.iter().map(|&r| g.intersect(r)).collect();
+
+        g.build_bvh();
+        for (r, expected) in rays.iter().zip(unaccelerated) {
+            let xs = g.intersect(*r);
+            assert_eq!(xs, expected);
+        }
+    }
 }
@@ -3,8 +3,9 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, bounding_box, identity_matrix, material,
     matrix::Matrix4,
+    point,
     point::Point,
     ray::Ray,
     shape::{Geometry, Shape},
@@ -44,6 +45,13 @@ impl Geometry for Plane {
         vector(0, 1, 0)
     }
 
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(
+            point(f32::NEG_INFINITY, 0, f32::NEG_INFINITY),
+            point(f32::INFINITY, 0, f32::INFINITY),
+        )
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -105,4 +113,12 @@ mod tests {
         assert_relative_eq!(xs[0].time, 1.0, epsilon = EPSILON);
         assert_eq!(xs[0].object, p);
     }
+
+    #[test]
+    fn plane_has_bounding_box() {
+        let p = plane().build();
+        let b = p.bounds();
+        assert_eq!(b.min, point(f32::NEG_INFINITY, 0, f32::NEG_INFINITY));
+        assert_eq!(b.max, point(f32::INFINITY, 0, f32::INFINITY));
+    }
 }
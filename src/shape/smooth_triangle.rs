@@ -3,11 +3,12 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, intersection_with_uv, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, identity_matrix, intersection_with_uv,
+    material,
     matrix::Matrix4,
     point::Point,
     ray::Ray,
-    shape::{Geometry, Shape},
+    shape::{Geometry, Shape, TangentFrame, triangle::tangent_frame},
 };
 
 #[builder(finish_fn = build)]
@@ -21,6 +22,7 @@ pub fn smooth_triangle(
     #[builder(start_fn)] n3: Vector,
     #[builder(default = identity_matrix())] transform: Matrix4,
     #[builder(default = material(), into)] material: Material,
+    uvs: Option<[(f32, f32); 3]>,
 ) -> Shape {
     let e1 = p2 - p1;
     let e2 = p3 - p1;
@@ -33,6 +35,7 @@ pub fn smooth_triangle(
         n3,
         e1,
         e2,
+        uvs,
     });
     shape.set_transform(transform);
     shape.set_material(material);
@@ -48,6 +51,8 @@ pub struct SmoothTriangle {
     pub n3: Vector,
     pub e1: Vector,
     pub e2: Vector,
+    /// Texture coordinates at `p1`, `p2` and `p3`.
+    pub uvs: Option<[(f32, f32); 3]>,
 }
 
 impl Geometry for SmoothTriangle {
@@ -86,6 +91,18 @@ impl Geometry for SmoothTriangle {
         self.n2 * u + self.n3 * v + self.n1 * (1.0 - u - v)
     }
 
+    fn local_tangent_frame(&self, hit: &Intersection) -> Option<TangentFrame> {
+        tangent_frame(self.e1, self.e2, self.uvs?, hit)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -172,4 +189,12 @@ mod tests {
         assert_relative_eq!(comps.normalv.y(), 0.83205, epsilon = EPSILON);
         assert_relative_eq!(comps.normalv.z(), 0.0, epsilon = EPSILON);
     }
+
+    #[test]
+    fn smooth_triangle_has_bounding_box() {
+        let tri = test_smooth_triangle();
+        let b = tri.inner().geometry.bounds();
+        assert_eq!(b.min, point(-1, 0, 0));
+        assert_eq!(b.max, point(1, 1, 0));
+    }
 }
@@ -3,8 +3,9 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    Intersection, Material, ORIGIN, identity_matrix, material,
+    BoundingBox, Intersection, Material, ORIGIN, bounding_box, identity_matrix, material,
     matrix::Matrix4,
+    point,
     point::Point,
     ray::Ray,
     shape::{Geometry, Shape},
@@ -66,6 +67,10 @@ impl Geometry for Sphere {
         point - ORIGIN
     }
 
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(point(-1, -1, -1), point(1, 1, 1))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -259,4 +264,12 @@ mod tests {
         assert_relative_eq!(s.inner().material.transparency, 1.0, epsilon = EPSILON);
         assert_relative_eq!(s.inner().material.refractive_index, 1.5, epsilon = EPSILON);
     }
+
+    #[test]
+    fn sphere_has_bounding_box() {
+        let s = sphere().build();
+        let b = s.inner().geometry.bounds();
+        assert_eq!(b.min, point(-1, -1, -1));
+        assert_eq!(b.max, point(1, 1, 1));
+    }
 }
@@ -3,11 +3,12 @@ use std::any::Any;
 use bon::builder;
 
 use crate::{
-    EPSILON, Intersection, Material, Vector, identity_matrix, intersection, material,
+    BoundingBox, EPSILON, Intersection, Material, Vector, identity_matrix, intersection_with_uv,
+    material,
     matrix::Matrix4,
     point::Point,
     ray::Ray,
-    shape::{Geometry, Shape},
+    shape::{Geometry, Shape, TangentFrame},
 };
 
 #[builder(finish_fn = build)]
@@ -18,6 +19,7 @@ pub fn triangle(
     #[builder(start_fn)] p3: Point,
     #[builder(default = identity_matrix())] transform: Matrix4,
     #[builder(default = material(), into)] material: Material,
+    uvs: Option<[(f32, f32); 3]>,
 ) -> Shape {
     let e1 = p2 - p1;
     let e2 = p3 - p1;
@@ -29,6 +31,7 @@ pub fn triangle(
         e1,
         e2,
         normal,
+        uvs,
     });
     shape.set_transform(transform);
     shape.set_material(material);
@@ -42,6 +45,36 @@ pub struct Triangle {
     pub e1: Vector,
     pub e2: Vector,
     pub normal: Vector,
+    /// Texture coordinates at `p1`, `p2` and `p3`.
+    pub uvs: Option<[(f32, f32); 3]>,
+}
+
+/// Interpolates per-vertex texture coordinates at a hit's barycentric `u`
+/// and `v`, and finds the directions along the edges `e1` and `e2` in which
+/// they increase. Returns `None` if the hit has no barycentrics or the
+/// texture coordinates do not span an area.
+pub(crate) fn tangent_frame(
+    e1: Vector,
+    e2: Vector,
+    uvs: [(f32, f32); 3],
+    hit: &Intersection,
+) -> Option<TangentFrame> {
+    let (u, v) = (hit.u?, hit.v?);
+    let [(u1, v1), (u2, v2), (u3, v3)] = uvs;
+    let (du1, dv1) = (u2 - u1, v2 - v1);
+    let (du2, dv2) = (u3 - u1, v3 - v1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < EPSILON {
+        return None;
+    }
+
+    let w = 1.0 - u - v;
+    Some(TangentFrame {
+        u: u1 * w + u2 * u + u3 * v,
+        v: v1 * w + v2 * u + v3 * v,
+        tangent: (e1 * dv2 - e2 * dv1) / det,
+        bitangent: (e2 * du1 - e1 * du2) / det,
+    })
 }
 
 impl Geometry for Triangle {
@@ -69,13 +102,25 @@ impl Geometry for Triangle {
         }
 
         let t = f * self.e2.dot(&origin_cross_e1);
-        vec![intersection(t, shape.clone())]
+        vec![intersection_with_uv(t, shape.clone(), u, v)]
     }
 
     fn local_normal_at(&self, _point: Point, _hit: Option<&Intersection>) -> Vector {
         self.normal
     }
 
+    fn local_tangent_frame(&self, hit: &Intersection) -> Option<TangentFrame> {
+        tangent_frame(self.e1, self.e2, self.uvs?, hit)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -177,4 +222,31 @@ mod tests {
         assert_eq!(xs.len(), 1);
         assert_relative_eq!(xs[0].time, 2.0, epsilon = EPSILON);
     }
+
+    #[test]
+    fn triangle_has_bounding_box() {
+        let t = triangle(point(-3, 7, 2), point(6, 2, -4), point(2, -1, -1)).build();
+        let b = t.inner().geometry.bounds();
+        assert_eq!(b.min, point(-3, -1, -4));
+        assert_eq!(b.max, point(6, 7, 2));
+    }
+
+    #[test]
+    fn texture_coordinates_give_a_tangent_frame() {
+        let t = triangle(point(0, 1, 0), point(-1, 0, 0), point(1, 0, 0))
+            .uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .build();
+        let xs = t.intersect(ray(point(0.0, 0.5, -2.0), vector(0, 0, 1)));
+        assert_relative_eq!(xs[0].u.unwrap(), 0.25, epsilon = EPSILON);
+        assert_relative_eq!(xs[0].v.unwrap(), 0.25, epsilon = EPSILON);
+
+        let frame = t.inner().geometry.local_tangent_frame(&xs[0]).unwrap();
+        assert_relative_eq!(frame.u, 0.5, epsilon = EPSILON);
+        assert_relative_eq!(frame.v, 0.5, epsilon = EPSILON);
+        assert_eq!(frame.tangent, vector(2, 0, 0));
+        assert_eq!(frame.bitangent, vector(0, 1, 0));
+
+        let plain = test_triangle();
+        assert_eq!(plain.inner().geometry.local_tangent_frame(&xs[0]), None);
+    }
 }
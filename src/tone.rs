@@ -0,0 +1,185 @@
+use bon::builder;
+
+use crate::{Color, clamp, color};
+
+/// Curve that compresses linear radiance into the displayable `0.0..=1.0`
+/// range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneOperator {
+    /// Cuts off every channel above `1.0`.
+    #[default]
+    Clamp,
+    /// Maps each channel `c` to `c / (1 + c)`, which never reaches white.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, with a gentle toe
+    /// and shoulder.
+    Aces,
+}
+
+impl ToneOperator {
+    fn apply(self, c: f32) -> f32 {
+        let c = c.max(0.0);
+        match self {
+            ToneOperator::Clamp => c.min(1.0),
+            ToneOperator::Reinhard => c / (1.0 + c),
+            ToneOperator::Aces => clamp(
+                (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+                0.0,
+                1.0,
+            ),
+        }
+    }
+}
+
+/// Post-processing that turns linear radiance into display values: an
+/// exposure adjustment in stops, a tone curve, and optionally the sRGB
+/// transfer function.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub exposure: f32,
+    pub operator: ToneOperator,
+    pub srgb: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        tone_mapping().build()
+    }
+}
+
+#[must_use]
+#[builder(finish_fn = build)]
+pub fn tone_mapping(
+    #[builder(default)] exposure: f32,
+    #[builder(default)] operator: ToneOperator,
+    #[builder(default = true)] srgb: bool,
+) -> ToneMapping {
+    ToneMapping {
+        exposure,
+        operator,
+        srgb,
+    }
+}
+
+impl ToneMapping {
+    #[must_use]
+    pub fn apply(&self, pixel: Color) -> Color {
+        let scale = self.exposure.exp2();
+        let map = |c: f32| {
+            let mapped = self.operator.apply(c * scale);
+            if self.srgb {
+                linear_to_srgb(mapped)
+            } else {
+                mapped
+            }
+        };
+
+        color(map(pixel.red()), map(pixel.green()), map(pixel.blue()))
+    }
+}
+
+/// Applies the sRGB transfer function to a linear value in `0.0..=1.0`.
+#[must_use]
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverts [`linear_to_srgb`], turning an encoded value back into a linear one.
+#[must_use]
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::EPSILON;
+
+    fn linear(operator: ToneOperator) -> ToneMapping {
+        tone_mapping().operator(operator).srgb(false).build()
+    }
+
+    #[test]
+    fn default_tone_mapping_clamps_and_encodes_srgb() {
+        let t = ToneMapping::default();
+        assert_relative_eq!(t.exposure, 0.0);
+        assert_eq!(t.operator, ToneOperator::Clamp);
+        assert!(t.srgb);
+
+        let c = t.apply(color(2, 0.5, -1));
+        assert_relative_eq!(c.red(), 1.0, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.735_357, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let t = tone_mapping()
+            .exposure(-2.0)
+            .operator(ToneOperator::Clamp)
+            .srgb(false)
+            .build();
+        let c = t.apply(color(2, 0.5, 4));
+        assert_relative_eq!(c.red(), 0.5, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.125, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn reinhard_compresses_highlights() {
+        let c = linear(ToneOperator::Reinhard).apply(color(1, 3, 0));
+        assert_relative_eq!(c.red(), 0.5, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.75, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn aces_curve() {
+        let c = linear(ToneOperator::Aces).apply(color(0, 0.18, 100));
+        assert_relative_eq!(c.red(), 0.0, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.266_895, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for operator in [
+            ToneOperator::Clamp,
+            ToneOperator::Reinhard,
+            ToneOperator::Aces,
+        ] {
+            let mut previous = 0.0;
+            for step in 0..200u8 {
+                let value = operator.apply(f32::from(step) * 0.1);
+                assert!(value >= previous && value <= 1.0);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_transfer_function() {
+        assert_relative_eq!(linear_to_srgb(0.0), 0.0);
+        assert_relative_eq!(linear_to_srgb(0.001), 0.012_92, epsilon = EPSILON);
+        assert_relative_eq!(linear_to_srgb(0.5), 0.735_357, epsilon = EPSILON);
+        assert_relative_eq!(linear_to_srgb(1.0), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for step in 0..=100u8 {
+            let c = f32::from(step) / 100.0;
+            assert_relative_eq!(srgb_to_linear(linear_to_srgb(c)), c, epsilon = EPSILON);
+        }
+    }
+}
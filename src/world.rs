@@ -1,19 +1,25 @@
+use std::collections::HashMap;
+
 use approx::relative_eq;
 use bon::Builder;
 use ord_subset::OrdSubsetSliceExt;
 
 use crate::{
-    Color, EPSILON, Intersection, Material, Point, PointLight, Ray, Shape, color,
+    Color, EPSILON, Intersection, Light, Material, Point, Ray, Shape, TraceDepth,
+    aov::AovSample,
+    color,
     color::{BLACK, WHITE},
     hit,
     intersection::{Computations, schlick},
-    point, point_light, ray, sphere, transform,
+    point, point_light, ray,
+    shape::Bvh,
+    sphere, transform,
 };
 
 #[must_use]
 pub fn default_world() -> World {
     World::builder()
-        .lights(vec![point_light(point(-10, 10, -10), WHITE)])
+        .lights(vec![point_light(point(-10, 10, -10), WHITE).into()])
         .objects(vec![
             sphere()
                 .material(
@@ -30,27 +36,73 @@ pub fn default_world() -> World {
         .build()
 }
 
+/// The objects and lights making up a scene.
+///
+/// Worlds loaded through `Scene` come with their bounding volume hierarchy
+/// already built. Worlds assembled by hand do not, and neither `intersect`
+/// nor `Camera::render` builds one, so call [`World::build_bvh`] before
+/// rendering or every ray is tested against every object.
 #[derive(Builder, Default)]
 pub struct World {
-    pub lights: Vec<PointLight>,
+    pub lights: Vec<Box<dyn Light>>,
     pub objects: Vec<Shape>,
+    #[builder(skip)]
+    bvh: Option<Bvh>,
 }
 
 impl World {
+    /// Partitions the world's objects, and the children of any groups among
+    /// them, into bounding volume hierarchies so that `intersect` only tests
+    /// objects whose bounds a ray passes through.
+    ///
+    /// The hierarchy must be rebuilt after objects are transformed. Adding,
+    /// removing, replacing or reordering objects discards it: `intersect`
+    /// tests every object until `build_bvh` is called again.
+    pub fn build_bvh(&mut self) {
+        for object in &self.objects {
+            object.build_bvh();
+        }
+        self.bvh = Some(Bvh::build(&self.objects));
+    }
+
+    /// Numbers every shape in `objects` that is not a group or CSG
+    /// operation, counting from 1 depth first, so that each primitive inside
+    /// a group or CSG operation has an ID of its own. The IDs follow the
+    /// current contents of `objects`, so they change when objects are added,
+    /// removed or reordered.
+    #[must_use]
+    pub fn object_ids(&self) -> HashMap<Shape, u32> {
+        let mut ids = HashMap::new();
+        let mut next_id = 1u32;
+        for object in &self.objects {
+            object.for_each_leaf(&mut |leaf| {
+                ids.entry(leaf.clone()).or_insert_with(|| {
+                    let id = next_id;
+                    next_id = next_id.saturating_add(1);
+                    id
+                });
+            });
+        }
+        ids
+    }
+
     #[must_use]
     pub fn intersect(&self, ray: Ray) -> Vec<Intersection> {
-        let mut xs = self
-            .objects
-            .iter()
-            .flat_map(|o| o.intersect(ray))
-            .collect::<Vec<Intersection>>();
+        let mut xs = match &self.bvh {
+            Some(bvh) if bvh.is_built_from(&self.objects) => bvh.intersect(&self.objects, ray),
+            _ => self
+                .objects
+                .iter()
+                .flat_map(|o| o.intersect(ray))
+                .collect::<Vec<Intersection>>(),
+        };
 
         xs.ord_subset_sort_by_key(|i| i.time);
         xs
     }
 
     #[must_use]
-    pub fn shade_hit(&self, comps: &Computations, remaining: usize) -> Color {
+    pub fn shade_hit(&self, comps: &Computations, depth: impl Into<TraceDepth>) -> Color {
         if self.lights.is_empty() {
             return BLACK;
         }
@@ -64,21 +116,13 @@ impl World {
         let ambient = base_color * material.ambient;
 
         let surface = self.lights.iter().fold(ambient, |acc, light| {
-            let shadowed = self.is_shadowed_for_light(comps.over_point, light);
-            let contribution = material.lighting_contribution(
-                &comps.object,
-                light,
-                comps.over_point,
-                comps.eyev,
-                comps.normalv,
-                shadowed,
-            );
-            acc + contribution
+            acc + self.light_contribution(comps, material, light.as_ref())
         });
         drop(inner);
 
-        let reflected = self.reflected_color(comps, remaining);
-        let refracted = self.refracted_color(comps, remaining);
+        let depth = depth.into();
+        let reflected = self.reflected_color(comps, depth);
+        let refracted = self.refracted_color(comps, depth);
 
         let inner = comps.object.inner();
         let material = &inner.material;
@@ -90,11 +134,54 @@ impl World {
         }
     }
 
+    /// Returns the diffuse and specular light that `light` casts on the hit
+    /// in `comps`, the same as `Material::lighting_contribution` given
+    /// `intensity_at`, but visiting the light's samples only once.
+    fn light_contribution(
+        &self,
+        comps: &Computations,
+        material: &Material,
+        light: &dyn Light,
+    ) -> Color {
+        let point = comps.over_point;
+        let shading =
+            material.sample_shading(&comps.object, light, point, comps.eyev, comps.normalv);
+        let mut sum = BLACK;
+        let mut count = 0usize;
+        let mut visible = 0usize;
+        light.for_each_sample(point, &mut |sample| {
+            count += 1;
+            if !self.is_occluded(point, sample, light.shadow_distance(point, sample)) {
+                visible += 1;
+            }
+            sum = sum + shading.at(sample);
+        });
+        if visible == 0 {
+            return BLACK;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let scale = visible as f32 / (count * count) as f32;
+        sum * scale
+    }
+
+    /// Returns `true` if `point` receives no light at all from `light`.
     #[must_use]
-    pub fn is_shadowed_for_light(&self, point: Point, light: &PointLight) -> bool {
-        let v = light.position - point;
-        let distance = v.magnitude();
-        let direction = v.normalize();
+    pub fn is_shadowed_for_light(&self, point: Point, light: &dyn Light) -> bool {
+        self.intensity_at(point, light) <= 0.0
+    }
+
+    /// Returns `true` if an object lies between `point` and `light_position`.
+    #[must_use]
+    pub fn is_shadowed(&self, point: Point, light_position: Point) -> bool {
+        let distance = (light_position - point).magnitude();
+        self.is_occluded(point, light_position, distance)
+    }
+
+    /// Returns `true` if an object lies within `distance` of `point` in the
+    /// direction of `toward`. The distance may be infinite.
+    fn is_occluded(&self, point: Point, toward: Point, distance: f32) -> bool {
+        let direction = (toward - point).normalize();
 
         let ray = ray(point, direction);
         let xs = self.intersect(ray);
@@ -105,36 +192,79 @@ impl World {
         }
     }
 
+    /// Returns the fraction of `light`'s sample points that are visible from
+    /// `point`, from `0.0` when fully shadowed to `1.0` when fully lit. A
+    /// light with no sample points gives `0.0`.
     #[must_use]
-    pub fn color_at(&self, ray: Ray, remaining: usize) -> Color {
-        let xs = self.intersect(ray);
-        if let Some(i) = hit(xs.clone()) {
-            let comps = i.prepare_computations(ray, &xs);
-            self.shade_hit(&comps, remaining)
-        } else {
-            BLACK
+    pub fn intensity_at(&self, point: Point, light: &dyn Light) -> f32 {
+        let mut count = 0usize;
+        let mut visible = 0usize;
+        light.for_each_sample(point, &mut |sample| {
+            count += 1;
+            if !self.is_occluded(point, sample, light.shadow_distance(point, sample)) {
+                visible += 1;
+            }
+        });
+        if count == 0 {
+            return 0.0;
         }
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = visible as f32 / count as f32;
+        fraction
+    }
+
+    /// Returns the color seen along `ray`, following reflections and
+    /// refractions as far as `depth` allows.
+    #[must_use]
+    pub fn color_at(&self, ray: Ray, depth: impl Into<TraceDepth>) -> Color {
+        self.color_and_aov_at(ray, depth, false).0
+    }
+
+    /// Like `color_at`, but when `aov` is set also returns the surface data
+    /// at the first hit for the AOV buffers.
+    pub(crate) fn color_and_aov_at(
+        &self,
+        ray: Ray,
+        depth: impl Into<TraceDepth>,
+        aov: bool,
+    ) -> (Color, Option<AovSample>) {
+        let xs = self.intersect(ray);
+        let Some(i) = hit(xs.clone()) else {
+            return (BLACK, None);
+        };
+        let comps = i.prepare_computations(ray, &xs);
+        let sample = aov.then(|| AovSample::from_computations(&comps));
+        (self.shade_hit(&comps, depth), sample)
     }
 
     #[must_use]
-    pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
+    pub fn reflected_color(&self, comps: &Computations, depth: impl Into<TraceDepth>) -> Color {
         let inner = comps.object.inner();
-        if remaining == 0 || inner.material.reflective.abs() < EPSILON {
-            BLACK
-        } else {
-            let reflective = inner.material.reflective;
-            drop(inner);
-            let reflect_ray = ray(comps.over_point, comps.reflectv);
-            self.color_at(reflect_ray, remaining - 1) * reflective
+        let reflective = inner.material.reflective;
+        drop(inner);
+        if reflective.abs() < EPSILON {
+            return BLACK;
         }
+        let Some(depth) = depth.into().reflected(reflective) else {
+            return BLACK;
+        };
+
+        let reflect_ray = ray(comps.over_point, comps.reflectv);
+        self.color_at(reflect_ray, depth) * reflective
     }
 
     #[must_use]
-    pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
+    pub fn refracted_color(&self, comps: &Computations, depth: impl Into<TraceDepth>) -> Color {
         let inner = comps.object.inner();
-        if remaining == 0 || relative_eq!(inner.material.transparency, 0.0, epsilon = EPSILON) {
+        let transparency = inner.material.transparency;
+        drop(inner);
+        if relative_eq!(transparency, 0.0, epsilon = EPSILON) {
             return BLACK;
         }
+        let Some(depth) = depth.into().refracted(transparency) else {
+            return BLACK;
+        };
 
         let n_ratio = comps.n1 / comps.n2;
         let cos_i = comps.eyev.dot(&comps.normalv);
@@ -147,24 +277,29 @@ impl World {
         let cos_t = (1.0 - sin2_t).sqrt();
         let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
 
-        let transparency = inner.material.transparency;
-        drop(inner);
-
         let refract_ray = ray(comps.under_point, direction);
-        self.color_at(refract_ray, remaining - 1) * transparency
+        self.color_at(refract_ray, depth) * transparency
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::slice;
+    use std::{
+        f32::consts::{FRAC_PI_4, FRAC_PI_6},
+        slice,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
 
     use approx::assert_relative_eq;
 
     use super::*;
     use crate::{
-        EPSILON, Material, color, intersection, pattern::test_pattern, point, point_light, ray,
-        shape::plane, transform, vector,
+        EPSILON, Material, area_light, color, directional_light, intersection,
+        pattern::test_pattern, point, point_light, ray, shape::plane, spot_light, trace_depth,
+        transform, vector,
     };
 
     #[test]
@@ -176,7 +311,6 @@ mod tests {
 
     #[test]
     fn default_world_configuration() {
-        let light = point_light(point(-10, 10, -10), color(1, 1, 1));
         let s1_material = Material::builder()
             .color(color(0.8, 1.0, 0.6))
             .diffuse(0.7)
@@ -185,7 +319,12 @@ mod tests {
 
         let w = default_world();
 
-        assert_eq!(w.lights, vec![light]);
+        assert_eq!(w.lights.len(), 1);
+        assert_eq!(
+            w.lights[0].sample_points(point(0, 0, 0)),
+            vec![point(-10, 10, -10)]
+        );
+        assert_eq!(w.lights[0].intensity_at(point(0, 0, 0)), color(1, 1, 1));
         assert_eq!(w.objects.len(), 2);
         assert_eq!(w.objects[0].inner().material, s1_material);
         assert_eq!(
@@ -206,6 +345,57 @@ mod tests {
         assert_relative_eq!(xs[3].time, 6.0, epsilon = EPSILON);
     }
 
+    #[test]
+    fn intersect_world_with_bvh() {
+        let mut w = default_world();
+        w.objects
+            .push(sphere().transform(transform::translation(10, 0, 0)).build());
+        w.build_bvh();
+        let r = ray(point(0, 0, -5), vector(0, 0, 1));
+        let xs = w.intersect(r);
+        assert_eq!(xs.len(), 4);
+        assert_relative_eq!(xs[0].time, 4.0, epsilon = EPSILON);
+        assert_relative_eq!(xs[1].time, 4.5, epsilon = EPSILON);
+        assert_relative_eq!(xs[2].time, 5.5, epsilon = EPSILON);
+        assert_relative_eq!(xs[3].time, 6.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn changing_objects_after_building_bvh_falls_back_to_every_object() {
+        let mut w = default_world();
+        w.build_bvh();
+        w.objects
+            .push(sphere().transform(transform::translation(0, 0, 2)).build());
+        let r = ray(point(0, 0, -5), vector(0, 0, 1));
+        assert_eq!(w.intersect(r).len(), 6);
+
+        w.objects.truncate(1);
+        let xs = w.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_relative_eq!(xs[0].time, 4.0, epsilon = EPSILON);
+        assert_relative_eq!(xs[1].time, 6.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn replacing_objects_after_building_bvh_falls_back_to_every_object() {
+        let mut w = default_world();
+        w.build_bvh();
+        let r = ray(point(0, 5, -5), vector(0, 0, 1));
+        w.objects[1] = sphere().transform(transform::translation(0, 5, 0)).build();
+        assert_eq!(w.intersect(r).len(), 2);
+
+        w.objects.pop();
+        w.objects
+            .push(sphere().transform(transform::translation(0, 5, 2)).build());
+        let xs = w.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_relative_eq!(xs[0].time, 6.0, epsilon = EPSILON);
+
+        w.build_bvh();
+        w.objects.swap(0, 1);
+        assert_eq!(w.intersect(r).len(), 2);
+    }
+
     #[test]
     fn shading_an_intersection() {
         let w = default_world();
@@ -222,7 +412,7 @@ mod tests {
     #[test]
     fn shading_an_intersection_from_the_inside() {
         let mut w = default_world();
-        w.lights = vec![point_light(point(0, 0.25, 0), color(1, 1, 1))];
+        w.lights = vec![point_light(point(0, 0.25, 0), color(1, 1, 1)).into()];
         let r = ray(point(0, 0, 0), vector(0, 0, 1));
         let shape = w.objects[1].clone();
         let i = intersection(0.5, shape);
@@ -265,28 +455,28 @@ mod tests {
     fn no_shadow_when_nothing_is_collinear_with_point_and_light() {
         let w = default_world();
         let p = point(0, 10, 0);
-        assert!(!w.is_shadowed_for_light(p, &w.lights[0]));
+        assert!(!w.is_shadowed_for_light(p, w.lights[0].as_ref()));
     }
 
     #[test]
     fn shadow_when_object_is_between_point_and_light() {
         let w = default_world();
         let p = point(10, -10, 10);
-        assert!(w.is_shadowed_for_light(p, &w.lights[0]));
+        assert!(w.is_shadowed_for_light(p, w.lights[0].as_ref()));
     }
 
     #[test]
     fn no_shadow_when_object_is_behind_light() {
         let w = default_world();
         let p = point(-20, 20, -20);
-        assert!(!w.is_shadowed_for_light(p, &w.lights[0]));
+        assert!(!w.is_shadowed_for_light(p, w.lights[0].as_ref()));
     }
 
     #[test]
     fn no_shadow_when_object_is_behind_point() {
         let w = default_world();
         let p = point(-2, 2, -2);
-        assert!(!w.is_shadowed_for_light(p, &w.lights[0]));
+        assert!(!w.is_shadowed_for_light(p, w.lights[0].as_ref()));
     }
 
     #[test]
@@ -294,7 +484,7 @@ mod tests {
         let s1 = sphere().build();
         let s2 = sphere().transform(transform::translation(0, 0, 10)).build();
         let w = World::builder()
-            .lights(vec![point_light(point(0, 0, -10), color(1, 1, 1))])
+            .lights(vec![point_light(point(0, 0, -10), color(1, 1, 1)).into()])
             .objects(vec![s1, s2])
             .build();
         let r = ray(point(0, 0, 5), vector(0, 0, 1));
@@ -359,7 +549,7 @@ mod tests {
     #[test]
     fn color_at_with_mutually_reflective_surfaces() {
         let w = World::builder()
-            .lights(vec![point_light(point(0, 0, 0), color(1, 1, 1))])
+            .lights(vec![point_light(point(0, 0, 0), color(1, 1, 1)).into()])
             .objects(vec![
                 plane()
                     .material(Material::builder().reflective(1.0))
@@ -392,6 +582,58 @@ mod tests {
         assert_eq!(c, color(0, 0, 0));
     }
 
+    #[test]
+    fn reflected_color_respects_separate_limits_and_cutoff() {
+        let sqrt2_over_2 = 2.0_f32.sqrt() / 2.0;
+        let mut w = default_world();
+        let shape = plane()
+            .material(Material::builder().reflective(0.5))
+            .transform(transform::translation(0, -1, 0))
+            .build();
+        w.objects.push(shape.clone());
+        let r = ray(point(0, 0, -3), vector(0.0, -sqrt2_over_2, sqrt2_over_2));
+        let i = intersection(2.0_f32.sqrt(), shape);
+        let comps = i.prepare_computations(r, slice::from_ref(&i));
+
+        let no_refraction = trace_depth().refraction(0).build();
+        assert_eq!(
+            w.reflected_color(&comps, no_refraction),
+            w.reflected_color(&comps, 5)
+        );
+        let faint = trace_depth().cutoff(0.6).build();
+        assert_eq!(w.reflected_color(&comps, faint), color(0, 0, 0));
+    }
+
+    #[test]
+    fn reflections_and_refractions_share_the_depth_budget() {
+        let glass = plane()
+            .material(
+                Material::builder()
+                    .reflective(0.5)
+                    .transparency(0.5)
+                    .refractive_index(1.0),
+            )
+            .build();
+        let mirror = |reflective| {
+            plane()
+                .transform(transform::translation(0, -1, 0))
+                .material(Material::builder().reflective(reflective))
+                .build()
+        };
+        let world = |reflective| {
+            World::builder()
+                .lights(vec![point_light(point(-10, 10, -10), WHITE).into()])
+                .objects(vec![glass.clone(), mirror(reflective)])
+                .build()
+        };
+        let (matte, shiny) = (world(0.0), world(0.5));
+        let r = ray(point(0, 1, 0), vector(0, -1, 0));
+
+        // One bounce refracts through the glass, leaving none for the mirror.
+        assert_eq!(matte.color_at(r, 1), shiny.color_at(r, 1));
+        assert_ne!(matte.color_at(r, 2), shiny.color_at(r, 2));
+    }
+
     #[test]
     fn refracted_color_with_opaque_surface() {
         let w = default_world();
@@ -511,20 +753,23 @@ mod tests {
         let light1 = point_light(point(-10, 10, -10), WHITE);
         let light2 = point_light(point(10, 10, -10), color(0.5, 0.5, 0.5));
         let w = World::builder()
-            .lights(vec![light1, light2])
+            .lights(vec![light1.into(), light2.into()])
             .objects(vec![])
             .build();
+        let p = point(0, 0, 0);
         assert_eq!(w.lights.len(), 2);
-        assert_eq!(w.lights[0], light1);
-        assert_eq!(w.lights[1], light2);
+        assert_eq!(w.lights[0].sample_points(p), vec![light1.position]);
+        assert_eq!(w.lights[0].intensity_at(p), light1.intensity);
+        assert_eq!(w.lights[1].sample_points(p), vec![light2.position]);
+        assert_eq!(w.lights[1].intensity_at(p), light2.intensity);
     }
 
     #[test]
     fn shading_with_multiple_lights() {
         let w = World::builder()
             .lights(vec![
-                point_light(point(-10, 10, -10), WHITE),
-                point_light(point(10, 10, -10), color(0.5, 0.5, 0.5)),
+                point_light(point(-10, 10, -10), WHITE).into(),
+                point_light(point(10, 10, -10), color(0.5, 0.5, 0.5)).into(),
             ])
             .objects(vec![
                 sphere()
@@ -554,7 +799,7 @@ mod tests {
         let blocker = sphere().transform(transform::translation(5, 5, -5)).build();
         let target = sphere().build();
         let w = World::builder()
-            .lights(vec![light1, light2])
+            .lights(vec![light1.into(), light2.into()])
             .objects(vec![blocker, target.clone()])
             .build();
         let r = ray(point(0, 0, -5), vector(0, 0, 1));
@@ -563,4 +808,321 @@ mod tests {
         let c = w.shade_hit(&comps, 5);
         assert!(c.red() > 0.1);
     }
+
+    #[test]
+    fn is_shadowed_tests_for_occlusion_between_two_points() {
+        let w = default_world();
+        let light_position = point(-10, -10, -10);
+
+        let test_cases = [
+            (point(-10, -10, 10), false),
+            (point(10, 10, 10), true),
+            (point(-20, -20, -20), false),
+            (point(-5, -5, -5), false),
+        ];
+        for (p, expected) in test_cases {
+            assert_eq!(w.is_shadowed(p, light_position), expected);
+        }
+    }
+
+    #[test]
+    fn point_lights_evaluate_light_intensity_at_given_point() {
+        let w = default_world();
+        let light = w.lights[0].as_ref();
+
+        let test_cases = [
+            (point(0, 1.0001, 0), 1.0),
+            (point(-1.0001, 0, 0), 1.0),
+            (point(0, 0, -1.0001), 1.0),
+            (point(0, 0, 1.0001), 0.0),
+            (point(1.0001, 0, 0), 0.0),
+            (point(0, -1.0001, 0), 0.0),
+            (point(0, 0, 0), 0.0),
+        ];
+        for (p, expected) in test_cases {
+            assert_relative_eq!(w.intensity_at(p, light), expected, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn area_lights_evaluate_light_intensity_at_given_point() {
+        let w = default_world();
+        let light = area_light(
+            point(-0.5, -0.5, -5),
+            vector(1, 0, 0),
+            2,
+            vector(0, 1, 0),
+            2,
+            WHITE,
+        )
+        .jitter(false)
+        .build();
+
+        let test_cases = [
+            (point(0, 0, 2), 0.0),
+            (point(1, -1, 2), 0.25),
+            (point(1.5, 0, 2), 0.5),
+            (point(1.25, 1.25, 3), 0.75),
+            (point(0, 0, -2), 1.0),
+        ];
+        for (p, expected) in test_cases {
+            assert_relative_eq!(w.intensity_at(p, &light), expected, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn area_light_is_shadowed_only_when_every_sample_is_blocked() {
+        let w = default_world();
+        let light = area_light(
+            point(-0.5, -0.5, -5),
+            vector(1, 0, 0),
+            2,
+            vector(0, 1, 0),
+            2,
+            WHITE,
+        )
+        .jitter(false)
+        .build();
+
+        assert!(w.is_shadowed_for_light(point(0, 0, 2), &light));
+        assert!(!w.is_shadowed_for_light(point(1.5, 0, 2), &light));
+    }
+
+    #[test]
+    fn area_light_casts_soft_shadow_edges() {
+        let floor = plane().build();
+        let blocker = sphere().transform(transform::translation(0, 2, 0)).build();
+        let light = area_light(
+            point(-2, 5, -2),
+            vector(4, 0, 0),
+            4,
+            vector(0, 0, 4),
+            4,
+            WHITE,
+        )
+        .build();
+        let w = World::builder()
+            .lights(vec![light.into()])
+            .objects(vec![floor, blocker])
+            .build();
+
+        let umbra = w.intensity_at(point(0, EPSILON, 0), w.lights[0].as_ref());
+        let penumbra = w.intensity_at(point(1.5, EPSILON, 0), w.lights[0].as_ref());
+        let lit = w.intensity_at(point(10, EPSILON, 0), w.lights[0].as_ref());
+        assert_relative_eq!(umbra, 0.0, epsilon = EPSILON);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_relative_eq!(lit, 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn spot_light_only_lights_points_inside_its_cone() {
+        let floor = plane().build();
+        let light = spot_light(point(0, 10, 0), vector(0, -1, 0), WHITE)
+            .inner_angle(FRAC_PI_6 / 2.0)
+            .outer_angle(FRAC_PI_6)
+            .build();
+        let w = World::builder()
+            .lights(vec![light.into()])
+            .objects(vec![floor])
+            .build();
+
+        let inside = w.color_at(ray(point(0, 5, 0), vector(0, -1, 0)), 5);
+        let outside = w.color_at(ray(point(20, 5, 0), vector(0, -1, 0)), 5);
+        assert_relative_eq!(inside.red(), 1.9, epsilon = EPSILON);
+        assert_relative_eq!(outside.red(), 0.1, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn spot_light_casts_shadows() {
+        let floor = plane().build();
+        let blocker = sphere().transform(transform::translation(0, 5, 0)).build();
+        let light = spot_light(point(0, 10, 0), vector(0, -1, 0), WHITE)
+            .inner_angle(FRAC_PI_6)
+            .outer_angle(FRAC_PI_4)
+            .build();
+        let w = World::builder()
+            .lights(vec![light.into()])
+            .objects(vec![floor, blocker])
+            .build();
+
+        assert!(w.is_shadowed_for_light(point(0, EPSILON, 0), &light));
+        assert!(!w.is_shadowed_for_light(point(3, EPSILON, 0), &light));
+    }
+
+    #[test]
+    fn directional_light_shadows_do_not_stop_at_a_distance() {
+        let blocker = sphere()
+            .transform(transform::translation(0, 1000, 0))
+            .build();
+        let light = directional_light(vector(0, -1, 0), WHITE);
+        let w = World::builder()
+            .lights(vec![light.into()])
+            .objects(vec![blocker])
+            .build();
+
+        assert!(w.is_shadowed_for_light(point(0, 0, 0), &light));
+        assert!(!w.is_shadowed_for_light(point(5, 0, 0), &light));
+        assert_relative_eq!(
+            w.intensity_at(point(0, 0, 0), &light),
+            0.0,
+            epsilon = EPSILON
+        );
+    }
+
+    #[test]
+    fn directional_light_shades_with_constant_light_vector() {
+        let floor = plane().build();
+        let light = directional_light(vector(0, -1, 0), WHITE);
+        let w = World::builder()
+            .lights(vec![light.into()])
+            .objects(vec![floor])
+            .build();
+
+        let near = w.color_at(ray(point(0, 5, 0), vector(0, -1, 0)), 5);
+        let far = w.color_at(ray(point(1000, 5, 0), vector(0, -1, 0)), 5);
+        assert_relative_eq!(near.red(), 1.9, epsilon = EPSILON);
+        assert_relative_eq!(far.red(), 1.9, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn world_accepts_custom_light_types() {
+        struct TwoBulbs {
+            left: Point,
+            right: Point,
+        }
+
+        impl Light for TwoBulbs {
+            fn for_each_sample(&self, _point: Point, f: &mut dyn FnMut(Point)) {
+                f(self.left);
+                f(self.right);
+            }
+
+            fn intensity_at(&self, _point: Point) -> Color {
+                WHITE
+            }
+        }
+
+        let blocker = sphere().transform(transform::translation(-2, 5, 0)).build();
+        let w = World::builder()
+            .lights(vec![
+                TwoBulbs {
+                    left: point(-4, 10, 0),
+                    right: point(4, 10, 0),
+                }
+                .into(),
+            ])
+            .objects(vec![blocker])
+            .build();
+
+        let p = point(0, 0, 0);
+        assert_relative_eq!(
+            w.intensity_at(p, w.lights[0].as_ref()),
+            0.5,
+            epsilon = EPSILON
+        );
+        assert!(!w.is_shadowed_for_light(p, w.lights[0].as_ref()));
+    }
+
+    #[test]
+    fn shading_visits_light_samples_once() {
+        struct Counted {
+            position: Point,
+            visits: Arc<AtomicUsize>,
+        }
+
+        impl Light for Counted {
+            fn for_each_sample(&self, _point: Point, f: &mut dyn FnMut(Point)) {
+                self.visits.fetch_add(1, Ordering::Relaxed);
+                f(self.position);
+            }
+
+            fn intensity_at(&self, _point: Point) -> Color {
+                WHITE
+            }
+        }
+
+        let visits = Arc::new(AtomicUsize::new(0));
+        let mut w = default_world();
+        w.lights = vec![
+            Counted {
+                position: point(-10, 10, -10),
+                visits: Arc::clone(&visits),
+            }
+            .into(),
+        ];
+        let r = ray(point(0, 0, -5), vector(0, 0, 1));
+        let i = intersection(4, w.objects[0].clone());
+        let comps = i.prepare_computations(r, slice::from_ref(&i));
+        let c = w.shade_hit(&comps, 5);
+        assert_relative_eq!(c.red(), 0.38066, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.47583, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 0.2855, epsilon = EPSILON);
+        assert_eq!(visits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn lights_without_samples_contribute_nothing() {
+        struct Unlit;
+
+        impl Light for Unlit {
+            fn for_each_sample(&self, _point: Point, _f: &mut dyn FnMut(Point)) {}
+
+            fn intensity_at(&self, _point: Point) -> Color {
+                WHITE
+            }
+        }
+
+        let mut w = default_world();
+        w.lights = vec![Unlit.into()];
+        let p = point(0, 0, -1);
+        assert_relative_eq!(w.intensity_at(p, w.lights[0].as_ref()), 0.0);
+        assert!(w.is_shadowed_for_light(p, w.lights[0].as_ref()));
+
+        let c = w.color_at(ray(point(0, 0, -5), vector(0, 0, 1)), 5);
+        assert_relative_eq!(c.red(), 0.08, epsilon = EPSILON);
+        assert_relative_eq!(c.green(), 0.1, epsilon = EPSILON);
+        assert_relative_eq!(c.blue(), 0.06, epsilon = EPSILON);
+
+        let m = crate::material();
+        let object = sphere().build();
+        let contribution =
+            m.lighting_contribution(&object, &Unlit, p, vector(0, 0, -1), vector(0, 0, -1), 1.0);
+        assert_eq!(contribution, BLACK);
+    }
+
+    #[test]
+    fn object_ids_number_every_primitive() {
+        let mut w = default_world();
+        let (first, second) = (sphere().build(), sphere().build());
+        let g = crate::group().build();
+        g.add_child(first.clone());
+        g.add_child(second.clone());
+        w.objects.push(g.clone());
+
+        let ids = w.object_ids();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids.get(&w.objects[0]), Some(&1));
+        assert_eq!(ids.get(&w.objects[1]), Some(&2));
+        assert_eq!(ids.get(&first), Some(&3));
+        assert_eq!(ids.get(&second), Some(&4));
+        assert_eq!(ids.get(&g), None);
+    }
+
+    #[test]
+    fn object_ids_follow_the_current_objects() {
+        let mut w = default_world();
+        assert_eq!(w.object_ids().get(&w.objects[1]), Some(&2));
+
+        let added = sphere().build();
+        w.objects.insert(0, added.clone());
+        let ids = w.object_ids();
+        assert_eq!(ids.get(&added), Some(&1));
+        assert_eq!(ids.get(&w.objects[2]), Some(&3));
+
+        let replaced = w.objects[1].clone();
+        w.objects[1] = sphere().build();
+        let ids = w.object_ids();
+        assert_eq!(ids.get(&replaced), None);
+        assert_eq!(ids.get(&w.objects[1]), Some(&2));
+    }
 }